@@ -1,17 +1,28 @@
-const API = "/api";
+// Relative, so the dashboard also works under BASE_PATH
+const API = "api";
 
 // ─── State ───────────────────────────────────────────────────────────────
 let tunnels = [];
 let suggestions = [];
+let selectedTemplateId = null;
+let discovery = null;
+let discovering = false;
 let i18nStrings = {};
 let i18nLanguages = [];
 let currentLang = "en";
 let tailscaleHostname = "tailscale-discloud";
+let defaultListenAddress = "all";
+let tailscaleStatus = null;
+let peers = [];
+let tailscaleError = null;
 
 // ─── DOM Cache ───────────────────────────────────────────────────────────
 const $ = (id) => document.getElementById(id);
 
 const dom = {
+  // Tailscale
+  tailscaleBanner: $("tailscale-banner"),
+
   // Stats
   statTotal: $("stat-total"),
   statActive: $("stat-active"),
@@ -24,6 +35,12 @@ const dom = {
   inputLocalPort: $("input-local-port"),
   inputTargetHost: $("input-target-host"),
   inputTargetPort: $("input-target-port"),
+  inputListenAddress: $("input-listen-address"),
+  inputServiceType: $("input-service-type"),
+  inputAllowedSources: $("input-allowed-sources"),
+  inputServeMode: $("input-serve-mode"),
+  inputServePort: $("input-serve-port"),
+  inputServePath: $("input-serve-path"),
   inputEnabled: $("input-enabled"),
   formSubmitBtn: $("form-submit-btn"),
   formCancelBtn: $("form-cancel-btn"),
@@ -31,17 +48,26 @@ const dom = {
   // Suggestions
   suggestionsSection: $("suggestions-section"),
   suggestionsGrid: $("suggestions-grid"),
+  discoverBtn: $("discover-btn"),
+  discoveryStatus: $("discovery-status"),
+  discoveryGrid: $("discovery-grid"),
 
   // Tunnel table
   emptyState: $("empty-state"),
   tableContainer: $("table-container"),
   tunnelsTbody: $("tunnels-tbody"),
+  peersEmpty: $("peers-empty"),
+  peersContainer: $("peers-container"),
+  peersTbody: $("peers-tbody"),
 
   // Test modal
   testOverlay: $("test-overlay"),
   testModalTitle: $("test-modal-title"),
   testModalClose: $("test-modal-close"),
   testModalDone: $("test-modal-done"),
+  loginOverlay: $("login-overlay"),
+  loginModalClose: $("login-modal-close"),
+  loginContainer: $("login-container"),
   testResultContainer: $("test-result-container"),
 
   // Edit modal
@@ -53,6 +79,13 @@ const dom = {
   editLocalPort: $("edit-local-port"),
   editTargetHost: $("edit-target-host"),
   editTargetPort: $("edit-target-port"),
+  editListenAddress: $("edit-listen-address"),
+  editServiceType: $("edit-service-type"),
+  editAllowedSources: $("edit-allowed-sources"),
+  editServeMode: $("edit-serve-mode"),
+  editServePort: $("edit-serve-port"),
+  editServePath: $("edit-serve-path"),
+  editFunnel: $("edit-funnel"),
   editEnabled: $("edit-enabled"),
   editCancelBtn: $("edit-cancel-btn"),
   editSubmitBtn: $("edit-submit-btn"),
@@ -82,8 +115,13 @@ document.addEventListener("DOMContentLoaded", async () => {
   await initI18n();
   bindEvents();
   loadConfig();
+  loadTailscaleStatus();
+  setInterval(loadTailscaleStatus, 60000);
+  loadPeers();
+  setInterval(loadPeers, 60000);
   loadSuggestions();
   loadTunnels();
+  connectEvents();
 });
 
 // ═══════════════════════════════════════════════════════════════════════════
@@ -118,7 +156,7 @@ function toggleTheme() {
 
 async function initI18n() {
   try {
-    const res = await fetch("/i18n/index.json");
+    const res = await fetch("i18n/index.json");
     if (!res.ok) throw new Error(`HTTP ${res.status}`);
     const data = await res.json();
     i18nLanguages = data.languages || [];
@@ -160,7 +198,7 @@ function detectBrowserLang() {
 
 async function loadLanguage(code) {
   try {
-    const res = await fetch(`/i18n/${code}.json`);
+    const res = await fetch(`i18n/${code}.json`);
     if (!res.ok) throw new Error(`HTTP ${res.status}`);
     i18nStrings = await res.json();
     currentLang = code;
@@ -229,12 +267,18 @@ function applyI18n() {
 
   document.documentElement.lang = currentLang;
 
+  renderListenOptions(dom.inputListenAddress);
+  renderListenOptions(dom.editListenAddress);
+
   if (tunnels.length > 0 || dom.tunnelsTbody) {
     renderTunnels();
   }
   if (suggestions.length > 0) {
     renderSuggestions();
   }
+  renderDiscovery();
+  renderTailscaleBanner();
+  renderPeers();
 }
 
 function renderLangDropdown() {
@@ -285,6 +329,7 @@ function bindEvents() {
   dom.formCancelBtn.addEventListener("click", resetCreateForm);
 
   dom.refreshBtn.addEventListener("click", loadTunnels);
+  dom.discoverBtn.addEventListener("click", discoverServices);
   dom.themeToggle.addEventListener("click", toggleTheme);
 
   dom.langBtn.addEventListener("click", (e) => {
@@ -304,6 +349,12 @@ function bindEvents() {
   dom.testModalDone.addEventListener("click", () =>
     closeOverlay(dom.testOverlay),
   );
+  dom.loginModalClose.addEventListener("click", () =>
+    closeOverlay(dom.loginOverlay),
+  );
+  dom.loginOverlay.addEventListener("click", (e) => {
+    if (e.target === dom.loginOverlay) closeOverlay(dom.loginOverlay);
+  });
   dom.testOverlay.addEventListener("click", (e) => {
     if (e.target === dom.testOverlay) closeOverlay(dom.testOverlay);
   });
@@ -332,15 +383,43 @@ function bindEvents() {
 // API Helpers
 // ═══════════════════════════════════════════════════════════════════════════
 
-async function api(path, opts = {}) {
+let tokenPrompt = null;
+
+/**
+ * Ask the user for the API token once, even when several requests
+ * are rejected at the same time. Resolves to false when cancelled.
+ */
+function promptForToken() {
+  if (!tokenPrompt) {
+    tokenPrompt = Promise.resolve().then(() => {
+      const token = window.prompt(t("auth.prompt"));
+      tokenPrompt = null;
+      if (!token || !token.trim()) return false;
+      localStorage.setItem("apiToken", token.trim());
+      return true;
+    });
+  }
+  return tokenPrompt;
+}
+
+function authHeaders(headers = {}) {
+  const token = localStorage.getItem("apiToken");
+  if (token) headers.Authorization = `Bearer ${token}`;
+  return headers;
+}
+
+async function api(path, opts = {}, retry = true) {
   const url = `${API}${path}`;
-  const config = {
-    headers: { "Content-Type": "application/json" },
-    ...opts,
-  };
+  const headers = authHeaders({ "Content-Type": "application/json" });
+  const config = { headers, ...opts };
 
   const res = await fetch(url, config);
 
+  if (res.status === 401 && retry) {
+    localStorage.removeItem("apiToken");
+    if (await promptForToken()) return api(path, opts, false);
+  }
+
   if (res.status === 204) return null;
 
   const body = await res.json().catch(() => null);
@@ -370,11 +449,37 @@ async function loadConfig() {
     if (cfg && cfg.version && dom.appVersion) {
       dom.appVersion.textContent = `v${cfg.version}`;
     }
+    if (cfg && cfg.listen_address) {
+      defaultListenAddress = cfg.listen_address;
+      renderListenOptions(dom.inputListenAddress);
+      renderListenOptions(dom.editListenAddress);
+    }
   } catch (err) {
     console.warn("[config] Could not load config:", err);
   }
 }
 
+async function loadTailscaleStatus() {
+  try {
+    tailscaleStatus = await api("/tailscale/status");
+    tailscaleError = null;
+  } catch (err) {
+    tailscaleStatus = null;
+    tailscaleError = err.message;
+  }
+  renderTailscaleBanner();
+}
+
+async function loadPeers() {
+  try {
+    peers = await api("/tailscale/peers");
+  } catch {
+    // The Tailscale banner already reports an unreachable LocalAPI.
+    peers = [];
+  }
+  renderPeers();
+}
+
 async function loadTunnels() {
   try {
     dom.refreshBtn.classList.add("spinning");
@@ -388,14 +493,19 @@ async function loadTunnels() {
   }
 }
 
+/** Insert a tunnel or replace the one with the same id. */
+function upsertTunnel(tun) {
+  const idx = tunnels.findIndex((t) => t.id === tun.id);
+  if (idx === -1) tunnels.push(tun);
+  else tunnels[idx] = tun;
+}
+
 async function loadSuggestions() {
   try {
-    const res = await fetch("/suggestions.json");
-    if (!res.ok) throw new Error(`HTTP ${res.status}`);
-    suggestions = await res.json();
+    suggestions = await api("/templates");
     renderSuggestions();
   } catch (err) {
-    console.warn("[suggestions] Could not load suggestions.json:", err);
+    console.warn("[suggestions] Could not load templates:", err);
     if (dom.suggestionsSection) {
       dom.suggestionsSection.style.display = "none";
     }
@@ -432,7 +542,7 @@ async function onCreateSubmit(e) {
 
     // The backend returns a TunnelResponse with { ...tunnel, connection_url, warning }
     const tunnel = extractTunnel(result);
-    tunnels.push(tunnel);
+    upsertTunnel(tunnel);
     renderTunnels();
     updateStats();
     resetCreateForm();
@@ -459,15 +569,27 @@ async function onCreateSubmit(e) {
 function readCreateForm() {
   return {
     name: dom.inputName.value.trim(),
-    local_port: parseInt(dom.inputLocalPort.value, 10),
+    local_port: dom.inputLocalPort.value.trim()
+      ? parseInt(dom.inputLocalPort.value, 10)
+      : "auto",
     target_host: dom.inputTargetHost.value.trim(),
     target_port: parseInt(dom.inputTargetPort.value, 10),
     enabled: dom.inputEnabled.checked,
+    listen_address: dom.inputListenAddress.value,
+    service_type: dom.inputServiceType.value,
+    template_id: selectedTemplateId,
+    allowed_sources: parseList(dom.inputAllowedSources.value),
+    serve: readServe(
+      dom.inputServeMode,
+      dom.inputServePort,
+      dom.inputServePath,
+    ),
   };
 }
 
 function resetCreateForm() {
   dom.tunnelForm.reset();
+  selectedTemplateId = null;
   dom.inputEnabled.checked = true;
   dom.formCancelBtn.style.display = "none";
 
@@ -528,6 +650,13 @@ function openEditModal(id) {
   dom.editLocalPort.value = tunnel.local_port;
   dom.editTargetHost.value = tunnel.target_host;
   dom.editTargetPort.value = tunnel.target_port;
+  renderListenOptions(dom.editListenAddress, tunnel.listen_address || "");
+  dom.editServiceType.value = tunnel.service_type || "";
+  dom.editAllowedSources.value = (tunnel.allowed_sources || []).join(", ");
+  dom.editServeMode.value = tunnel.serve ? tunnel.serve.mode : "";
+  dom.editServePort.value = (tunnel.serve && tunnel.serve.port) || "";
+  dom.editServePath.value = (tunnel.serve && tunnel.serve.path) || "";
+  renderFunnelOptions(dom.editFunnel, tunnel);
   dom.editEnabled.checked = tunnel.enabled;
 
   openOverlay(dom.editOverlay);
@@ -543,10 +672,25 @@ async function onEditSubmit(e) {
     target_host: dom.editTargetHost.value.trim(),
     target_port: parseInt(dom.editTargetPort.value, 10),
     enabled: dom.editEnabled.checked,
+    listen_address: dom.editListenAddress.value,
+    service_type: dom.editServiceType.value,
+    allowed_sources: parseList(dom.editAllowedSources.value),
+    serve: readServe(dom.editServeMode, dom.editServePort, dom.editServePath),
   };
 
   if (!validateTunnelData(data)) return;
 
+  const funnel = readFunnel(dom.editFunnel);
+  if (
+    funnel.funnel_expires_at &&
+    !confirm(
+      t("confirm.funnel", { name: data.name, hours: dom.editFunnel.value }),
+    )
+  ) {
+    return;
+  }
+  Object.assign(data, funnel);
+
   const btn = dom.editSubmitBtn;
   btn.disabled = true;
   const spinner = document.createElement("div");
@@ -708,20 +852,87 @@ async function testConnection(targetHost, targetPort, btnEl) {
 // Suggestions
 // ═══════════════════════════════════════════════════════════════════════════
 
-function applySuggestion(index) {
-  const s = suggestions[index];
-  if (!s) return;
-
+/** Fill the create form from a template or a discovered service. */
+function fillCreateForm(s) {
   resetCreateForm();
   dom.inputName.value = s.name;
   dom.inputTargetHost.value = s.target_host;
   dom.inputTargetPort.value = s.target_port;
-  dom.inputLocalPort.value = s.target_port;
+  dom.inputLocalPort.value = s.local_port || "";
+  dom.inputServiceType.value = s.service_type || "";
   dom.inputLocalPort.focus();
   dom.inputLocalPort.select();
+  dom.tunnelForm.scrollIntoView({ behavior: "smooth", block: "center" });
+}
 
+function applySuggestion(index) {
+  const s = suggestions[index];
+  if (!s) return;
+  fillCreateForm(s);
+  selectedTemplateId = s.id;
   toast(t("toast.suggestion.applied", { name: s.name }), "info");
-  dom.tunnelForm.scrollIntoView({ behavior: "smooth", block: "center" });
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// VLAN Discovery
+// ═══════════════════════════════════════════════════════════════════════════
+
+async function discoverServices() {
+  if (discovering) return;
+  discovering = true;
+  renderDiscovery();
+  try {
+    discovery = await api("/discovery?refresh=true");
+  } catch (err) {
+    toast(t("toast.discovery.fail", { error: err.message }), "error");
+  } finally {
+    discovering = false;
+    renderDiscovery();
+  }
+}
+
+function applyDiscovered(index) {
+  const s = discovery && discovery.services[index];
+  if (!s) return;
+  fillCreateForm(s);
+  toast(t("toast.discovery.applied", { name: s.name }), "info");
+}
+
+function renderDiscovery() {
+  if (!dom.discoveryGrid) return;
+  dom.discoverBtn.disabled = discovering;
+
+  const services = discovery ? discovery.services : [];
+  const parts = [];
+  if (discovering) {
+    parts.push(t("discovery.scanning"));
+  } else if (!discovery) {
+    parts.push(t("discovery.hint"));
+  } else {
+    parts.push(
+      services.length === 0
+        ? t("discovery.none")
+        : t("discovery.found", { count: services.length }),
+    );
+    if (discovery.subnet) {
+      parts.push(t("discovery.subnet", { subnet: discovery.subnet }));
+    }
+    parts.push(...discovery.errors);
+  }
+  dom.discoveryStatus.textContent = parts.join(" · ");
+
+  dom.discoveryGrid.innerHTML = services
+    .map((s, i) => {
+      const source = t(`discovery.source.${s.source}`);
+      const detail = s.tunnel_id ? t("discovery.tunneled") : s.address;
+      return `
+      <div class="suggestion-card ${s.tunnel_id ? "discovered-tunneled" : ""}" onclick="applyDiscovered(${i})" title="${escAttr(`${s.target_host}:${s.target_port}`)}">
+        <span class="suggestion-name">${esc(s.name)}</span>
+        <span class="suggestion-port">${esc(s.target_host)}:${s.target_port}</span>
+        <span class="suggestion-desc">${esc(`${source} · ${detail}`)}</span>
+      </div>`;
+    })
+    .join("");
 }
 
 // ═══════════════════════════════════════════════════════════════════════════
@@ -750,6 +961,87 @@ function getConnectionUrl(tun) {
   return null;
 }
 
+/**
+ * Fill a listen-address <select> with the default, the three named modes
+ * and, when editing a tunnel bound to an explicit IP, that IP.
+ */
+function renderListenOptions(select, selected = select ? select.value : "") {
+  if (!select) return;
+  const modes = ["all", "tailscale", "loopback"];
+  const options = [
+    {
+      value: "",
+      label: t("form.option.listen.default", {
+        value: modes.includes(defaultListenAddress)
+          ? t(`form.option.listen.${defaultListenAddress}`)
+          : defaultListenAddress,
+      }),
+    },
+    ...modes.map((m) => ({ value: m, label: t(`form.option.listen.${m}`) })),
+  ];
+  if (selected && !modes.includes(selected)) {
+    options.push({ value: selected, label: selected });
+  }
+  select.innerHTML = options
+    .map(
+      (o) =>
+        `<option value="${escAttr(o.value)}" ${o.value === selected ? "selected" : ""}>${esc(o.label)}</option>`,
+    )
+    .join("");
+}
+
+/**
+ * Split a comma- or whitespace-separated input into a list of entries.
+ */
+/** Funnel durations offered in the edit form, in hours. */
+const FUNNEL_HOURS = [1, 4, 24];
+
+/** Fill the Funnel select; an active Funnel can be kept as it is. */
+function renderFunnelOptions(select, tunnel) {
+  const options = [`<option value="">${esc(t("form.funnel.off"))}</option>`];
+  if (tunnel.funnel && tunnel.funnel_expires_at) {
+    const time = new Date(tunnel.funnel_expires_at * 1000).toLocaleString();
+    options.push(
+      `<option value="keep">${esc(t("form.funnel.keep", { time }))}</option>`,
+    );
+  }
+  for (const hours of FUNNEL_HOURS) {
+    options.push(
+      `<option value="${hours}">${esc(t("form.funnel.hours", { hours }))}</option>`,
+    );
+  }
+  select.innerHTML = options.join("");
+  select.value = tunnel.funnel ? "keep" : "";
+}
+
+/** Funnel fields of an update from the Funnel select. */
+function readFunnel(select) {
+  if (!select.value) return { funnel: false };
+  if (select.value === "keep") return { funnel: true };
+  const hours = parseInt(select.value, 10);
+  return {
+    funnel: true,
+    funnel_expires_at: Math.floor(Date.now() / 1000) + hours * 3600,
+  };
+}
+
+/** Tailscale Serve settings from a form, or `null` when off. */
+function readServe(modeEl, portEl, pathEl) {
+  if (!modeEl.value) return null;
+  const serve = { mode: modeEl.value };
+  const port = parseInt(portEl.value, 10);
+  if (port) serve.port = port;
+  if (pathEl.value.trim()) serve.path = pathEl.value.trim();
+  return serve;
+}
+
+function parseList(value) {
+  return value
+    .split(/[\s,]+/)
+    .map((s) => s.trim())
+    .filter(Boolean);
+}
+
 /**
  * Copy text to clipboard and show toast feedback.
  */
@@ -813,13 +1105,26 @@ function renderTunnels() {
         : `<svg width="14" height="14" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2"><polygon points="5 3 19 12 5 21 5 3"/></svg>`;
 
       const connUrl = getConnectionUrl(tun);
-      const connUrlHtml = connUrl
-        ? `<div class="connection-url-cell">
-            <code class="connection-url-text">${esc(connUrl)}</code>
-            <button class="btn-copy" onclick="event.stopPropagation(); copyToClipboard('${escAttr(connUrl)}', this)" title="${escAttr(t("actions.copy"))}">
+      const urlCell = (url, title = "") => `<div class="connection-url-cell">
+            <code class="connection-url-text" title="${escAttr(title)}">${esc(url)}</code>
+            <button class="btn-copy" onclick="event.stopPropagation(); copyToClipboard('${escAttr(url)}', this)" title="${escAttr(t("actions.copy"))}">
               <svg width="14" height="14" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2"><rect x="9" y="9" width="13" height="13" rx="2" ry="2"/><path d="M5 15H4a2 2 0 0 1-2-2V4a2 2 0 0 1 2-2h9a2 2 0 0 1 2 2v1"/></svg>
             </button>
-          </div>`
+          </div>`;
+      const otherUrls = (tun.connection_urls || []).filter(
+        (u) => u.url !== connUrl,
+      );
+      const connStrings = tun.connection_strings || [];
+      const moreCount = otherUrls.length + connStrings.length;
+      const moreUrlsHtml = moreCount
+        ? `<details class="connection-url-more" onclick="event.stopPropagation()">
+            <summary>${esc(t("tunnels.connection.more", { count: moreCount }))}</summary>
+            ${otherUrls.map((u) => urlCell(u.url, t(`tunnels.connection.${u.kind}`))).join("")}
+            ${connStrings.map((c) => urlCell(c.value, t(`tunnels.connection.${c.kind}`))).join("")}
+          </details>`
+        : "";
+      const connUrlHtml = connUrl
+        ? urlCell(connUrl) + moreUrlsHtml
         : `<span class="text-muted">—</span>`;
 
       // Build warning tooltip for persisted warnings (e.g. port closed)
@@ -829,6 +1134,22 @@ function renderTunnels() {
           </span>`
         : "";
 
+      const healthHtml =
+        tun.enabled && (tun.health === "degraded" || tun.health === "down")
+          ? `<span class="tunnel-warning tunnel-health-${tun.health}" title="${escAttr(t(`tunnels.health.${tun.health}`))}">
+            <svg width="14" height="14" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2" stroke-linecap="round" stroke-linejoin="round"><circle cx="12" cy="12" r="10"/><line x1="15" y1="9" x2="9" y2="15"/><line x1="9" y1="9" x2="15" y2="15"/></svg>
+          </span>`
+          : "";
+
+      const publicHtml =
+        tun.enabled && tun.funnel && tun.funnel_expires_at
+          ? `<span class="tunnel-public" title="${escAttr(t("tunnels.publicUntil", { time: new Date(tun.funnel_expires_at * 1000).toLocaleString() }))}">${esc(t("tunnels.public"))}</span>`
+          : "";
+
+      const activeHtml = tun.active_connections
+        ? `<span class="tunnel-connections" title="${escAttr(t("tunnels.connections", { count: tun.active_connections }))}">${tun.active_connections}</span>`
+        : "";
+
       return `
         <tr>
           <td data-label="${escAttr(t("tunnels.col.status"))}">
@@ -838,6 +1159,9 @@ function renderTunnels() {
                 ${esc(badgeText)}
               </span>
               ${warningHtml}
+              ${healthHtml}
+              ${publicHtml}
+              ${activeHtml}
             </div>
           </td>
           <td data-label="${escAttr(t("tunnels.col.name"))}">
@@ -863,6 +1187,9 @@ function renderTunnels() {
               <button class="btn btn-ghost btn-icon" onclick="testConnection('${escAttr(tun.target_host)}', ${tun.target_port}, this)" title="${escAttr(t("actions.test"))}">
                 <svg width="14" height="14" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2"><path d="M22 11.08V12a10 10 0 1 1-5.93-9.14"/><polyline points="22 4 12 14.01 9 11.01"/></svg>
               </button>
+              <button class="btn btn-ghost btn-icon" onclick="showTunnelLogs('${tun.id}')" title="${escAttr(t("actions.logs"))}">
+                <svg width="14" height="14" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2"><path d="M14 2H6a2 2 0 0 0-2 2v16a2 2 0 0 0 2 2h12a2 2 0 0 0 2-2V8z"/><polyline points="14 2 14 8 20 8"/><line x1="16" y1="13" x2="8" y2="13"/><line x1="16" y1="17" x2="8" y2="17"/></svg>
+              </button>
               <button class="btn btn-ghost btn-icon" onclick="openEditModal('${tun.id}')" title="${escAttr(t("actions.edit"))}">
                 <svg width="14" height="14" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2"><path d="M11 4H4a2 2 0 0 0-2 2v14a2 2 0 0 0 2 2h14a2 2 0 0 0 2-2v-7"/><path d="M18.5 2.5a2.121 2.121 0 0 1 3 3L12 15l-4 1 1-4 9.5-9.5z"/></svg>
               </button>
@@ -876,6 +1203,190 @@ function renderTunnels() {
     .join("");
 }
 
+/** Days before key expiry at which the banner starts warning. */
+const KEY_EXPIRY_WARN_DAYS = 7;
+
+/**
+ * Warn when Tailscale is unreachable, logged out, or its node key is
+ * expired or about to expire. Hidden when everything is fine.
+ */
+function renderTailscaleBanner() {
+  const banner = dom.tailscaleBanner;
+  if (!banner) return;
+
+  let html = "";
+  const st = tailscaleStatus;
+  if (tailscaleError) {
+    html = esc(tailscaleError);
+  } else if (st && st.backend_state !== "Running") {
+    html = esc(t("tailscale.banner.loggedOut", { state: st.backend_state }));
+    html += ` <button class="btn btn-primary btn-sm" onclick="startTailscaleLogin()">${esc(t("tailscale.banner.login"))}</button>`;
+  } else if (st && st.key_expiry) {
+    const msLeft = new Date(st.key_expiry).getTime() - Date.now();
+    const days = Math.ceil(msLeft / 86400000);
+    if (msLeft <= 0) {
+      html = esc(t("tailscale.banner.keyExpired"));
+    } else if (days <= KEY_EXPIRY_WARN_DAYS) {
+      html = esc(t("tailscale.banner.keyExpiring", { days }));
+    }
+  }
+
+  banner.innerHTML = html;
+  banner.hidden = !html;
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// Tailnet Devices
+// ═══════════════════════════════════════════════════════════════════════════
+
+/** "Direct (1.2.3.4:41641)", "Relay (fra)" or "Idle". */
+function peerConnectionText(connection, endpoint, relay) {
+  const detail = connection === "direct" ? endpoint : relay;
+  const label = t(`peers.connection.${connection}`);
+  return connection !== "idle" && detail ? `${label} (${detail})` : label;
+}
+
+function renderPeers() {
+  if (!dom.peersTbody) return;
+  dom.peersEmpty.hidden = peers.length > 0;
+  dom.peersContainer.hidden = peers.length === 0;
+
+  dom.peersTbody.innerHTML = peers
+    .map((peer) => {
+      const badgeClass = peer.online ? "badge-online" : "badge-offline";
+      const badgeText = peer.online
+        ? t("tunnels.status.online")
+        : t("tunnels.status.offline");
+      const lastSeen = peer.last_seen
+        ? `<div class="tunnel-id">${esc(t("peers.lastSeen", { time: new Date(peer.last_seen).toLocaleString() }))}</div>`
+        : "";
+      const ip = (peer.ips || [])[0];
+      const pingBtn = ip
+        ? `<button class="btn btn-ghost btn-icon" onclick="pingPeer('${escAttr(ip)}', this)" title="${escAttr(t("peers.ping"))}">
+            <svg width="14" height="14" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2"><polyline points="22 12 18 12 15 21 9 3 6 12 2 12"/></svg>
+          </button>`
+        : "";
+      return `
+        <tr>
+          <td data-label="${escAttr(t("tunnels.col.status"))}">
+            <span class="badge ${badgeClass}">
+              <span class="badge-dot"></span>
+              ${esc(badgeText)}
+            </span>
+          </td>
+          <td data-label="${escAttr(t("peers.col.name"))}">
+            <div class="tunnel-name-group">
+              <div class="tunnel-name">${esc(peer.hostname)}</div>
+              <div class="tunnel-id">${esc(peer.dns_name || ip || "")}</div>
+              ${lastSeen}
+            </div>
+          </td>
+          <td data-label="${escAttr(t("peers.col.os"))}">${esc(peer.os)}</td>
+          <td data-label="${escAttr(t("peers.col.connection"))}">
+            <span class="tunnel-endpoint">${esc(peerConnectionText(peer.connection, peer.endpoint, peer.relay))}</span>
+          </td>
+          <td data-label="${escAttr(t("peers.col.tags"))}">
+            ${(peer.tags || []).map((tag) => `<code>${esc(tag)}</code>`).join(" ") || `<span class="text-muted">—</span>`}
+          </td>
+          <td data-label="${escAttr(t("tunnels.col.actions"))}" style="text-align:right;">
+            <div class="actions-cell">${pingBtn}</div>
+          </td>
+        </tr>`;
+    })
+    .join("");
+}
+
+/** Tailscale-ping a peer and report the result as a toast. */
+async function pingPeer(ip, btnEl) {
+  const original = btnEl.innerHTML;
+  btnEl.disabled = true;
+  btnEl.innerHTML = '<span class="spinner spinner-sm"></span>';
+  try {
+    const result = await api(
+      `/tailscale/peers/${encodeURIComponent(ip)}/ping`,
+      { method: "POST" },
+    );
+    const name = result.node_name || ip;
+    if (result.success) {
+      toast(
+        t("peers.ping.success", {
+          name,
+          ms: result.latency_ms,
+          path: peerConnectionText(
+            result.connection,
+            result.endpoint,
+            result.relay,
+          ),
+        }),
+      );
+    } else {
+      toast(t("peers.ping.fail", { name, error: result.error }), "error");
+    }
+  } catch (err) {
+    toast(t("peers.ping.fail", { name: ip, error: err.message }), "error");
+  } finally {
+    btnEl.innerHTML = original;
+    btnEl.disabled = false;
+  }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// Tailscale Login
+// ═══════════════════════════════════════════════════════════════════════════
+
+/** Milliseconds between login state checks while the login modal is open. */
+const LOGIN_POLL_MS = 3000;
+
+let loginPollTimer = null;
+
+/** Start an interactive login and show its link and QR code until the node runs. */
+async function startTailscaleLogin() {
+  dom.loginContainer.innerHTML = `<div class="spinner"></div>`;
+  openOverlay(dom.loginOverlay);
+  try {
+    renderTailscaleLogin(await api("/tailscale/login", { method: "POST" }));
+  } catch (err) {
+    dom.loginContainer.textContent = t("tailscale.login.fail", {
+      error: err.message,
+    });
+    return;
+  }
+  clearInterval(loginPollTimer);
+  loginPollTimer = setInterval(pollTailscaleLogin, LOGIN_POLL_MS);
+}
+
+async function pollTailscaleLogin() {
+  if (!dom.loginOverlay.classList.contains("active")) {
+    clearInterval(loginPollTimer);
+    return;
+  }
+  try {
+    const login = await api("/tailscale/login");
+    if (login.backend_state === "Running") {
+      clearInterval(loginPollTimer);
+      closeOverlay(dom.loginOverlay);
+      toast(t("toast.tailscale.connected"));
+      loadTailscaleStatus();
+      return;
+    }
+    renderTailscaleLogin(login);
+  } catch {
+    // Transient; try again on the next tick.
+  }
+}
+
+function renderTailscaleLogin(login) {
+  if (!login.auth_url) {
+    dom.loginContainer.innerHTML = `<p class="text-muted">${esc(t("tailscale.login.waiting"))}</p>`;
+    return;
+  }
+  // The QR code is an SVG rendered by our own backend.
+  dom.loginContainer.innerHTML = `
+    <div class="login-qr">${login.auth_qr_svg || ""}</div>
+    <p>${esc(t("tailscale.login.scan"))}</p>
+    <a class="btn btn-primary" href="${escAttr(login.auth_url)}" target="_blank" rel="noopener">${esc(t("tailscale.login.open"))}</a>`;
+}
+
 function renderSuggestions() {
   if (!dom.suggestionsGrid || suggestions.length === 0) {
     if (dom.suggestionsSection) dom.suggestionsSection.style.display = "none";
@@ -885,14 +1396,17 @@ function renderSuggestions() {
   if (dom.suggestionsSection) dom.suggestionsSection.style.display = "block";
 
   dom.suggestionsGrid.innerHTML = suggestions
-    .map(
-      (s, i) => `
-      <div class="suggestion-card" onclick="applySuggestion(${i})" title="${escAttr(s.description)}">
+    .map((s, i) => {
+      const description = s.description_key
+        ? t(s.description_key)
+        : s.description || "";
+      return `
+      <div class="suggestion-card" onclick="applySuggestion(${i})" title="${escAttr(description)}">
         <span class="suggestion-name">${esc(s.name)}</span>
         <span class="suggestion-port">:${s.target_port}</span>
-        <span class="suggestion-desc">${esc(s.description)}</span>
-      </div>`,
-    )
+        <span class="suggestion-desc">${esc(description)}</span>
+      </div>`;
+    })
     .join("");
 }
 
@@ -919,7 +1433,10 @@ function validateTunnelData(data) {
     toast(t("validation.targetHostRequired"), "error");
     return false;
   }
-  if (!data.local_port || data.local_port < 1 || data.local_port > 65535) {
+  if (
+    data.local_port !== "auto" &&
+    (!data.local_port || data.local_port < 1 || data.local_port > 65535)
+  ) {
     toast(t("validation.localPortRange"), "error");
     return false;
   }
@@ -930,6 +1447,151 @@ function validateTunnelData(data) {
   return true;
 }
 
+// ═══════════════════════════════════════════════════════════════════════════
+// Live Events
+// ═══════════════════════════════════════════════════════════════════════════
+
+let events = null;
+
+/**
+ * Subscribe to /api/events so changes made in other sessions (and
+ * connection counts / health) show up without a refresh. EventSource
+ * cannot send headers, so the token goes in the query string.
+ */
+function connectEvents() {
+  const token = localStorage.getItem("apiToken");
+  const query = token ? `?token=${encodeURIComponent(token)}` : "";
+  let reconnecting = false;
+
+  events = new EventSource(`${API}/events${query}`);
+
+  events.onopen = () => {
+    // Changes made while disconnected were missed.
+    if (reconnecting) loadTunnels();
+    reconnecting = false;
+  };
+
+  events.onerror = () => {
+    reconnecting = true;
+    // The browser retries on its own unless the server refused us
+    // (e.g. a 401 before a token was entered).
+    if (events.readyState === EventSource.CLOSED) {
+      setTimeout(connectEvents, 5000);
+    }
+  };
+
+  const on = (type, handler) =>
+    events.addEventListener(type, (e) => {
+      handler(JSON.parse(e.data));
+      renderTunnels();
+      updateStats();
+    });
+
+  on("tunnel_created", (ev) => upsertTunnel(ev.tunnel));
+  on("tunnel_updated", (ev) => upsertTunnel(ev.tunnel));
+  on("tunnel_deleted", (ev) => {
+    tunnels = tunnels.filter((t) => t.id !== ev.id);
+  });
+  on("health_changed", (ev) => {
+    const tun = tunnels.find((t) => t.id === ev.id);
+    if (tun) tun.health = ev.health;
+  });
+  on("connections", (ev) => {
+    const tun = tunnels.find((t) => t.id === ev.id);
+    if (tun) {
+      tun.active_connections = ev.active;
+      tun.rejected_connections = ev.rejected;
+    }
+  });
+  events.addEventListener("resync", () => loadTunnels());
+}
+
+// ═══════════════════════════════════════════════════════════════════════════
+// Tunnel Logs
+// ═══════════════════════════════════════════════════════════════════════════
+
+let logStream = null;
+
+function stopLogStream() {
+  if (logStream) {
+    logStream.abort();
+    logStream = null;
+  }
+}
+
+function formatLogEntry(entry) {
+  const time = new Date(entry.timestamp).toLocaleTimeString();
+  return `${time} ${entry.level.padEnd(5)} ${entry.message}`;
+}
+
+/**
+ * Show a tunnel's recent log lines in the test modal and keep following
+ * new ones until the modal is closed.
+ */
+async function showTunnelLogs(id) {
+  const tun = tunnels.find((t) => t.id === id);
+  if (!tun) return;
+
+  stopLogStream();
+  const titleSpan = dom.testModalTitle.querySelector("[data-i18n]");
+  if (titleSpan) {
+    titleSpan.textContent = t("logs.modal.title", { name: tun.name });
+  }
+  dom.testResultContainer.innerHTML = `<pre class="test-output tunnel-logs">${esc(t("logs.empty"))}</pre>`;
+  const output = dom.testResultContainer.querySelector("pre");
+  openOverlay(dom.testOverlay);
+
+  const controller = new AbortController();
+  logStream = controller;
+  let lines = 0;
+
+  try {
+    const res = await fetch(`${API}/tunnels/${id}/logs?follow=true&lines=200`, {
+      headers: authHeaders(),
+      signal: controller.signal,
+    });
+    if (!res.ok) {
+      const body = await res.json().catch(() => null);
+      throw new Error(body && body.error ? resolveApiMessage(body.error) : `HTTP ${res.status}`);
+    }
+
+    const reader = res.body.getReader();
+    const decoder = new TextDecoder();
+    let buffer = "";
+    for (;;) {
+      const { value, done } = await reader.read();
+      if (done) break;
+      buffer += decoder.decode(value, { stream: true });
+
+      // Server-sent events are separated by a blank line.
+      const events = buffer.split("\n\n");
+      buffer = events.pop();
+      for (const event of events) {
+        const data = event
+          .split("\n")
+          .filter((l) => l.startsWith("data:"))
+          .map((l) => l.slice(5).trim())
+          .join("");
+        if (!data) continue;
+        const atBottom = output.scrollTop + output.clientHeight >= output.scrollHeight - 4;
+        const line = formatLogEntry(JSON.parse(data));
+        output.textContent = lines === 0 ? line : `${output.textContent}\n${line}`;
+        lines++;
+        if (atBottom) output.scrollTop = output.scrollHeight;
+      }
+    }
+  } catch (err) {
+    if (err.name === "AbortError") return;
+    dom.testResultContainer.innerHTML = `
+      <div class="test-status failure">
+        <span class="test-status-icon">✕</span>
+        <span>${esc(err.message)}</span>
+      </div>`;
+  } finally {
+    if (logStream === controller) logStream = null;
+  }
+}
+
 // ═══════════════════════════════════════════════════════════════════════════
 // Modal Helpers
 // ═══════════════════════════════════════════════════════════════════════════
@@ -941,6 +1603,7 @@ function openOverlay(overlay) {
 
 function closeOverlay(overlay) {
   overlay.classList.remove("active");
+  if (overlay === dom.testOverlay) stopLogStream();
   const anyOpen = document.querySelector(".modal-overlay.active");
   if (!anyOpen) {
     document.body.style.overflow = "";
@@ -0,0 +1,265 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::{LazyLock, RwLock};
+
+use axum::{
+    body::Bytes,
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode, Uri},
+    response::{IntoResponse, Response},
+};
+use flate2::{write::GzEncoder, Compression};
+use rust_embed::RustEmbed;
+use tracing::warn;
+
+use crate::config::settings;
+
+/// The dashboard, compiled into the binary.
+#[derive(RustEmbed)]
+#[folder = "public/"]
+struct Embedded;
+
+/// Gzipped copies of embedded files, made on first request.
+static GZIPPED: LazyLock<RwLock<HashMap<String, Bytes>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// Contents of a dashboard file, e.g. `i18n/en.json`: from `PUBLIC_DIR`
+/// when set, otherwise from the binary.
+pub fn read(path: &str) -> Option<Cow<'static, [u8]>> {
+    match &settings().public_dir {
+        Some(dir) => std::fs::read(dir.join(path)).ok().map(Cow::Owned),
+        None => Embedded::get(path).map(|file| file.data),
+    }
+}
+
+/// Whether gzip pays off for a content type.
+fn compressible(mime: &str) -> bool {
+    mime.starts_with("text/")
+        || matches!(
+            mime,
+            "application/javascript" | "application/json" | "image/svg+xml"
+        )
+}
+
+fn gzip(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+fn gzipped(path: &str, data: &[u8]) -> Option<Bytes> {
+    if let Some(compressed) = GZIPPED.read().unwrap().get(path) {
+        return Some(compressed.clone());
+    }
+    let compressed = Bytes::from(
+        gzip(data)
+            .inspect_err(|e| warn!("Could not compress {path}: {e}"))
+            .ok()?,
+    );
+    GZIPPED
+        .write()
+        .unwrap()
+        .insert(path.to_string(), compressed.clone());
+    Some(compressed)
+}
+
+fn accepts_gzip(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|coding| {
+            let mut parts = coding.split(';');
+            let refused = |p: &str| {
+                p.trim()
+                    .strip_prefix("q=")
+                    .and_then(|q| q.parse::<f32>().ok())
+                    == Some(0.0)
+            };
+            parts.next().is_some_and(|c| c.trim() == "gzip") && !parts.any(refused)
+        })
+}
+
+fn not_modified(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|tag| tag.trim() == "*" || tag.trim() == etag)
+}
+
+/// Fallback handler serving the embedded dashboard, with its content type,
+/// an ETag for revalidation and gzip when the client accepts it.
+pub async fn serve(method: Method, uri: Uri, headers: HeaderMap) -> Response {
+    if method != Method::GET && method != Method::HEAD {
+        return StatusCode::METHOD_NOT_ALLOWED.into_response();
+    }
+    let mut path = uri.path().trim_start_matches('/').to_string();
+    if path.is_empty() || path.ends_with('/') {
+        path.push_str("index.html");
+    }
+    let Some(file) = Embedded::get(&path) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let mime = file.metadata.mimetype();
+    let hash = hex::encode(&file.metadata.sha256_hash()[..16]);
+    let compressed = (compressible(mime) && accepts_gzip(&headers))
+        .then(|| gzipped(&path, &file.data))
+        .flatten();
+    let etag = match compressed {
+        Some(_) => format!("\"{hash}-gzip\""),
+        None => format!("\"{hash}\""),
+    };
+
+    let mut response = if not_modified(&headers, &etag) {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        match &compressed {
+            Some(data) => data.clone().into_response(),
+            None => file.data.into_response(),
+        }
+    };
+    let response_headers = response.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(mime) {
+        response_headers.insert(header::CONTENT_TYPE, value);
+    }
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        response_headers.insert(header::ETAG, value);
+    }
+    // Names are not fingerprinted, so always revalidate
+    response_headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    if compressible(mime) {
+        response_headers.insert(header::VARY, HeaderValue::from_static("accept-encoding"));
+    }
+    if compressed.is_some() {
+        response_headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn headers(pairs: &[(header::HeaderName, &str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.clone(), HeaderValue::from_str(value).unwrap()))
+            .collect()
+    }
+
+    async fn get(path: &str, pairs: &[(header::HeaderName, &str)]) -> Response {
+        serve(Method::GET, path.parse().unwrap(), headers(pairs)).await
+    }
+
+    async fn body(response: Response) -> Bytes {
+        axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap()
+    }
+
+    fn etag(response: &Response) -> String {
+        response.headers()[header::ETAG]
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn serves_index_for_the_root() {
+        let response = get("/", &[]).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/html");
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "no-cache");
+        assert_eq!(response.headers()[header::VARY], "accept-encoding");
+        assert!(response.headers().get(header::CONTENT_ENCODING).is_none());
+        let index = Embedded::get("index.html").unwrap();
+        assert_eq!(body(response).await, &index.data[..]);
+    }
+
+    #[tokio::test]
+    async fn gzips_when_accepted() {
+        let response = get("/app.js", &[(header::ACCEPT_ENCODING, "br, gzip;q=0.8")]).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+        assert!(etag(&response).ends_with("-gzip\""));
+
+        let mut plain = Vec::new();
+        flate2::read::GzDecoder::new(&body(response).await[..])
+            .read_to_end(&mut plain)
+            .unwrap();
+        assert_eq!(plain, &Embedded::get("app.js").unwrap().data[..]);
+    }
+
+    #[tokio::test]
+    async fn skips_gzip_when_refused_or_not_worth_it() {
+        for accept in ["identity", "gzip;q=0", "gzip; q=0.0, identity"] {
+            let response = get("/app.js", &[(header::ACCEPT_ENCODING, accept)]).await;
+            assert!(
+                response.headers().get(header::CONTENT_ENCODING).is_none(),
+                "{accept}"
+            );
+        }
+        // Images are already compressed.
+        let response = get("/icon.webp", &[(header::ACCEPT_ENCODING, "gzip")]).await;
+        assert!(response.headers().get(header::CONTENT_ENCODING).is_none());
+        assert!(response.headers().get(header::VARY).is_none());
+    }
+
+    #[tokio::test]
+    async fn matching_if_none_match_is_not_modified() {
+        let tag = etag(&get("/style.css", &[]).await);
+        let response = get("/style.css", &[(header::IF_NONE_MATCH, &tag)]).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(etag(&response), tag);
+        assert!(body(response).await.is_empty());
+
+        let listed = format!("\"stale\", {tag}");
+        let response = get("/style.css", &[(header::IF_NONE_MATCH, &listed)]).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        let response = get("/style.css", &[(header::IF_NONE_MATCH, "*")]).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    }
+
+    #[tokio::test]
+    async fn etag_depends_on_the_encoding() {
+        let plain = etag(&get("/style.css", &[]).await);
+        let gzip = [(header::ACCEPT_ENCODING, "gzip")];
+        let compressed = etag(&get("/style.css", &gzip).await);
+        assert_ne!(plain, compressed);
+
+        // The plain ETag does not validate the gzipped copy.
+        let response = get(
+            "/style.css",
+            &[
+                (header::ACCEPT_ENCODING, "gzip"),
+                (header::IF_NONE_MATCH, &plain),
+            ],
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = get(
+            "/style.css",
+            &[
+                (header::ACCEPT_ENCODING, "gzip"),
+                (header::IF_NONE_MATCH, &compressed),
+            ],
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    }
+
+    #[tokio::test]
+    async fn unknown_paths_and_methods_are_refused() {
+        assert_eq!(
+            get("/missing.js", &[]).await.status(),
+            StatusCode::NOT_FOUND
+        );
+        let response = serve(Method::POST, "/".parse().unwrap(), HeaderMap::new()).await;
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+}
@@ -0,0 +1,72 @@
+use std::net::IpAddr;
+
+use serde::Serialize;
+use tokio::io::AsyncWriteExt;
+use tracing::{error, warn};
+
+use crate::config::settings;
+use crate::models::Tunnel;
+use crate::webhooks::unix_now;
+
+/// One line of the audit log.
+#[derive(Debug, Serialize)]
+struct AuditEntry<'a> {
+    timestamp: u64,
+    /// Client that made the change; `None` for changes made by the manager
+    /// itself, such as an expiry.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client: Option<IpAddr>,
+    action: &'a str,
+    tunnel_id: &'a str,
+    tunnel_name: &'a str,
+    detail: &'a str,
+}
+
+/// Path of the audit log, from `AUDIT_LOG_PATH` (default `./audit.log`).
+fn audit_log_path() -> String {
+    settings().audit_log_path.to_string_lossy().into_owned()
+}
+
+/// Record a sensitive change (e.g. `funnel.enabled`) as a warning and as a
+/// JSON line appended to the audit log.
+pub async fn record(client: Option<IpAddr>, action: &str, tunnel: &Tunnel, detail: &str) {
+    warn!(
+        target: "audit",
+        client = client.map(|c| c.to_string()),
+        action,
+        tunnel.id = %tunnel.id,
+        tunnel.name = %tunnel.name,
+        "{detail}"
+    );
+
+    let entry = AuditEntry {
+        timestamp: unix_now(),
+        client,
+        action,
+        tunnel_id: &tunnel.id,
+        tunnel_name: &tunnel.name,
+        detail,
+    };
+    let mut line = match serde_json::to_string(&entry) {
+        Ok(line) => line,
+        Err(e) => {
+            error!("Failed to serialize audit entry: {e}");
+            return;
+        }
+    };
+    line.push('\n');
+
+    let path = audit_log_path();
+    let result = async {
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await?;
+        file.write_all(line.as_bytes()).await
+    }
+    .await;
+    if let Err(e) = result {
+        error!("Failed to write audit log {path}: {e}");
+    }
+}
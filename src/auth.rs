@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{Extension, Query, Request, State},
+    http::{header, HeaderMap, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+
+use tracing::{info, warn};
+
+use crate::config::Settings;
+use crate::limits::{too_many_requests, RequestClient};
+use crate::routes::api_err;
+
+#[derive(Debug)]
+struct Failures {
+    count: u64,
+    first: Instant,
+    locked_until: Option<Instant>,
+}
+
+/// What an authenticated request may do, set by [`require_token`] as a
+/// request extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    User,
+    /// Presented `ADMIN_TOKEN`; may expose tunnels through Funnel.
+    Admin,
+}
+
+/// Optional bearer-token authentication for the API with lockout after
+/// repeated failures.
+#[derive(Debug)]
+pub struct Auth {
+    token: Option<String>,
+    admin_token: Option<String>,
+    max_failures: u64,
+    window: Duration,
+    lockout: Duration,
+    failures: Mutex<HashMap<IpAddr, Failures>>,
+}
+
+/// Compare two strings without short-circuiting on the first mismatch.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Extract the token from `Authorization: Bearer <token>`.
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
+/// Extract the token from a `?token=` query parameter.  Only honoured on
+/// `GET`, for clients such as `EventSource` that cannot set headers.
+fn query_token(request: &Request) -> Option<String> {
+    if request.method() != Method::GET {
+        return None;
+    }
+    let Query(mut params) = Query::<HashMap<String, String>>::try_from_uri(request.uri()).ok()?;
+    params.remove("token")
+}
+
+impl Auth {
+    /// Build from the environment, which keeps the tokens off the command
+    /// line, and the lockout settings:
+    /// - `API_TOKEN` — required bearer token; authentication is off when unset
+    /// - `ADMIN_TOKEN` — token granting admin actions; also accepted wherever
+    ///   `API_TOKEN` is
+    /// - `AUTH_MAX_FAILURES` — failed attempts before lockout
+    /// - `AUTH_LOCKOUT_SECONDS` — lockout duration and failure window
+    pub fn from_settings(settings: &Settings) -> Self {
+        let read = |name| {
+            std::env::var(name)
+                .ok()
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty())
+        };
+        let token = read("API_TOKEN");
+        let admin_token = read("ADMIN_TOKEN");
+        let max_failures = settings.auth_max_failures;
+        let lockout = Duration::from_secs(settings.auth_lockout_seconds);
+
+        if token.is_some() {
+            info!(
+                "API token required — lockout after {max_failures} failure(s) for {}s",
+                lockout.as_secs()
+            );
+        } else {
+            info!("API_TOKEN not set — API authentication disabled");
+        }
+        if admin_token.is_none() {
+            info!("ADMIN_TOKEN not set — admin actions (Funnel) disabled");
+        }
+
+        Self {
+            token,
+            admin_token,
+            max_failures,
+            window: lockout,
+            lockout,
+            failures: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Remaining lockout for `client`, if any.
+    fn locked_for(&self, client: IpAddr) -> Option<Duration> {
+        self.locked_for_at(client, Instant::now())
+    }
+
+    fn locked_for_at(&self, client: IpAddr, now: Instant) -> Option<Duration> {
+        let failures = self.failures.lock().unwrap();
+        let until = failures.get(&client)?.locked_until?;
+        until.checked_duration_since(now).filter(|d| !d.is_zero())
+    }
+
+    /// Record a failed attempt.  Returns the lockout duration when this
+    /// failure triggered one.
+    fn record_failure(&self, client: IpAddr) -> Option<Duration> {
+        self.record_failure_at(client, Instant::now())
+    }
+
+    fn record_failure_at(&self, client: IpAddr, now: Instant) -> Option<Duration> {
+        let mut failures = self.failures.lock().unwrap();
+        failures.retain(|_, f| {
+            now.duration_since(f.first) < self.window
+                || f.locked_until.is_some_and(|until| until > now)
+        });
+
+        let entry = failures.entry(client).or_insert(Failures {
+            count: 0,
+            first: now,
+            locked_until: None,
+        });
+        if now.duration_since(entry.first) >= self.window {
+            *entry = Failures {
+                count: 0,
+                first: now,
+                locked_until: None,
+            };
+        }
+        entry.count += 1;
+
+        if entry.count >= self.max_failures {
+            entry.locked_until = Some(now + self.lockout);
+            Some(self.lockout)
+        } else {
+            None
+        }
+    }
+
+    fn record_success(&self, client: IpAddr) {
+        self.failures.lock().unwrap().remove(&client);
+    }
+}
+
+/// Middleware requiring `Authorization: Bearer <API_TOKEN>` (or `?token=`
+/// on `GET`) on API routes when a token is configured, and tagging each
+/// request with its [`Role`].  `ADMIN_TOKEN` is accepted too and grants
+/// [`Role::Admin`].  Clients that present a wrong token too often are
+/// locked out and receive `429` until the lockout expires.
+pub async fn require_token(
+    State(auth): State<Arc<Auth>>,
+    Extension(client): Extension<RequestClient>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if auth.token.is_none() && auth.admin_token.is_none() {
+        request.extensions_mut().insert(Role::User);
+        return next.run(request).await;
+    }
+
+    let client = client.ip().await;
+    if let Some(remaining) = auth.locked_for(client) {
+        return too_many_requests("api.error.auth_locked_out", remaining);
+    }
+
+    let token = bearer_token(request.headers())
+        .map(str::to_string)
+        .or_else(|| query_token(&request));
+    let matches = |expected: &Option<String>, token: &str| {
+        expected
+            .as_deref()
+            .is_some_and(|expected| constant_time_eq(token, expected))
+    };
+    match token {
+        Some(token) if matches(&auth.admin_token, &token) => {
+            auth.record_success(client);
+            request.extensions_mut().insert(Role::Admin);
+            next.run(request).await
+        }
+        Some(token) if matches(&auth.token, &token) => {
+            auth.record_success(client);
+            request.extensions_mut().insert(Role::User);
+            next.run(request).await
+        }
+        // Without API_TOKEN, the admin token is only needed for admin actions.
+        None if auth.token.is_none() => {
+            request.extensions_mut().insert(Role::User);
+            next.run(request).await
+        }
+        Some(_) => {
+            let path = request.uri().path().to_string();
+            if let Some(lockout) = auth.record_failure(client) {
+                warn!(
+                    %client,
+                    path,
+                    "Locked out for {}s after repeated invalid tokens",
+                    lockout.as_secs()
+                );
+                return too_many_requests("api.error.auth_locked_out", lockout);
+            }
+            warn!(%client, path, "Invalid API token");
+            unauthorized()
+        }
+        // A missing token is not an attempt — the UI asks for one on 401.
+        None => unauthorized(),
+    }
+}
+
+fn unauthorized() -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        Json(api_err("api.error.unauthorized")),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLIENT: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(100, 64, 0, 1));
+
+    fn auth(max_failures: u64, lockout_secs: u64) -> Auth {
+        let lockout = Duration::from_secs(lockout_secs);
+        Auth {
+            token: Some("secret".to_string()),
+            admin_token: None,
+            max_failures,
+            window: lockout,
+            lockout,
+            failures: Mutex::new(HashMap::new()),
+        }
+    }
+
+    #[test]
+    fn locks_out_after_max_failures_until_it_expires() {
+        let auth = auth(3, 60);
+        let start = Instant::now();
+        assert_eq!(auth.record_failure_at(CLIENT, start), None);
+        assert_eq!(auth.record_failure_at(CLIENT, start), None);
+        assert_eq!(auth.locked_for_at(CLIENT, start), None);
+        assert_eq!(
+            auth.record_failure_at(CLIENT, start),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(
+            auth.locked_for_at(CLIENT, start + Duration::from_secs(20)),
+            Some(Duration::from_secs(40))
+        );
+        assert_eq!(
+            auth.locked_for_at(CLIENT, start + Duration::from_secs(60)),
+            None
+        );
+    }
+
+    #[test]
+    fn failures_outside_the_window_start_over() {
+        let auth = auth(2, 60);
+        let start = Instant::now();
+        assert_eq!(auth.record_failure_at(CLIENT, start), None);
+        let later = start + Duration::from_secs(61);
+        assert_eq!(auth.record_failure_at(CLIENT, later), None);
+        assert!(auth.record_failure_at(CLIENT, later).is_some());
+    }
+
+    #[test]
+    fn success_clears_failures() {
+        let auth = auth(2, 60);
+        let now = Instant::now();
+        assert_eq!(auth.record_failure_at(CLIENT, now), None);
+        auth.record_success(CLIENT);
+        assert_eq!(auth.record_failure_at(CLIENT, now), None);
+    }
+
+    #[test]
+    fn compares_tokens() {
+        assert!(constant_time_eq("secret", "secret"));
+        assert!(!constant_time_eq("secret", "secreT"));
+        assert!(!constant_time_eq("secret", "secret2"));
+    }
+
+    #[test]
+    fn reads_bearer_tokens() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(bearer_token(&headers), None);
+        headers.insert(header::AUTHORIZATION, "Bearer  abc ".parse().unwrap());
+        assert_eq!(bearer_token(&headers), Some("abc"));
+        headers.insert(header::AUTHORIZATION, "Basic abc".parse().unwrap());
+        assert_eq!(bearer_token(&headers), None);
+    }
+}
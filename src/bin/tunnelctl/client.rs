@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use reqwest::{header, Method, RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::sync::OnceCell;
+
+/// Locale used when the requested one has no translations.
+const FALLBACK_LOCALE: &str = "en";
+
+/// Rate-limited requests are retried this often after their `Retry-After`,
+/// so imports of many tunnels go through.
+const RATE_LIMIT_RETRIES: u32 = 5;
+
+/// `{ "error": { "id": ..., "params": ... } }`, as returned by every
+/// failing API call.
+#[derive(Debug, Deserialize)]
+struct ApiErrorResponse {
+    error: ApiMessage,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApiMessage {
+    pub id: String,
+    #[serde(default)]
+    pub params: HashMap<String, Value>,
+}
+
+/// The tunnel manager's HTTP API.
+pub struct Client {
+    http: reqwest::Client,
+    base: String,
+    token: Option<String>,
+    locale: String,
+    /// The server's translations, fetched with the first message to show.
+    catalog: OnceCell<HashMap<String, String>>,
+}
+
+impl Client {
+    pub fn new(base: &str, token: Option<String>, locale: String) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base: base.trim_end_matches('/').to_string(),
+            token,
+            locale,
+            catalog: OnceCell::new(),
+        }
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = self.http.request(method, format!("{}{path}", self.base));
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    /// Send a request to `/api{path}` and return the response, or the
+    /// API's error as a translated message.
+    pub async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&Value>,
+    ) -> Result<Response, String> {
+        let mut retries = 0;
+        loop {
+            let mut request = self.request(method.clone(), &format!("/api{path}"));
+            if let Some(body) = body {
+                request = request.json(body);
+            }
+            let response = request
+                .send()
+                .await
+                .map_err(|e| format!("Could not reach {}: {e}", self.base))?;
+            if response.status().is_success() {
+                return Ok(response);
+            }
+            let retry_after = response
+                .headers()
+                .get(header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok()?.parse::<u64>().ok());
+            match retry_after {
+                Some(secs)
+                    if response.status() == StatusCode::TOO_MANY_REQUESTS
+                        && retries < RATE_LIMIT_RETRIES =>
+                {
+                    retries += 1;
+                    eprintln!("Rate limited — retrying in {secs}s");
+                    tokio::time::sleep(Duration::from_secs(secs)).await;
+                }
+                _ => return Err(self.error(response).await),
+            }
+        }
+    }
+
+    /// [`send`](Self::send) and decode the JSON answer.
+    pub async fn call<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&Value>,
+    ) -> Result<T, String> {
+        let response = self.send(method, path, body).await?;
+        response
+            .json()
+            .await
+            .map_err(|e| format!("Unexpected answer from {path}: {e}"))
+    }
+
+    pub async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, String> {
+        self.call(Method::GET, path, None).await
+    }
+
+    async fn error(&self, response: Response) -> String {
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        match serde_json::from_str::<ApiErrorResponse>(&text) {
+            Ok(body) => self.translate(&body.error).await,
+            Err(_) if status == StatusCode::UNAUTHORIZED => {
+                "Unauthorized — set TUNNELCTL_TOKEN or pass --token".to_string()
+            }
+            Err(_) if text.trim().is_empty() => format!("Request failed with {status}"),
+            Err(_) => text.trim().to_string(),
+        }
+    }
+
+    /// Render an API message with the dashboard's translations, falling
+    /// back to its id.
+    pub async fn translate(&self, message: &ApiMessage) -> String {
+        let catalog = self.catalog.get_or_init(|| self.load_catalog()).await;
+        let template = catalog
+            .get(&message.id)
+            .cloned()
+            .unwrap_or_else(|| message.id.clone());
+        message.params.iter().fold(template, |text, (key, value)| {
+            let value = match value {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            text.replace(&format!("{{{key}}}"), &value)
+        })
+    }
+
+    /// `pt-BR`, then `pt`, then English.
+    async fn load_catalog(&self) -> HashMap<String, String> {
+        let language = self.locale.split('-').next().unwrap_or_default();
+        for locale in [self.locale.as_str(), language, FALLBACK_LOCALE] {
+            let response = self
+                .request(Method::GET, &format!("/i18n/{locale}.json"))
+                .header(header::ACCEPT, "application/json")
+                .send()
+                .await;
+            if let Ok(response) = response.and_then(Response::error_for_status) {
+                if let Ok(catalog) = response.json().await {
+                    return catalog;
+                }
+            }
+        }
+        HashMap::new()
+    }
+}
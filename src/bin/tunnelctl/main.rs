@@ -0,0 +1,884 @@
+//! `tunnelctl` — manage tunnels through the HTTP API, e.g. from CI or a
+//! laptop on the tailnet.
+
+mod client;
+mod output;
+
+use std::io::{IsTerminal, Read};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Args, Parser, Subcommand};
+use reqwest::Method;
+use serde_json::{json, Map, Value};
+
+use crate::client::{ApiMessage, Client};
+use crate::output::{print_json, print_log_entry, print_table, tunnel_row, TUNNEL_HEADERS};
+
+/// Settings `export` writes and `import` applies.  Keys the API fills in
+/// (id, status, connection URLs) are left out, and so are template links,
+/// which may not exist on another server.
+const EXPORTED: [&str; 12] = [
+    "name",
+    "local_port",
+    "target_host",
+    "target_port",
+    "enabled",
+    "allowed_sources",
+    "listen_address",
+    "service_type",
+    "health_check",
+    "serve",
+    "funnel",
+    "funnel_expires_at",
+];
+
+/// Values `import` sends for settings missing from an entry, so updated
+/// tunnels match the file.
+fn cleared(key: &str) -> Option<Value> {
+    Some(match key {
+        "allowed_sources" => json!([]),
+        "listen_address" | "service_type" => json!(""),
+        "health_check" => json!("tcp"),
+        "serve" => Value::Null,
+        "funnel" => json!(false),
+        _ => return None,
+    })
+}
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "tunnelctl",
+    version,
+    about = "Manage tunnels over the tunnel manager's HTTP API"
+)]
+struct Cli {
+    /// Address of the API, including its BASE_PATH.
+    #[arg(
+        long,
+        env = "TUNNELCTL_URL",
+        default_value = "http://127.0.0.1:3000",
+        global = true
+    )]
+    url: String,
+
+    /// The server's API_TOKEN, or ADMIN_TOKEN for Funnel changes.
+    #[arg(long, env = "TUNNELCTL_TOKEN", hide_env_values = true, global = true)]
+    token: Option<String>,
+
+    /// Print the API's JSON instead of tables.
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Language of error messages, e.g. `pt-BR` (default from `LANG`).
+    #[arg(long, env = "TUNNELCTL_LANG", global = true)]
+    lang: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// List tunnels.
+    List,
+    /// Create a tunnel.
+    Create {
+        /// Fill settings left out from this template.
+        #[arg(long)]
+        template: Option<String>,
+        /// Create it without starting it.
+        #[arg(long)]
+        disabled: bool,
+        #[command(flatten)]
+        settings: Settings,
+    },
+    /// Change settings of a tunnel.
+    Update {
+        /// Tunnel id, id prefix or name.
+        tunnel: String,
+        #[command(flatten)]
+        settings: Settings,
+    },
+    /// Start a tunnel.
+    Enable { tunnel: String },
+    /// Stop a tunnel, keeping its settings.
+    Disable { tunnel: String },
+    /// Delete a tunnel.
+    Delete { tunnel: String },
+    /// Check that a target accepts connections: `host:port` or a tunnel.
+    Test { target: String },
+    /// Write the settings of every tunnel as JSON, for `import`.
+    Export {
+        /// File to write instead of standard output.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Create or update tunnels from an `export` file (`-` for standard
+    /// input), matching existing tunnels by name.
+    Import {
+        file: PathBuf,
+        /// Only print what would change.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Print the recent log lines of a tunnel.
+    Logs {
+        tunnel: String,
+        /// Only the last N lines.
+        #[arg(short = 'n', long)]
+        lines: Option<usize>,
+        /// Keep printing new lines.
+        #[arg(short, long)]
+        follow: bool,
+    },
+}
+
+/// Settings shared by `create` and `update`; omitted ones are left alone.
+#[derive(Debug, Args)]
+struct Settings {
+    #[arg(long)]
+    name: Option<String>,
+    /// Local port, or `auto` to pick a free one (create only).
+    #[arg(long, value_parser = parse_local_port)]
+    local_port: Option<Value>,
+    /// Where connections go, as `host:port`.
+    #[arg(long, value_parser = parse_target)]
+    target: Option<(String, u16)>,
+    /// `postgres`, `mysql`, `redis` or `mongodb`; empty for none.
+    #[arg(long)]
+    service_type: Option<String>,
+    /// `all`, `tailscale`, `loopback` or an IP; empty for the default.
+    #[arg(long)]
+    listen_address: Option<String>,
+    /// Tailnet IP, CIDR or `tag:` name allowed to connect; repeatable.
+    #[arg(long = "allow", value_name = "SOURCE")]
+    allowed_sources: Vec<String>,
+    /// Let any client connect again.
+    #[arg(long, conflicts_with = "allowed_sources")]
+    allow_any: bool,
+    /// Periodic health checks.
+    #[arg(long, value_parser = ["tcp", "off"])]
+    health_check: Option<String>,
+}
+
+fn parse_local_port(value: &str) -> Result<Value, String> {
+    if value.eq_ignore_ascii_case("auto") {
+        return Ok(json!("auto"));
+    }
+    value
+        .parse::<u16>()
+        .map(|port| json!(port))
+        .map_err(|_| "expected a port or \"auto\"".to_string())
+}
+
+fn parse_target(value: &str) -> Result<(String, u16), String> {
+    let (host, port) = value
+        .rsplit_once(':')
+        .ok_or_else(|| "expected host:port".to_string())?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let port = port.parse().map_err(|_| format!("invalid port '{port}'"))?;
+    Ok((host.to_string(), port))
+}
+
+impl Settings {
+    fn body(self) -> Map<String, Value> {
+        let mut body = Map::new();
+        if let Some(name) = self.name {
+            body.insert("name".into(), json!(name));
+        }
+        if let Some(port) = self.local_port {
+            body.insert("local_port".into(), port);
+        }
+        if let Some((host, port)) = self.target {
+            body.insert("target_host".into(), json!(host));
+            body.insert("target_port".into(), json!(port));
+        }
+        if let Some(service_type) = self.service_type {
+            body.insert("service_type".into(), json!(service_type));
+        }
+        if let Some(listen_address) = self.listen_address {
+            body.insert("listen_address".into(), json!(listen_address));
+        }
+        if !self.allowed_sources.is_empty() || self.allow_any {
+            body.insert("allowed_sources".into(), json!(self.allowed_sources));
+        }
+        if let Some(health_check) = self.health_check {
+            body.insert("health_check".into(), json!(health_check));
+        }
+        body
+    }
+}
+
+/// `pt-BR` from `LANG=pt_BR.UTF-8`.
+fn locale_from_env() -> String {
+    locale_from(std::env::var("LC_ALL").ok(), std::env::var("LANG").ok())
+}
+
+fn locale_from(lc_all: Option<String>, lang: Option<String>) -> String {
+    lc_all
+        .filter(|lang| !lang.is_empty())
+        .or(lang)
+        .and_then(|lang| {
+            let lang = lang.split(['.', '@']).next()?.replace('_', "-");
+            (!lang.is_empty() && lang != "C" && lang != "POSIX").then_some(lang)
+        })
+        .unwrap_or_else(|| "en".to_string())
+}
+
+/// Names of `import` entries.  Tunnels are matched by name, so entries
+/// without one, or with one used twice, refuse the whole file.
+fn entry_names(entries: &[Map<String, Value>]) -> Result<Vec<String>, String> {
+    let mut names: Vec<String> = Vec::with_capacity(entries.len());
+    for (index, entry) in entries.iter().enumerate() {
+        let name = entry
+            .get("name")
+            .and_then(Value::as_str)
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .ok_or_else(|| format!("Entry {} has no name", index + 1))?;
+        if names.iter().any(|n| n == name) {
+            return Err(format!("'{name}' appears more than once"));
+        }
+        names.push(name.to_string());
+    }
+    Ok(names)
+}
+
+/// The request body for an `import` entry: exported settings only and,
+/// for an update, cleared values for the missing ones.  An `auto` local
+/// port keeps the port of an existing tunnel.
+fn import_body(mut entry: Map<String, Value>, update: bool) -> Map<String, Value> {
+    entry.retain(|key, _| EXPORTED.contains(&key.as_str()));
+    if update {
+        if entry.get("local_port").is_some_and(Value::is_string) {
+            entry.remove("local_port");
+        }
+        for key in EXPORTED {
+            if let (false, Some(value)) = (entry.contains_key(key), cleared(key)) {
+                entry.insert(key.to_string(), value);
+            }
+        }
+    }
+    entry
+}
+
+/// Catch what the API would refuse for type reasons before sending an
+/// `import` entry, so a dry run reports it too.
+fn check_entry(entry: &Map<String, Value>, create: bool) -> Result<(), String> {
+    let port = |key: &str| match entry.get(key) {
+        Some(value) if value.as_u64().is_some_and(|p| (1..=65535).contains(&p)) => Ok(()),
+        Some(value) => Err(format!("{key} {value} is not a port")),
+        None => Ok(()),
+    };
+    match entry.get("local_port") {
+        None | Some(Value::Null) if create => {}
+        Some(Value::String(value)) if create && value.eq_ignore_ascii_case("auto") => {}
+        _ => port("local_port")?,
+    }
+    port("target_port")?;
+    if create && !entry.contains_key("target_port") {
+        return Err("target_port is missing".to_string());
+    }
+    match entry.get("target_host") {
+        Some(Value::String(host)) if !host.trim().is_empty() => {}
+        None if !create => {}
+        _ => return Err("target_host is missing".to_string()),
+    }
+    for key in ["enabled", "funnel"] {
+        if entry.get(key).is_some_and(|v| !v.is_boolean()) {
+            return Err(format!("{key} must be true or false"));
+        }
+    }
+    for key in ["listen_address", "service_type"] {
+        if entry
+            .get(key)
+            .is_some_and(|v| !v.is_string() && !v.is_null())
+        {
+            return Err(format!("{key} must be a string"));
+        }
+    }
+    if let Some(value) = entry.get("health_check") {
+        if value != "tcp" && value != "off" {
+            return Err(format!("health_check {value} is not \"tcp\" or \"off\""));
+        }
+    }
+    if let Some(value) = entry.get("allowed_sources") {
+        let valid = value
+            .as_array()
+            .is_some_and(|sources| sources.iter().all(Value::is_string));
+        if !valid {
+            return Err("allowed_sources must be a list of strings".to_string());
+        }
+    }
+    Ok(())
+}
+
+/// A setting as the API reports it, with missing and `null` values
+/// replaced by what they mean.
+fn setting(value: Option<&Value>, key: &str) -> Value {
+    match value {
+        None | Some(Value::Null) => cleared(key).unwrap_or(Value::Null),
+        Some(value) => value.clone(),
+    }
+}
+
+/// Settings of `body` that differ from `current`, as `(key, from, to)`.
+fn changes(current: &Value, body: &Map<String, Value>) -> Vec<(&'static str, Value, Value)> {
+    EXPORTED
+        .into_iter()
+        .filter(|key| body.contains_key(*key))
+        .filter_map(|key| {
+            let from = setting(current.get(key), key);
+            let to = setting(body.get(key), key);
+            (from != to).then_some((key, from, to))
+        })
+        .collect()
+}
+
+struct Ctl {
+    client: Client,
+    json: bool,
+}
+
+impl Ctl {
+    async fn tunnels(&self) -> Result<Vec<Value>, String> {
+        self.client.get("/tunnels").await
+    }
+
+    /// A tunnel by id, name or unique id prefix.
+    async fn find(&self, reference: &str) -> Result<Value, String> {
+        let tunnels = self.tunnels().await?;
+        let exact = tunnels
+            .iter()
+            .find(|t| t["id"] == reference || t["name"] == reference);
+        if let Some(tunnel) = exact {
+            return Ok(tunnel.clone());
+        }
+        let mut matches = tunnels
+            .iter()
+            .filter(|t| t["id"].as_str().is_some_and(|id| id.starts_with(reference)));
+        match (matches.next(), matches.next()) {
+            (Some(tunnel), None) => Ok(tunnel.clone()),
+            (Some(_), Some(_)) => Err(format!("'{reference}' matches several tunnels")),
+            _ => Err(format!("No tunnel '{reference}'")),
+        }
+    }
+
+    async fn print_warning(&self, response: &Value) {
+        if let Ok(message) = serde_json::from_value::<ApiMessage>(response["warning"].clone()) {
+            eprintln!("warning: {}", self.client.translate(&message).await);
+        }
+    }
+
+    /// Print a tunnel returned by a create or update.
+    async fn print_tunnel(&self, tunnel: &Value) {
+        if self.json {
+            print_json(tunnel);
+        } else {
+            print_table(&TUNNEL_HEADERS, &[tunnel_row(tunnel)]);
+        }
+        self.print_warning(tunnel).await;
+    }
+
+    async fn update(&self, reference: &str, body: Map<String, Value>) -> Result<(), String> {
+        let tunnel = self.find(reference).await?;
+        let id = tunnel["id"].as_str().unwrap_or_default();
+        let updated: Value = self
+            .client
+            .call(
+                Method::PUT,
+                &format!("/tunnels/{id}"),
+                Some(&Value::Object(body)),
+            )
+            .await?;
+        self.print_tunnel(&updated).await;
+        Ok(())
+    }
+
+    async fn run(&self, command: Command) -> Result<ExitCode, String> {
+        match command {
+            Command::List => {
+                let tunnels = self.tunnels().await?;
+                if self.json {
+                    print_json(&Value::Array(tunnels));
+                } else {
+                    let rows: Vec<Vec<String>> = tunnels.iter().map(tunnel_row).collect();
+                    print_table(&TUNNEL_HEADERS, &rows);
+                }
+            }
+            Command::Create {
+                template,
+                disabled,
+                settings,
+            } => {
+                let mut body = settings.body();
+                if let Some(template) = template {
+                    body.insert("template_id".into(), json!(template));
+                }
+                body.insert("enabled".into(), json!(!disabled));
+                let created: Value = self
+                    .client
+                    .call(Method::POST, "/tunnels", Some(&Value::Object(body)))
+                    .await?;
+                self.print_tunnel(&created).await;
+            }
+            Command::Update { tunnel, settings } => {
+                if settings.local_port.as_ref().is_some_and(Value::is_string) {
+                    return Err("--local-port auto only works with create".to_string());
+                }
+                let body = settings.body();
+                if body.is_empty() {
+                    return Err("Nothing to change — pass at least one setting".to_string());
+                }
+                self.update(&tunnel, body).await?;
+            }
+            Command::Enable { tunnel } => {
+                self.update(&tunnel, Map::from_iter([("enabled".into(), json!(true))]))
+                    .await?;
+            }
+            Command::Disable { tunnel } => {
+                self.update(&tunnel, Map::from_iter([("enabled".into(), json!(false))]))
+                    .await?;
+            }
+            Command::Delete { tunnel } => {
+                let tunnel = self.find(&tunnel).await?;
+                let id = tunnel["id"].as_str().unwrap_or_default();
+                self.client
+                    .send(Method::DELETE, &format!("/tunnels/{id}"), None)
+                    .await?;
+                if !self.json {
+                    println!(
+                        "Deleted {} ({id})",
+                        tunnel["name"].as_str().unwrap_or_default()
+                    );
+                }
+            }
+            Command::Test { target } => return self.test(&target).await,
+            Command::Export { output } => {
+                let tunnels: Vec<Value> = self
+                    .tunnels()
+                    .await?
+                    .into_iter()
+                    .map(|mut tunnel| {
+                        if let Some(fields) = tunnel.as_object_mut() {
+                            fields.retain(|key, _| EXPORTED.contains(&key.as_str()));
+                        }
+                        tunnel
+                    })
+                    .collect();
+                let json = serde_json::to_string_pretty(&tunnels)
+                    .map_err(|e| format!("Could not encode tunnels: {e}"))?;
+                match output {
+                    Some(path) => {
+                        std::fs::write(&path, json + "\n")
+                            .map_err(|e| format!("Could not write {}: {e}", path.display()))?;
+                        eprintln!("Exported {} tunnel(s) to {}", tunnels.len(), path.display());
+                    }
+                    None => println!("{json}"),
+                }
+            }
+            Command::Import { file, dry_run } => return self.import(&file, dry_run).await,
+            Command::Logs {
+                tunnel,
+                lines,
+                follow,
+            } => self.logs(&tunnel, lines, follow).await?,
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+
+    async fn test(&self, target: &str) -> Result<ExitCode, String> {
+        let (host, port) = match parse_target(target) {
+            Ok(target) => target,
+            Err(_) => {
+                let tunnel = self.find(target).await?;
+                let host = tunnel["target_host"].as_str().unwrap_or_default();
+                let port = tunnel["target_port"].as_u64().unwrap_or_default() as u16;
+                (host.to_string(), port)
+            }
+        };
+        let body = json!({ "target_host": host, "target_port": port });
+        let result: Value = self.client.call(Method::POST, "/test", Some(&body)).await?;
+        let success = result["success"].as_bool().unwrap_or(false);
+        if self.json {
+            print_json(&result);
+        } else {
+            let log = result["log"].as_str().unwrap_or_default().trim_end();
+            if !log.is_empty() {
+                println!("{log}");
+            }
+            println!("{host}:{port} {}", if success { "OK" } else { "FAILED" });
+        }
+        Ok(if success {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        })
+    }
+
+    async fn import(&self, file: &PathBuf, dry_run: bool) -> Result<ExitCode, String> {
+        let contents = if file.as_os_str() == "-" {
+            let mut contents = String::new();
+            std::io::stdin()
+                .read_to_string(&mut contents)
+                .map_err(|e| format!("Could not read standard input: {e}"))?;
+            contents
+        } else {
+            std::fs::read_to_string(file)
+                .map_err(|e| format!("Could not read {}: {e}", file.display()))?
+        };
+        let entries: Vec<Map<String, Value>> = serde_json::from_str(&contents)
+            .map_err(|e| format!("{} is not a tunnel export: {e}", file.display()))?;
+        let names = entry_names(&entries)?;
+        let existing = self.tunnels().await?;
+
+        let mut failed = 0;
+        for (entry, name) in entries.into_iter().zip(names) {
+            let current = existing.iter().find(|t| t["name"] == name.as_str());
+            let (action, body) = match current {
+                Some(_) => ("update", import_body(entry, true)),
+                None => ("create", import_body(entry, false)),
+            };
+            if let Err(e) = check_entry(&body, current.is_none()) {
+                eprintln!("cannot {action} {name}: {e}");
+                failed += 1;
+                continue;
+            }
+            let result: Result<Value, String> = match current {
+                Some(tunnel) => {
+                    let changes = changes(tunnel, &body);
+                    if changes.is_empty() {
+                        println!("unchanged {name}");
+                        continue;
+                    }
+                    if dry_run {
+                        println!("would update {name}");
+                        for (key, from, to) in changes {
+                            println!("  {key}: {from} -> {to}");
+                        }
+                        continue;
+                    }
+                    let id = tunnel["id"].as_str().unwrap_or_default();
+                    let body = Value::Object(body);
+                    self.client
+                        .call(Method::PUT, &format!("/tunnels/{id}"), Some(&body))
+                        .await
+                }
+                None => {
+                    if dry_run {
+                        println!("would create {name}");
+                        for key in EXPORTED {
+                            if let Some(value) = body.get(key) {
+                                println!("  {key}: {value}");
+                            }
+                        }
+                        continue;
+                    }
+                    let body = Value::Object(body);
+                    self.client
+                        .call(Method::POST, "/tunnels", Some(&body))
+                        .await
+                }
+            };
+            match result {
+                Ok(tunnel) => {
+                    println!("{action}d {name} (local port {})", tunnel["local_port"]);
+                    self.print_warning(&tunnel).await;
+                }
+                Err(e) => {
+                    eprintln!("failed to {action} {name}: {e}");
+                    failed += 1;
+                }
+            }
+        }
+        Ok(if failed == 0 {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        })
+    }
+
+    async fn logs(&self, tunnel: &str, lines: Option<usize>, follow: bool) -> Result<(), String> {
+        let tunnel = self.find(tunnel).await?;
+        let id = tunnel["id"].as_str().unwrap_or_default();
+        let mut path = format!("/tunnels/{id}/logs?follow={follow}");
+        if let Some(lines) = lines {
+            path.push_str(&format!("&lines={lines}"));
+        }
+        let print = |entry: &Value| {
+            if self.json {
+                println!("{entry}");
+            } else {
+                print_log_entry(entry);
+            }
+        };
+
+        if !follow {
+            let entries: Vec<Value> = self.client.get(&path).await?;
+            entries.iter().for_each(print);
+            return Ok(());
+        }
+
+        // Server-sent events: `data:` lines of events separated by a blank line
+        let mut response = self.client.send(Method::GET, &path, None).await?;
+        let mut buffer = String::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| format!("Log stream ended: {e}"))?
+        {
+            buffer.push_str(&String::from_utf8_lossy(&chunk));
+            while let Some(end) = buffer.find("\n\n") {
+                let event: String = buffer.drain(..end + 2).collect();
+                for data in event.lines().filter_map(|l| l.strip_prefix("data:")) {
+                    if let Ok(entry) = serde_json::from_str::<Value>(data.trim()) {
+                        print(&entry);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let locale = cli.lang.unwrap_or_else(locale_from_env);
+    let ctl = Ctl {
+        client: Client::new(&cli.url, cli.token, locale),
+        json: cli.json,
+    };
+    match ctl.run(cli.command).await {
+        Ok(code) => code,
+        Err(e) => {
+            let prefix = if std::io::stderr().is_terminal() {
+                "\x1b[31merror:\x1b[0m"
+            } else {
+                "error:"
+            };
+            eprintln!("{prefix} {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(map) => map,
+            other => panic!("not an object: {other}"),
+        }
+    }
+
+    #[test]
+    fn parses_targets() {
+        assert_eq!(parse_target("db:5432"), Ok(("db".to_string(), 5432)));
+        assert_eq!(parse_target("[::1]:5432"), Ok(("::1".to_string(), 5432)));
+        assert_eq!(
+            parse_target("10.0.0.5:80"),
+            Ok(("10.0.0.5".to_string(), 80))
+        );
+        assert!(parse_target("db").is_err());
+        assert!(parse_target("db:").is_err());
+        assert!(parse_target("db:postgres").is_err());
+        assert!(parse_target("db:70000").is_err());
+    }
+
+    #[test]
+    fn parses_local_ports() {
+        assert_eq!(parse_local_port("auto"), Ok(json!("auto")));
+        assert_eq!(parse_local_port("AUTO"), Ok(json!("auto")));
+        assert_eq!(parse_local_port("5432"), Ok(json!(5432)));
+        assert!(parse_local_port("").is_err());
+        assert!(parse_local_port("70000").is_err());
+        assert!(parse_local_port("random").is_err());
+    }
+
+    #[test]
+    fn derives_locale_from_environment() {
+        let locale = |lc_all: Option<&str>, lang: Option<&str>| {
+            locale_from(lc_all.map(String::from), lang.map(String::from))
+        };
+        assert_eq!(locale(None, Some("pt_BR.UTF-8")), "pt-BR");
+        assert_eq!(locale(None, Some("de_DE@euro")), "de-DE");
+        assert_eq!(locale(Some("ja_JP.UTF-8"), Some("pt_BR.UTF-8")), "ja-JP");
+        assert_eq!(locale(Some(""), Some("fr_FR.UTF-8")), "fr-FR");
+        assert_eq!(locale(None, Some("C.UTF-8")), "en");
+        assert_eq!(locale(None, Some("POSIX")), "en");
+        assert_eq!(locale(None, None), "en");
+    }
+
+    fn settings() -> Settings {
+        Settings {
+            name: None,
+            local_port: None,
+            target: None,
+            service_type: None,
+            listen_address: None,
+            allowed_sources: Vec::new(),
+            allow_any: false,
+            health_check: None,
+        }
+    }
+
+    #[test]
+    fn body_only_has_given_settings() {
+        assert!(settings().body().is_empty());
+        let body = Settings {
+            local_port: Some(json!("auto")),
+            target: Some(("::1".to_string(), 5432)),
+            allowed_sources: vec!["tag:dev".to_string()],
+            ..settings()
+        }
+        .body();
+        assert_eq!(
+            Value::Object(body),
+            json!({
+                "local_port": "auto",
+                "target_host": "::1",
+                "target_port": 5432,
+                "allowed_sources": ["tag:dev"],
+            })
+        );
+    }
+
+    #[test]
+    fn allow_any_clears_sources() {
+        let body = Settings {
+            allow_any: true,
+            ..settings()
+        }
+        .body();
+        assert_eq!(Value::Object(body), json!({ "allowed_sources": [] }));
+    }
+
+    #[test]
+    fn import_fills_cleared_settings_on_update_only() {
+        let entry = object(json!({
+            "name": "db",
+            "id": "ignored",
+            "local_port": "auto",
+            "target_host": "db",
+            "target_port": 5432,
+        }));
+        let created = import_body(entry.clone(), false);
+        assert_eq!(
+            Value::Object(created),
+            json!({
+                "name": "db",
+                "local_port": "auto",
+                "target_host": "db",
+                "target_port": 5432,
+            })
+        );
+        let updated = import_body(entry, true);
+        assert_eq!(
+            Value::Object(updated),
+            json!({
+                "name": "db",
+                "target_host": "db",
+                "target_port": 5432,
+                "allowed_sources": [],
+                "listen_address": "",
+                "service_type": "",
+                "health_check": "tcp",
+                "serve": null,
+                "funnel": false,
+            })
+        );
+    }
+
+    #[test]
+    fn import_requires_unique_names() {
+        let entries =
+            |value: Value| -> Vec<Map<String, Value>> { serde_json::from_value(value).unwrap() };
+        assert_eq!(
+            entry_names(&entries(json!([{ "name": " db " }, { "name": "cache" }]))),
+            Ok(vec!["db".to_string(), "cache".to_string()])
+        );
+        assert_eq!(
+            entry_names(&entries(json!([{ "name": "db" }, { "target_port": 1 }]))),
+            Err("Entry 2 has no name".to_string())
+        );
+        assert_eq!(
+            entry_names(&entries(json!([{ "name": "" }]))),
+            Err("Entry 1 has no name".to_string())
+        );
+        assert_eq!(
+            entry_names(&entries(json!([{ "name": 5 }]))),
+            Err("Entry 1 has no name".to_string())
+        );
+        assert_eq!(
+            entry_names(&entries(json!([{ "name": "db" }, { "name": "db" }]))),
+            Err("'db' appears more than once".to_string())
+        );
+    }
+
+    #[test]
+    fn checks_import_entries() {
+        let check = |value: Value, create: bool| check_entry(&object(value), create);
+        assert!(check(json!({ "target_host": "db", "target_port": 5432 }), true).is_ok());
+        assert!(check(
+            json!({ "local_port": "auto", "target_host": "db", "target_port": 5432 }),
+            true
+        )
+        .is_ok());
+        assert!(check(json!({ "local_port": 5433 }), false).is_ok());
+        assert!(check(json!({ "local_port": "auto" }), false).is_err());
+        assert!(check(json!({ "target_host": "db" }), true).is_err());
+        assert!(check(json!({ "target_port": 5432 }), true).is_err());
+        assert!(check(json!({ "target_port": 0 }), false).is_err());
+        assert!(check(json!({ "local_port": 70000 }), false).is_err());
+        assert!(check(json!({ "enabled": "yes" }), false).is_err());
+        assert!(check(json!({ "health_check": "http" }), false).is_err());
+        assert!(check(json!({ "allowed_sources": "tag:dev" }), false).is_err());
+        assert!(check(json!({ "allowed_sources": [1] }), false).is_err());
+    }
+
+    #[test]
+    fn diffs_against_current_tunnel() {
+        let current = json!({
+            "id": "1",
+            "name": "db",
+            "local_port": 5432,
+            "target_host": "db",
+            "target_port": 5432,
+            "enabled": true,
+        });
+        let unchanged = import_body(
+            object(json!({
+                "name": "db",
+                "local_port": 5432,
+                "target_host": "db",
+                "target_port": 5432,
+                "enabled": true,
+            })),
+            true,
+        );
+        assert!(changes(&current, &unchanged).is_empty());
+
+        let body = import_body(
+            object(json!({
+                "name": "db",
+                "local_port": 5433,
+                "target_host": "db",
+                "target_port": 5432,
+                "enabled": true,
+                "allowed_sources": ["tag:dev"],
+            })),
+            true,
+        );
+        assert_eq!(
+            changes(&current, &body),
+            vec![
+                ("local_port", json!(5432), json!(5433)),
+                ("allowed_sources", json!([]), json!(["tag:dev"])),
+            ]
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use serde_json::Value;
+
+/// Print rows as columns aligned under `headers`.
+pub fn print_table(headers: &[&str], rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let line = |cells: Vec<&str>| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect();
+        println!("{}", padded.join("  ").trim_end());
+    };
+    line(headers.to_vec());
+    for row in rows {
+        line(row.iter().map(String::as_str).collect());
+    }
+}
+
+pub fn print_json(value: &Value) {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string())
+    );
+}
+
+fn str_field<'a>(tunnel: &'a Value, key: &str) -> &'a str {
+    tunnel[key].as_str().unwrap_or_default()
+}
+
+pub const TUNNEL_HEADERS: [&str; 7] = ["ID", "NAME", "LOCAL", "TARGET", "STATE", "CONN", "URL"];
+
+/// A tunnel from `GET /api/tunnels` as a table row.
+pub fn tunnel_row(tunnel: &Value) -> Vec<String> {
+    let id = str_field(tunnel, "id");
+    let state = match (tunnel["enabled"].as_bool(), tunnel["health"].as_str()) {
+        (Some(true), Some(health)) => format!("enabled ({health})"),
+        (Some(true), None) => "enabled".to_string(),
+        _ => "disabled".to_string(),
+    };
+    vec![
+        id.chars().take(8).collect(),
+        str_field(tunnel, "name").to_string(),
+        tunnel["local_port"].to_string(),
+        format!(
+            "{}:{}",
+            str_field(tunnel, "target_host"),
+            tunnel["target_port"]
+        ),
+        state,
+        tunnel["active_connections"]
+            .as_u64()
+            .unwrap_or(0)
+            .to_string(),
+        tunnel["connection_url"].as_str().unwrap_or("-").to_string(),
+    ]
+}
+
+/// `YYYY-MM-DD HH:MM:SS` in UTC for milliseconds since the unix epoch.
+pub fn format_timestamp(millis: u64) -> String {
+    let secs = millis / 1000;
+    let (days, rest) = ((secs / 86_400) as i64, secs % 86_400);
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}",
+        rest / 3600,
+        rest % 3600 / 60,
+        rest % 60
+    )
+}
+
+/// A tunnel log line, as returned by `GET /api/tunnels/:id/logs`.
+pub fn print_log_entry(entry: &Value) {
+    println!(
+        "{} {:<5} {}",
+        format_timestamp(entry["timestamp"].as_u64().unwrap_or(0)),
+        str_field(entry, "level").to_uppercase(),
+        str_field(entry, "message")
+    );
+}
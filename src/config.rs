@@ -0,0 +1,279 @@
+use std::net::{IpAddr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use axum::{
+    extract::Request,
+    http::{StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+};
+use clap::{error::ErrorKind, CommandFactory, Parser};
+
+use crate::models::ListenAddress;
+use crate::ports::PortPolicy;
+
+/// Where the API listens and where it keeps its files.  Every setting can
+/// be given as a flag or through its environment variable.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Settings {
+    /// Address the API and dashboard listen on.
+    #[arg(long, env = "API_ADDRESS", default_value = "0.0.0.0")]
+    pub address: IpAddr,
+
+    /// Port the API and dashboard listen on.
+    #[arg(long, env = "API_PORT", default_value_t = 3000)]
+    pub port: u16,
+
+    /// Serve the dashboard from this directory instead of the copy built
+    /// into the binary, e.g. `./public` while working on it.
+    #[arg(long, env = "PUBLIC_DIR")]
+    pub public_dir: Option<PathBuf>,
+
+    /// File the tunnels are saved to.
+    #[arg(long, env = "TUNNELS_PATH", default_value = "./tunnels.json")]
+    pub tunnels_path: PathBuf,
+
+    /// File the webhook endpoints are saved to.
+    #[arg(long, env = "WEBHOOKS_PATH", default_value = "./webhooks.json")]
+    pub webhooks_path: PathBuf,
+
+    /// File user templates are saved to (default `templates.json` next to
+    /// the tunnels file).
+    #[arg(long, env = "TEMPLATES_PATH")]
+    pub templates_path: Option<PathBuf>,
+
+    /// File Funnel changes are appended to as JSON lines.
+    #[arg(long, env = "AUDIT_LOG_PATH", default_value = "./audit.log")]
+    pub audit_log_path: PathBuf,
+
+    /// Default listen address for tunnels: `all`, `tailscale`, `loopback`
+    /// or an IP.  Tunnels can override it.
+    #[arg(long, env = "LISTEN_ADDRESS", default_value = "all", value_parser = parse_listen_address)]
+    pub listen_address: ListenAddress,
+
+    /// Ports picked for tunnels created without a local port when the
+    /// target port is taken, as `start-end`.
+    #[arg(long, env = "AUTO_PORT_RANGE", default_value = "10000-19999", value_parser = parse_port_range)]
+    pub auto_port_range: RangeInclusive<u16>,
+
+    /// URL path everything is served under, e.g. `/tunnels` behind
+    /// `tailscale serve --set-path /tunnels`.
+    #[arg(long, env = "BASE_PATH", default_value = "", value_parser = parse_base_path)]
+    pub base_path: String,
+
+    /// Latest Funnel expiry allowed, in hours from now.
+    #[arg(long, env = "FUNNEL_MAX_HOURS", default_value_t = 24, value_parser = clap::value_parser!(u64).range(1..))]
+    pub funnel_max_hours: u64,
+
+    /// Create, update and delete requests allowed per client per minute.
+    #[arg(long, env = "RATE_LIMIT_PER_MINUTE", default_value_t = 30)]
+    pub rate_limit_per_minute: u64,
+
+    /// Connection tests, peer pings and discovery requests allowed per
+    /// client per minute.
+    #[arg(long, env = "PROBE_RATE_LIMIT_PER_MINUTE", default_value_t = 10)]
+    pub probe_rate_limit_per_minute: u64,
+
+    /// Connection tests running at once across all clients.
+    #[arg(long, env = "MAX_CONCURRENT_PROBES", default_value_t = 4, value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_concurrent_probes: u64,
+
+    /// Invalid tokens a client may send before it is locked out.
+    #[arg(long, env = "AUTH_MAX_FAILURES", default_value_t = 5, value_parser = clap::value_parser!(u64).range(1..))]
+    pub auth_max_failures: u64,
+
+    /// How long a locked-out client is refused, in seconds; also the
+    /// window failures are counted in.
+    #[arg(long, env = "AUTH_LOCKOUT_SECONDS", default_value_t = 900)]
+    pub auth_lockout_seconds: u64,
+}
+
+fn parse_listen_address(value: &str) -> Result<ListenAddress, String> {
+    value
+        .parse()
+        .map_err(|_| "expected `all`, `tailscale`, `loopback` or an IP".to_string())
+}
+
+/// `start-end` with `0 < start <= end`.
+fn parse_port_range(value: &str) -> Result<RangeInclusive<u16>, String> {
+    let invalid = || "expected `start-end` with 1 <= start <= end <= 65535".to_string();
+    let (start, end) = value.split_once('-').ok_or_else(invalid)?;
+    let start: u16 = start.trim().parse().map_err(|_| invalid())?;
+    let end: u16 = end.trim().parse().map_err(|_| invalid())?;
+    if start == 0 || start > end {
+        return Err(invalid());
+    }
+    Ok(start..=end)
+}
+
+/// `/a/b` from `a/b/`, `/a/b` or `/a/b/`; empty for the root.
+fn parse_base_path(value: &str) -> Result<String, String> {
+    let trimmed = value.trim().trim_matches('/');
+    if trimmed.is_empty() {
+        return Ok(String::new());
+    }
+    if trimmed
+        .split('/')
+        .any(|segment| segment.is_empty() || segment == "." || segment == "..")
+    {
+        return Err(format!("'{value}' is not a valid URL path"));
+    }
+    Ok(format!("/{trimmed}"))
+}
+
+impl Settings {
+    pub fn bind_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.address, self.port)
+    }
+
+    /// `AUTO_PORT_RANGE` must hold a port the policy lets tunnels use.
+    fn validate_auto_port_range(&self, policy: &PortPolicy) -> Result<(), String> {
+        let range = &self.auto_port_range;
+        if range.clone().any(|port| policy.check(port).is_ok()) {
+            return Ok(());
+        }
+        Err(format!(
+            "AUTO_PORT_RANGE {}-{} has no port tunnels may listen on ({policy})",
+            range.start(),
+            range.end()
+        ))
+    }
+}
+
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+/// Parse the command line and environment.  Exits with usage on errors.
+pub fn init() -> &'static Settings {
+    SETTINGS.get_or_init(Settings::parse)
+}
+
+/// Check the settings that depend on the port policy.  Exits with usage on
+/// errors, like [`init`].
+pub fn check_ports(policy: &PortPolicy) {
+    if let Err(e) = settings().validate_auto_port_range(policy) {
+        Settings::command()
+            .error(ErrorKind::ValueValidation, e)
+            .exit();
+    }
+}
+
+/// The settings parsed by [`init`].
+pub fn settings() -> &'static Settings {
+    SETTINGS.get().expect("settings not initialized")
+}
+
+/// The defaults, with the environment applied, for tests that reach
+/// [`settings`].
+#[cfg(test)]
+pub fn init_defaults() -> &'static Settings {
+    SETTINGS.get_or_init(|| Settings::parse_from(["api"]))
+}
+
+/// Serve the app under `BASE_PATH`: the prefix is removed before routing,
+/// so routes and their metrics labels stay the same.  The bare prefix is
+/// redirected to `prefix/` for the dashboard's relative URLs to resolve.
+pub async fn strip_base_path(mut request: Request, next: Next) -> Response {
+    let base = settings().base_path.as_str();
+    if base.is_empty() {
+        return next.run(request).await;
+    }
+    let path = request.uri().path();
+    let rest = match path.strip_prefix(base) {
+        Some("") => return Redirect::permanent(&format!("{base}/")).into_response(),
+        Some(rest) if rest.starts_with('/') => rest,
+        _ => return StatusCode::NOT_FOUND.into_response(),
+    };
+    let path_and_query = match request.uri().query() {
+        Some(query) => format!("{rest}?{query}"),
+        None => rest.to_string(),
+    };
+    let mut parts = request.uri().clone().into_parts();
+    parts.path_and_query = path_and_query.parse().ok();
+    match Uri::from_parts(parts) {
+        Ok(uri) => *request.uri_mut() = uri,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_auto_port_range() {
+        assert_eq!(parse_port_range("10000-19999"), Ok(10000..=19999));
+        assert_eq!(parse_port_range(" 2000 - 2000 "), Ok(2000..=2000));
+        for bad in ["3000-2000", "0-100", "1000-70000", "1000", "a-b", "", "-"] {
+            assert!(parse_port_range(bad).is_err(), "{bad:?}");
+        }
+    }
+
+    fn with_range(range: &str) -> Settings {
+        Settings::try_parse_from(["api", "--port", "3000", "--auto-port-range", range]).unwrap()
+    }
+
+    #[test]
+    fn limits_come_from_flags() {
+        let settings = Settings::try_parse_from([
+            "api",
+            "--funnel-max-hours",
+            "2",
+            "--rate-limit-per-minute",
+            "60",
+            "--max-concurrent-probes",
+            "8",
+            "--auth-lockout-seconds",
+            "30",
+        ])
+        .unwrap();
+        assert_eq!(settings.funnel_max_hours, 2);
+        assert_eq!(settings.rate_limit_per_minute, 60);
+        assert_eq!(settings.max_concurrent_probes, 8);
+        assert_eq!(settings.auth_lockout_seconds, 30);
+    }
+
+    #[test]
+    fn zero_is_refused_where_it_would_disable_a_feature() {
+        for flag in [
+            "--funnel-max-hours",
+            "--max-concurrent-probes",
+            "--auth-max-failures",
+        ] {
+            let err = Settings::try_parse_from(["api", flag, "0"]).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::ValueValidation, "{flag}");
+        }
+    }
+
+    #[test]
+    fn invalid_auto_port_range_is_a_usage_error() {
+        let err =
+            Settings::try_parse_from(["api", "--auto-port-range", "20000-10000"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn auto_port_range_needs_a_usable_port() {
+        let policy = PortPolicy::new(vec![10000..=19999], [10001], 3000);
+        assert!(with_range("10000-10001")
+            .validate_auto_port_range(&policy)
+            .is_ok());
+        assert!(with_range("9000-10000")
+            .validate_auto_port_range(&policy)
+            .is_ok());
+
+        // Reserved, outside the allowed ranges, or the API port itself.
+        for range in ["10001-10001", "20000-29999", "3000-3000", "1-1023"] {
+            let err = with_range(range)
+                .validate_auto_port_range(&policy)
+                .unwrap_err();
+            assert!(
+                err.starts_with(&format!("AUTO_PORT_RANGE {range} ")),
+                "{err}"
+            );
+        }
+    }
+}
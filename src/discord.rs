@@ -0,0 +1,326 @@
+use serde_json::{json, Value};
+use tokio::time::Duration;
+
+use crate::i18n::translate;
+use crate::models::{ApiMessage, WebhookEvent};
+use crate::routes::params1;
+use crate::state::get_hostname;
+use crate::webhooks::WebhookPayload;
+
+/// Embeds Discord accepts in one message.
+const MAX_EMBEDS: usize = 10;
+
+/// One embed of a batched message.  State events (`tunnel.up`,
+/// `tunnel.down`, `health.degraded`) of the same tunnel are merged into a
+/// single entry showing the latest state.
+#[derive(Debug, Clone)]
+pub struct BatchEntry {
+    pub payload: WebhookPayload,
+    /// Events merged into this entry.
+    pub changes: u32,
+}
+
+fn is_state_event(event: WebhookEvent) -> bool {
+    matches!(
+        event,
+        WebhookEvent::TunnelUp | WebhookEvent::TunnelDown | WebhookEvent::HealthDegraded
+    )
+}
+
+/// Add `payload` to a pending batch, merging repeated state changes of
+/// the same tunnel so a flapping target yields one embed.
+pub fn add_to_batch(batch: &mut Vec<BatchEntry>, payload: WebhookPayload) {
+    let tunnel_id = payload.tunnel.as_ref().map(|t| t.id.as_str());
+    if is_state_event(payload.event) {
+        if let Some(entry) = batch.iter_mut().find(|e| {
+            is_state_event(e.payload.event)
+                && e.payload.tunnel.as_ref().map(|t| t.id.as_str()) == tunnel_id
+        }) {
+            entry.payload = payload;
+            entry.changes += 1;
+            return;
+        }
+    }
+    batch.push(BatchEntry {
+        payload,
+        changes: 1,
+    });
+}
+
+fn color(event: WebhookEvent) -> u32 {
+    match event {
+        WebhookEvent::TunnelUp => 0x1a9d4a,
+        WebhookEvent::TunnelDown => 0xf25757,
+        WebhookEvent::HealthDegraded => 0xf2c857,
+        WebhookEvent::TunnelDeleted => 0x9a9a9a,
+        WebhookEvent::TunnelCreated | WebhookEvent::Ping => 0x57b4f2,
+    }
+}
+
+/// Cut `text` to at most `max` characters, as Discord rejects longer fields.
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let kept: String = text.chars().take(max - 1).collect();
+    kept + "…"
+}
+
+fn embed(locale: &str, entry: &BatchEntry, window: Duration) -> Value {
+    let payload = &entry.payload;
+    let t = |id: &str, params| translate(locale, &ApiMessage::with_params(id, params));
+
+    let title = t(
+        &format!("notify.{}.title", payload.event.as_str()),
+        Default::default(),
+    );
+    let description = match &payload.tunnel {
+        Some(tunnel) => {
+            let mut params = params1("name", tunnel.name.clone());
+            params.insert("port".into(), tunnel.local_port.into());
+            params.insert(
+                "target".into(),
+                format!("{}:{}", tunnel.target_host, tunnel.target_port).into(),
+            );
+            t("notify.tunnel.description", params)
+        }
+        None => t(
+            "notify.ping.description",
+            params1("hostname", get_hostname()),
+        ),
+    };
+
+    let mut embed = json!({
+        "title": title,
+        "description": truncate(&description, 4096),
+        "color": color(payload.event),
+    });
+    if let Some(detail) = &payload.detail {
+        embed["fields"] = json!([{
+            "name": t("notify.field.detail", Default::default()),
+            "value": truncate(detail, 1024),
+        }]);
+    }
+    if entry.changes > 1 {
+        let mut params = params1("count", entry.changes);
+        params.insert("seconds".into(), window.as_secs().into());
+        embed["footer"] = json!({ "text": t("notify.repeated", params) });
+    }
+    embed
+}
+
+/// The wait a `429` from Discord asks for: `retry_after` of its JSON body,
+/// in (fractional) seconds.
+pub fn retry_after(body: &[u8]) -> Option<Duration> {
+    let secs = serde_json::from_slice::<Value>(body)
+        .ok()?
+        .get("retry_after")?
+        .as_f64()?;
+    Duration::try_from_secs_f64(secs).ok()
+}
+
+/// Discord webhook body for a batch.  Entries beyond the embed limit are
+/// summarized in the message text.
+pub fn message(locale: &str, entries: &[BatchEntry], window: Duration) -> Value {
+    let shown = entries.len().min(MAX_EMBEDS);
+    let embeds: Vec<Value> = entries[..shown]
+        .iter()
+        .map(|e| embed(locale, e, window))
+        .collect();
+
+    let mut body = json!({
+        "username": translate(locale, &ApiMessage::new("header.title")),
+        "embeds": embeds,
+        "allowed_mentions": { "parse": [] },
+    });
+    if shown < entries.len() {
+        body["content"] = translate(
+            locale,
+            &ApiMessage::with_params("notify.more", params1("count", entries.len() - shown)),
+        )
+        .into();
+    }
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webhooks::TunnelRef;
+
+    fn payload(event: WebhookEvent, tunnel: &str) -> WebhookPayload {
+        WebhookPayload {
+            id: format!("{}-{tunnel}", event.as_str()),
+            event,
+            timestamp: 0,
+            tunnel: Some(TunnelRef {
+                id: tunnel.to_string(),
+                name: format!("db-{tunnel}"),
+                local_port: 5432,
+                target_host: "postgres".to_string(),
+                target_port: 5432,
+            }),
+            detail: None,
+        }
+    }
+
+    fn batch(events: &[(WebhookEvent, &str)]) -> Vec<BatchEntry> {
+        let mut batch = Vec::new();
+        for (event, tunnel) in events {
+            add_to_batch(&mut batch, payload(*event, tunnel));
+        }
+        batch
+    }
+
+    fn batch_of(payload: WebhookPayload) -> Vec<BatchEntry> {
+        let mut batch = Vec::new();
+        add_to_batch(&mut batch, payload);
+        batch
+    }
+
+    fn summary(batch: &[BatchEntry]) -> Vec<(&str, String, u32)> {
+        batch
+            .iter()
+            .map(|e| {
+                (
+                    e.payload.event.as_str(),
+                    e.payload.tunnel.as_ref().unwrap().id.clone(),
+                    e.changes,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn state_changes_of_a_tunnel_merge_into_the_latest() {
+        use WebhookEvent::*;
+        let batch = batch(&[
+            (TunnelDown, "a"),
+            (TunnelUp, "a"),
+            (HealthDegraded, "a"),
+            (TunnelDown, "a"),
+        ]);
+        assert_eq!(summary(&batch), [("tunnel.down", "a".to_string(), 4)]);
+        assert_eq!(batch[0].payload.id, "tunnel.down-a");
+    }
+
+    #[test]
+    fn merged_entries_keep_their_place() {
+        use WebhookEvent::*;
+        let batch = batch(&[
+            (TunnelDown, "a"),
+            (TunnelDown, "b"),
+            (TunnelUp, "a"),
+            (TunnelUp, "c"),
+        ]);
+        assert_eq!(
+            summary(&batch),
+            [
+                ("tunnel.up", "a".to_string(), 2),
+                ("tunnel.down", "b".to_string(), 1),
+                ("tunnel.up", "c".to_string(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn lifecycle_events_are_never_merged() {
+        use WebhookEvent::*;
+        let batch = batch(&[
+            (TunnelCreated, "a"),
+            (TunnelUp, "a"),
+            (TunnelDeleted, "a"),
+            (TunnelCreated, "a"),
+            (TunnelDown, "a"),
+        ]);
+        assert_eq!(
+            summary(&batch),
+            [
+                ("tunnel.created", "a".to_string(), 1),
+                ("tunnel.down", "a".to_string(), 2),
+                ("tunnel.deleted", "a".to_string(), 1),
+                ("tunnel.created", "a".to_string(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn message_has_one_embed_per_entry_in_order() {
+        crate::config::init_defaults();
+        use WebhookEvent::*;
+        let entries = batch(&[(TunnelCreated, "a"), (TunnelDown, "b"), (TunnelUp, "b")]);
+        let body = message("en", &entries, Duration::from_secs(10));
+
+        assert_eq!(body["username"], "Tunnel Manager");
+        assert_eq!(body["allowed_mentions"], json!({ "parse": [] }));
+        assert!(body.get("content").is_none());
+        let embeds = body["embeds"].as_array().unwrap();
+        assert_eq!(embeds.len(), 2);
+        assert_eq!(embeds[0]["title"], "Tunnel created");
+        assert_eq!(
+            embeds[0]["description"],
+            "**db-a** — port 5432 → postgres:5432"
+        );
+        assert_eq!(embeds[0]["color"], 0x57b4f2);
+        assert!(embeds[0].get("footer").is_none());
+        assert_eq!(embeds[1]["title"], "Tunnel up");
+        assert_eq!(embeds[1]["color"], 0x1a9d4a);
+        assert_eq!(
+            embeds[1]["footer"]["text"],
+            "2 state changes in the last 10s"
+        );
+    }
+
+    #[test]
+    fn message_summarizes_entries_beyond_the_embed_limit() {
+        crate::config::init_defaults();
+        let tunnels: Vec<String> = (0..MAX_EMBEDS + 3).map(|i| i.to_string()).collect();
+        let events: Vec<_> = tunnels
+            .iter()
+            .map(|t| (WebhookEvent::TunnelDown, t.as_str()))
+            .collect();
+        let body = message("en", &batch(&events), Duration::from_secs(10));
+
+        let embeds = body["embeds"].as_array().unwrap();
+        assert_eq!(embeds.len(), MAX_EMBEDS);
+        assert_eq!(
+            embeds[MAX_EMBEDS - 1]["description"],
+            format!("**db-{}** — port 5432 → postgres:5432", MAX_EMBEDS - 1)
+        );
+        assert_eq!(body["content"], "…and 3 more event(s)");
+    }
+
+    #[test]
+    fn detail_becomes_a_truncated_field() {
+        crate::config::init_defaults();
+        let mut entry = payload(WebhookEvent::TunnelDown, "a");
+        entry.detail = Some("x".repeat(2000));
+        let body = message("en", &batch_of(entry), Duration::from_secs(10));
+        let field = &body["embeds"][0]["fields"][0];
+        assert_eq!(field["name"], "Detail");
+        let value = field["value"].as_str().unwrap();
+        assert_eq!(value.chars().count(), 1024);
+        assert!(value.ends_with('…'));
+    }
+
+    #[test]
+    fn truncate_keeps_short_text() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("exactly10!", 10), "exactly10!");
+        assert_eq!(truncate("ação ação ação", 5), "ação…");
+    }
+
+    #[test]
+    fn retry_after_reads_discord_rate_limit_bodies() {
+        let body =
+            br#"{"message": "You are being rate limited.", "retry_after": 1.5, "global": false}"#;
+        assert_eq!(retry_after(body), Some(Duration::from_millis(1500)));
+        assert_eq!(
+            retry_after(br#"{"retry_after": 3}"#),
+            Some(Duration::from_secs(3))
+        );
+        assert_eq!(retry_after(br#"{"retry_after": -1}"#), None);
+        assert_eq!(retry_after(br#"{"message": "slow down"}"#), None);
+        assert_eq!(retry_after(b"not json"), None);
+    }
+}
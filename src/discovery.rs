@@ -0,0 +1,564 @@
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use serde::Deserialize;
+use tokio::net::{lookup_host, TcpStream, UdpSocket};
+use tokio::sync::{Mutex, Semaphore};
+use tokio::task::JoinSet;
+use tokio::time::{timeout, Duration, Instant};
+use tracing::{info, warn};
+
+use crate::limits::env_u64;
+use crate::models::{DiscoveredService, DiscoveryResponse, DiscoverySource, ServiceType};
+use crate::webhooks::unix_now;
+
+/// Services looked for on the VLAN: type, display name, default port and
+/// the hostnames they usually run under.
+const KNOWN: [(ServiceType, &str, u16, &[&str]); 4] = [
+    (
+        ServiceType::Postgres,
+        "PostgreSQL",
+        5432,
+        &["postgres", "postgresql", "db"],
+    ),
+    (ServiceType::Mysql, "MySQL", 3306, &["mysql", "mariadb"]),
+    (ServiceType::Redis, "Redis", 6379, &["redis", "cache"]),
+    (
+        ServiceType::Mongodb,
+        "MongoDB",
+        27017,
+        &["mongodb", "mongo"],
+    ),
+];
+
+/// The known service usually listening on `port`.
+fn known_by_port(port: u16) -> Option<ServiceType> {
+    KNOWN
+        .iter()
+        .find(|(_, _, p, _)| *p == port)
+        .map(|(service_type, _, _, _)| *service_type)
+}
+
+/// How discovery runs, from the environment:
+/// - `DISCOVERY_HOSTNAMES` — extra names to try, comma-separated, as
+///   `host` (every known port) or `host:port`
+/// - `DISCOVERY_SUBNET` — IPv4 subnet to scan, e.g. `10.0.0.0/24` (default
+///   the VLAN route of the container; `off` disables the scan)
+/// - `DISCOVERY_MAX_HOSTS` — larger subnets are not scanned (default 1024)
+/// - `DISCOVERY_CONCURRENCY` — connection attempts at once (default 128)
+/// - `DISCOVERY_TIMEOUT_MS` — time allowed per attempt (default 500)
+/// - `DISCOVERY_CACHE_SECONDS` — how long results are reused (default 60)
+/// - `DISCLOUD_API_TOKEN` — also list apps through the Discloud API, at
+///   `DISCLOUD_API_URL` (default `https://api.discloud.app/v2`)
+#[derive(Debug)]
+struct Config {
+    hostnames: Vec<String>,
+    subnet: Option<String>,
+    max_hosts: u64,
+    concurrency: usize,
+    attempt_timeout: Duration,
+    cache_for: Duration,
+    api_token: Option<String>,
+    api_url: String,
+}
+
+impl Config {
+    fn from_env() -> Self {
+        let var = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        Self {
+            hostnames: var("DISCOVERY_HOSTNAMES")
+                .map(|v| {
+                    v.split(',')
+                        .map(|h| h.trim().to_string())
+                        .filter(|h| !h.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            subnet: var("DISCOVERY_SUBNET"),
+            max_hosts: env_u64("DISCOVERY_MAX_HOSTS", 1024),
+            concurrency: env_u64("DISCOVERY_CONCURRENCY", 128).max(1) as usize,
+            attempt_timeout: Duration::from_millis(env_u64("DISCOVERY_TIMEOUT_MS", 500).max(1)),
+            cache_for: Duration::from_secs(env_u64("DISCOVERY_CACHE_SECONDS", 60)),
+            api_token: var("DISCLOUD_API_TOKEN"),
+            api_url: var("DISCLOUD_API_URL")
+                .unwrap_or_else(|| "https://api.discloud.app/v2".to_string()),
+        }
+    }
+}
+
+// ─── Subnet ──────────────────────────────────────────────────────────────
+
+/// An IPv4 network in CIDR notation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Subnet {
+    network: Ipv4Addr,
+    prefix: u8,
+}
+
+impl Subnet {
+    fn new(ip: Ipv4Addr, prefix: u8) -> Self {
+        let mask = u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0);
+        Self {
+            network: Ipv4Addr::from(u32::from(ip) & mask),
+            prefix,
+        }
+    }
+
+    fn size(&self) -> u64 {
+        1u64 << (32 - u32::from(self.prefix))
+    }
+
+    /// Usable host addresses: all but the network and broadcast addresses,
+    /// which /31 and /32 do not have.
+    fn hosts(&self) -> impl Iterator<Item = Ipv4Addr> {
+        let first = u32::from(self.network) as u64;
+        let (start, end) = if self.prefix >= 31 {
+            (first, first + self.size())
+        } else {
+            (first + 1, first + self.size() - 1)
+        };
+        (start..end).map(|ip| Ipv4Addr::from(ip as u32))
+    }
+}
+
+impl fmt::Display for Subnet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix)
+    }
+}
+
+impl FromStr for Subnet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (ip, prefix) = s.split_once('/').unwrap_or((s, "32"));
+        let ip: Ipv4Addr = ip
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid subnet '{s}'"))?;
+        match prefix.trim().parse::<u8>() {
+            Ok(prefix) if prefix <= 32 => Ok(Subnet::new(ip, prefix)),
+            _ => Err(format!("Invalid subnet '{s}'")),
+        }
+    }
+}
+
+/// The subnet of the first directly connected IPv4 route outside
+/// Tailscale and loopback, read from `/proc/net/route` — on Discloud, the
+/// VLAN.
+async fn vlan_subnet() -> Option<Subnet> {
+    let routes = tokio::fs::read_to_string("/proc/net/route").await.ok()?;
+    routes.lines().skip(1).find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (iface, destination, gateway, mask) = (
+            fields.first()?,
+            fields.get(1)?,
+            fields.get(2)?,
+            fields.get(7)?,
+        );
+        if iface.starts_with("tailscale") || *iface == "lo" {
+            return None;
+        }
+        // Addresses are printed as host-order hex of network-order bytes.
+        let parse = |hex: &str| u32::from_str_radix(hex, 16).ok().map(u32::to_ne_bytes);
+        let destination = Ipv4Addr::from(parse(destination)?);
+        let mask = u32::from_be_bytes(parse(mask)?);
+        let direct = parse(gateway)? == [0; 4];
+        (direct && !destination.is_unspecified() && mask != 0)
+            .then(|| Subnet::new(destination, mask.count_ones() as u8))
+    })
+}
+
+/// Our own address on the route towards `ip`, so the scan skips it.
+async fn local_addr_towards(ip: Ipv4Addr) -> Option<IpAddr> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await.ok()?;
+    socket.connect((ip, 9)).await.ok()?;
+    Some(socket.local_addr().ok()?.ip())
+}
+
+// ─── Discloud API ────────────────────────────────────────────────────────
+
+#[derive(Debug, Deserialize)]
+struct DiscloudApps {
+    #[serde(default)]
+    apps: Vec<DiscloudApp>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DiscloudApp {
+    id: String,
+    #[serde(default)]
+    name: String,
+}
+
+/// Apps of the account behind `DISCLOUD_API_TOKEN`.  On the VLAN, an app
+/// is reachable under its id.
+async fn discloud_apps(config: &Config, token: &str) -> Result<Vec<DiscloudApp>, String> {
+    let url = format!("{}/app/all", config.api_url.trim_end_matches('/'));
+    let response = reqwest::Client::new()
+        .get(&url)
+        .header("api-token", token)
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await
+        .map_err(|e| format!("Discloud API unreachable: {e}"))?;
+    if !response.status().is_success() {
+        return Err(format!("Discloud API answered {}", response.status()));
+    }
+    let apps: DiscloudApps = response
+        .json()
+        .await
+        .map_err(|e| format!("Unexpected Discloud API response: {e}"))?;
+    Ok(apps.apps)
+}
+
+// ─── Probing ─────────────────────────────────────────────────────────────
+
+/// A host and port to try.
+#[derive(Debug)]
+struct Candidate {
+    name: String,
+    service_type: Option<ServiceType>,
+    host: String,
+    port: u16,
+    source: DiscoverySource,
+}
+
+/// Candidates for every known port of `host`.
+fn every_known_port(name: &str, host: &str, source: DiscoverySource) -> Vec<Candidate> {
+    KNOWN
+        .iter()
+        .map(|(service_type, label, port, _)| Candidate {
+            name: format!("{name} ({label})"),
+            service_type: Some(*service_type),
+            host: host.to_string(),
+            port: *port,
+            source,
+        })
+        .collect()
+}
+
+/// Candidates for every known port of every host of `subnet`.
+fn scan_candidates(subnet: Subnet) -> Vec<Candidate> {
+    subnet
+        .hosts()
+        .flat_map(|ip| {
+            KNOWN
+                .iter()
+                .map(move |(service_type, label, port, _)| Candidate {
+                    name: format!("{label} {ip}"),
+                    service_type: Some(*service_type),
+                    host: ip.to_string(),
+                    port: *port,
+                    source: DiscoverySource::Scan,
+                })
+        })
+        .collect()
+}
+
+/// Resolve `candidate` and try to connect to it.  Returns the address that
+/// accepted the connection.
+async fn probe(candidate: &Candidate, limit: Duration) -> Option<SocketAddr> {
+    let addr = timeout(
+        limit,
+        lookup_host((candidate.host.as_str(), candidate.port)),
+    )
+    .await
+    .ok()?
+    .ok()?
+    .find(SocketAddr::is_ipv4)?;
+    timeout(limit, TcpStream::connect(addr))
+        .await
+        .ok()?
+        .ok()
+        .map(|_| addr)
+}
+
+/// Try every candidate, at most `concurrency` at a time, and keep those
+/// that accepted a connection.  An address found by several candidates is
+/// reported once, under the most specific name.
+async fn probe_all(
+    candidates: Vec<Candidate>,
+    config: &Config,
+    skip: &[IpAddr],
+) -> Vec<DiscoveredService> {
+    let slots = Arc::new(Semaphore::new(config.concurrency));
+    let limit = config.attempt_timeout;
+    let mut tasks = JoinSet::new();
+    for candidate in candidates {
+        let slots = slots.clone();
+        tasks.spawn(async move {
+            let _permit = slots.acquire_owned().await.ok()?;
+            probe(&candidate, limit).await.map(|addr| (candidate, addr))
+        });
+    }
+
+    let mut found: Vec<DiscoveredService> = Vec::new();
+    while let Some(result) = tasks.join_next().await {
+        let Ok(Some((candidate, addr))) = result else {
+            continue;
+        };
+        if addr.ip().is_loopback() || skip.contains(&addr.ip()) {
+            continue;
+        }
+        let service = DiscoveredService {
+            name: candidate.name,
+            service_type: candidate.service_type,
+            target_host: candidate.host,
+            target_port: candidate.port,
+            address: addr.ip(),
+            source: candidate.source,
+            tunnel_id: None,
+        };
+        match found
+            .iter_mut()
+            .find(|s| s.address == service.address && s.target_port == service.target_port)
+        {
+            Some(existing) if service.source < existing.source => *existing = service,
+            Some(_) => {}
+            None => found.push(service),
+        }
+    }
+    found.sort_by(|a, b| {
+        a.source
+            .cmp(&b.source)
+            .then_with(|| a.name.cmp(&b.name))
+            .then_with(|| a.target_port.cmp(&b.target_port))
+    });
+    found
+}
+
+// ─── Discovery ───────────────────────────────────────────────────────────
+
+/// Last result and when it was produced.  Held while scanning, so
+/// concurrent requests share one scan.
+static CACHE: Mutex<Option<(Instant, DiscoveryResponse)>> = Mutex::const_new(None);
+
+/// Services on the VLAN, reusing the last result for
+/// `DISCOVERY_CACHE_SECONDS` unless `refresh` is set.
+pub async fn discover(refresh: bool) -> DiscoveryResponse {
+    let config = Config::from_env();
+    let mut cache = CACHE.lock().await;
+    if let Some((at, response)) = cache.as_ref() {
+        if !refresh && at.elapsed() < config.cache_for {
+            return response.clone();
+        }
+    }
+    let response = run(&config).await;
+    *cache = Some((Instant::now(), response.clone()));
+    response
+}
+
+async fn run(config: &Config) -> DiscoveryResponse {
+    let started = Instant::now();
+    let mut errors = Vec::new();
+    let mut candidates = Vec::new();
+
+    // Names services usually run under, and any configured ones.
+    for (service_type, label, port, hostnames) in KNOWN {
+        candidates.extend(hostnames.iter().map(|host| Candidate {
+            name: label.to_string(),
+            service_type: Some(service_type),
+            host: host.to_string(),
+            port,
+            source: DiscoverySource::Dns,
+        }));
+    }
+    for entry in &config.hostnames {
+        match entry.rsplit_once(':').map(|(h, p)| (h, p.parse::<u16>())) {
+            Some((host, Ok(port))) => candidates.push(Candidate {
+                name: host.to_string(),
+                service_type: known_by_port(port),
+                host: host.to_string(),
+                port,
+                source: DiscoverySource::Dns,
+            }),
+            _ => candidates.extend(every_known_port(entry, entry, DiscoverySource::Dns)),
+        }
+    }
+
+    // Apps listed by the Discloud API.
+    if let Some(token) = &config.api_token {
+        match discloud_apps(config, token).await {
+            Ok(apps) => {
+                for app in apps {
+                    let name = if app.name.is_empty() {
+                        &app.id
+                    } else {
+                        &app.name
+                    };
+                    candidates.extend(every_known_port(name, &app.id, DiscoverySource::Discloud));
+                }
+            }
+            Err(e) => {
+                warn!("Discovery: {e}");
+                errors.push(e);
+            }
+        }
+    }
+
+    // Every host of the VLAN subnet on the known ports.
+    let subnet = match config.subnet.as_deref() {
+        Some("off") => None,
+        Some(value) => value.parse::<Subnet>().map_err(|e| errors.push(e)).ok(),
+        None => vlan_subnet().await,
+    };
+    let mut skip = Vec::new();
+    if let Some(subnet) = subnet {
+        if subnet.size() > config.max_hosts {
+            let e = format!(
+                "Subnet {subnet} has {} addresses, more than DISCOVERY_MAX_HOSTS ({}) — not scanned",
+                subnet.size(),
+                config.max_hosts
+            );
+            warn!("Discovery: {e}");
+            errors.push(e);
+        } else {
+            skip.extend(local_addr_towards(subnet.network).await);
+            candidates.extend(scan_candidates(subnet));
+        }
+    }
+
+    let tried = candidates.len();
+    let services = probe_all(candidates, config, &skip).await;
+    let duration = started.elapsed();
+    info!(
+        "Discovery found {} service(s) in {:.1}s ({tried} attempt(s){})",
+        services.len(),
+        duration.as_secs_f64(),
+        subnet.map(|s| format!(", subnet {s}")).unwrap_or_default()
+    );
+    DiscoveryResponse {
+        services,
+        subnet: subnet.map(|s| s.to_string()),
+        scanned_at: unix_now(),
+        duration_ms: duration.as_millis() as u64,
+        errors,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subnet(s: &str) -> Subnet {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn subnet_parses_cidr_and_masks_the_network() {
+        assert_eq!(subnet("10.0.0.0/24").to_string(), "10.0.0.0/24");
+        assert_eq!(subnet("10.0.0.77/24").to_string(), "10.0.0.0/24");
+        assert_eq!(subnet(" 192.168.1.5 / 16 ").to_string(), "192.168.0.0/16");
+        assert_eq!(subnet("10.1.2.3").to_string(), "10.1.2.3/32");
+        assert_eq!(subnet("10.1.2.3/0").to_string(), "0.0.0.0/0");
+    }
+
+    #[test]
+    fn subnet_rejects_invalid_input() {
+        for bad in [
+            "10.0.0.0/33",
+            "10.0.0.0/255",
+            "10.0.0.0/-1",
+            "10.0.0.0/abc",
+            "10.0.0.0/",
+            "10.0.0/24",
+            "10.0.0.256/24",
+            "fd00::/64",
+            "",
+            "off",
+        ] {
+            assert!(bad.parse::<Subnet>().is_err(), "{bad:?} should not parse");
+        }
+    }
+
+    #[test]
+    fn subnet_size_covers_oversize_ranges() {
+        assert_eq!(subnet("10.0.0.0/32").size(), 1);
+        assert_eq!(subnet("10.0.0.0/24").size(), 256);
+        assert_eq!(subnet("10.0.0.0/22").size(), 1024);
+        assert_eq!(subnet("10.0.0.0/21").size(), 2048);
+        assert_eq!(subnet("10.0.0.0/8").size(), 1 << 24);
+        assert_eq!(subnet("0.0.0.0/0").size(), 1 << 32);
+        // The default DISCOVERY_MAX_HOSTS of 1024 scans a /22 but not a /21.
+        assert!(subnet("10.0.0.0/22").size() <= 1024);
+        assert!(subnet("10.0.0.0/21").size() > 1024);
+    }
+
+    #[test]
+    fn hosts_skip_network_and_broadcast() {
+        let hosts: Vec<_> = subnet("10.0.0.0/30").hosts().collect();
+        assert_eq!(
+            hosts,
+            [Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2)]
+        );
+        let hosts: Vec<_> = subnet("192.168.1.0/24").hosts().collect();
+        assert_eq!(hosts.len(), 254);
+        assert_eq!(hosts[0], Ipv4Addr::new(192, 168, 1, 1));
+        assert_eq!(hosts[253], Ipv4Addr::new(192, 168, 1, 254));
+    }
+
+    #[test]
+    fn hosts_of_point_to_point_and_single_host_subnets() {
+        let hosts: Vec<_> = subnet("10.0.0.4/31").hosts().collect();
+        assert_eq!(
+            hosts,
+            [Ipv4Addr::new(10, 0, 0, 4), Ipv4Addr::new(10, 0, 0, 5)]
+        );
+        let hosts: Vec<_> = subnet("10.0.0.9/32").hosts().collect();
+        assert_eq!(hosts, [Ipv4Addr::new(10, 0, 0, 9)]);
+    }
+
+    #[test]
+    fn hosts_stop_at_the_top_of_the_address_space() {
+        let hosts: Vec<_> = subnet("255.255.255.252/30").hosts().collect();
+        assert_eq!(
+            hosts,
+            [
+                Ipv4Addr::new(255, 255, 255, 253),
+                Ipv4Addr::new(255, 255, 255, 254)
+            ]
+        );
+    }
+
+    #[test]
+    fn scan_candidates_try_every_known_port_of_every_host() {
+        let candidates = scan_candidates(subnet("10.0.0.0/30"));
+        assert_eq!(candidates.len(), 2 * KNOWN.len());
+        let targets: Vec<_> = candidates
+            .iter()
+            .map(|c| (c.host.as_str(), c.port))
+            .collect();
+        assert_eq!(
+            targets,
+            [
+                ("10.0.0.1", 5432),
+                ("10.0.0.1", 3306),
+                ("10.0.0.1", 6379),
+                ("10.0.0.1", 27017),
+                ("10.0.0.2", 5432),
+                ("10.0.0.2", 3306),
+                ("10.0.0.2", 6379),
+                ("10.0.0.2", 27017),
+            ]
+        );
+        assert_eq!(candidates[0].name, "PostgreSQL 10.0.0.1");
+        assert!(candidates
+            .iter()
+            .all(|c| c.source == DiscoverySource::Scan && c.service_type == known_by_port(c.port)));
+    }
+
+    #[test]
+    fn known_by_port_matches_default_ports_only() {
+        assert_eq!(known_by_port(5432), Some(ServiceType::Postgres));
+        assert_eq!(known_by_port(27017), Some(ServiceType::Mongodb));
+        assert_eq!(known_by_port(8080), None);
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::atomic::Ordering;
+use std::sync::LazyLock;
+
+use axum::response::sse::{Event, KeepAlive, Sse};
+use serde::Serialize;
+use tokio::sync::broadcast;
+use tokio::time::Duration;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
+
+use crate::models::{Health, TunnelListItem};
+use crate::state::SharedState;
+
+/// A change pushed to every `/api/events` subscriber.  The SSE event name
+/// is the `type` tag; the data is the whole event as JSON.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ApiEvent {
+    TunnelCreated {
+        tunnel: TunnelListItem,
+    },
+    TunnelUpdated {
+        tunnel: TunnelListItem,
+    },
+    TunnelDeleted {
+        id: String,
+    },
+    /// The forwarder was started or stopped.  `error` is set when it
+    /// stopped because it could not be (re)started.
+    StatusChanged {
+        id: String,
+        running: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    /// The periodic health checks moved the tunnel to another state.
+    HealthChanged {
+        id: String,
+        health: Health,
+        /// Why the last check failed.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    /// Connection counters changed since the last tick.
+    Connections {
+        id: String,
+        active: u64,
+        total: u64,
+        rejected: u64,
+    },
+}
+
+impl ApiEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::TunnelCreated { .. } => "tunnel_created",
+            Self::TunnelUpdated { .. } => "tunnel_updated",
+            Self::TunnelDeleted { .. } => "tunnel_deleted",
+            Self::StatusChanged { .. } => "status_changed",
+            Self::HealthChanged { .. } => "health_changed",
+            Self::Connections { .. } => "connections",
+        }
+    }
+}
+
+static EVENTS: LazyLock<broadcast::Sender<ApiEvent>> = LazyLock::new(|| broadcast::channel(256).0);
+
+/// Send `event` to every connected subscriber.
+pub fn publish(event: ApiEvent) {
+    // No subscribers is the normal case.
+    let _ = EVENTS.send(event);
+}
+
+/// Receive every event published from now on.
+pub fn subscribe() -> broadcast::Receiver<ApiEvent> {
+    EVENTS.subscribe()
+}
+
+/// How often connection counters are compared and published.
+const CONNECTIONS_TICK: Duration = Duration::from_secs(2);
+
+/// Start the task that publishes `connections` events for tunnels whose
+/// counters changed.  It only looks at the tunnels while someone listens.
+pub fn spawn_connection_updates(state: SharedState) {
+    tokio::spawn(async move {
+        let mut last: HashMap<String, (u64, u64, u64)> = HashMap::new();
+        let mut ticker = tokio::time::interval(CONNECTIONS_TICK);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            if EVENTS.receiver_count() == 0 {
+                last.clear();
+                continue;
+            }
+
+            let tunnels = state.read().await;
+            last.retain(|id, _| tunnels.iter().any(|t| &t.id == id));
+            for t in tunnels.iter() {
+                let counts = (
+                    t.stats.active.load(Ordering::Relaxed),
+                    t.stats.connections.load(Ordering::Relaxed),
+                    t.stats.rejected.load(Ordering::Relaxed),
+                );
+                if last.insert(t.id.clone(), counts) != Some(counts) {
+                    publish(ApiEvent::Connections {
+                        id: t.id.clone(),
+                        active: counts.0,
+                        total: counts.1,
+                        rejected: counts.2,
+                    });
+                }
+            }
+        }
+    });
+}
+
+// ─── GET /api/events ────────────────────────────────────────────────────
+
+/// Server-sent stream of [`ApiEvent`]s.  A subscriber that falls too far
+/// behind receives a `resync` event and should reload the tunnel list.
+pub async fn get_events() -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let stream = BroadcastStream::new(subscribe()).map(|item| {
+        Ok(match item {
+            Ok(event) => Event::default()
+                .event(event.name())
+                .json_data(&event)
+                .unwrap_or_else(|_| Event::default().event("resync").data("{}")),
+            Err(BroadcastStreamRecvError::Lagged(_)) => Event::default().event("resync").data("{}"),
+        })
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
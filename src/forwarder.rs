@@ -0,0 +1,466 @@
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::{timeout, Duration};
+use tracing::{debug, info, info_span, warn, Instrument};
+
+use crate::metrics::TunnelStats;
+use crate::tailscale;
+
+// ─── Source allowlist ────────────────────────────────────────────────────
+
+/// A single entry of a tunnel's `allowed_sources` list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SourceRule {
+    /// A single tailnet (or VLAN) address, e.g. `100.101.102.103`.
+    Addr(IpAddr),
+    /// An address range in CIDR notation, e.g. `100.64.0.0/10`.
+    Cidr(IpAddr, u8),
+    /// A Tailscale ACL tag, e.g. `tag:dev`, matched against the tags of
+    /// the connecting node as reported by the LocalAPI.
+    Tag(String),
+}
+
+impl FromStr for SourceRule {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(name) = s.strip_prefix("tag:") {
+            let valid = !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+            return if valid {
+                Ok(SourceRule::Tag(s.to_string()))
+            } else {
+                Err(())
+            };
+        }
+        if let Some((addr, bits)) = s.split_once('/') {
+            let addr: IpAddr = addr.parse().map_err(|_| ())?;
+            let bits: u8 = bits.parse().map_err(|_| ())?;
+            let max = if addr.is_ipv4() { 32 } else { 128 };
+            if bits > max {
+                return Err(());
+            }
+            return Ok(SourceRule::Cidr(addr, bits));
+        }
+        s.parse().map(SourceRule::Addr).map_err(|_| ())
+    }
+}
+
+impl SourceRule {
+    fn matches_ip(&self, ip: IpAddr) -> bool {
+        match (self, ip) {
+            (SourceRule::Addr(a), ip) => *a == canonical(ip),
+            (SourceRule::Cidr(IpAddr::V4(net), bits), ip) => match canonical(ip) {
+                IpAddr::V4(ip) => {
+                    let mask = u32::MAX.checked_shl(32 - *bits as u32).unwrap_or(0);
+                    u32::from(ip) & mask == u32::from(*net) & mask
+                }
+                IpAddr::V6(_) => false,
+            },
+            (SourceRule::Cidr(IpAddr::V6(net), bits), ip) => match canonical(ip) {
+                IpAddr::V6(ip) => {
+                    let mask = u128::MAX.checked_shl(128 - *bits as u32).unwrap_or(0);
+                    u128::from(ip) & mask == u128::from(*net) & mask
+                }
+                IpAddr::V4(_) => false,
+            },
+            (SourceRule::Tag(_), _) => false,
+        }
+    }
+}
+
+/// Map IPv4-mapped IPv6 peers (`::ffff:a.b.c.d`) back to plain IPv4 so
+/// they compare equal to IPv4 rules.
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6
+            .to_ipv4_mapped()
+            .map(IpAddr::V4)
+            .unwrap_or(IpAddr::V6(v6)),
+        v4 => v4,
+    }
+}
+
+/// Parse a list of `allowed_sources` entries.
+/// On failure, returns the first entry that could not be parsed.
+pub fn parse_sources(sources: &[String]) -> Result<Vec<SourceRule>, String> {
+    sources
+        .iter()
+        .map(|s| s.parse().map_err(|_| s.clone()))
+        .collect()
+}
+
+/// How long a connection waits for tailscaled to identify its peer before
+/// it is refused.
+const WHOIS_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Decide whether a peer may use a tunnel restricted to `rules`.
+///
+/// The peer is first resolved to a tailnet node through the LocalAPI so
+/// that connections proxied by userspace-mode tailscaled (which appear to
+/// come from `127.0.0.1`) are matched against the node's real tailnet IPs
+/// and tags.  Peers that are not tailnet nodes are matched by socket
+/// address only, and peers tailscaled does not identify within
+/// [`WHOIS_TIMEOUT`] are refused.  Returns a human-readable identity for
+/// logging.
+async fn check_source(rules: &[SourceRule], peer: SocketAddr) -> (bool, String) {
+    let Ok(node) = timeout(WHOIS_TIMEOUT, tailscale::whois(peer)).await else {
+        warn!("Tailnet lookup of {peer} timed out");
+        return (false, format!("{peer} (lookup timed out)"));
+    };
+    match node {
+        Some(node) => {
+            let ips = node.ips();
+            let allowed = rules.iter().any(|rule| match rule {
+                SourceRule::Tag(tag) => node.tags.iter().any(|t| t == tag),
+                rule => ips.iter().any(|ip| rule.matches_ip(*ip)),
+            });
+            let name = node.name.trim_end_matches('.');
+            (allowed, format!("{peer} ({name})"))
+        }
+        None => {
+            let allowed = rules.iter().any(|rule| rule.matches_ip(peer.ip()));
+            (allowed, peer.to_string())
+        }
+    }
+}
+
+// ─── Forwarder ───────────────────────────────────────────────────────────
+
+/// Handle to a running in-process forwarder.  Clones share the same
+/// listener; stopping any of them closes the listener and every open
+/// connection.
+#[derive(Debug, Clone)]
+pub struct ForwarderHandle {
+    /// The address the listener is bound to.
+    pub local_addr: SocketAddr,
+    shutdown: Arc<watch::Sender<bool>>,
+    task: Arc<Mutex<Option<JoinHandle<()>>>>,
+}
+
+impl ForwarderHandle {
+    /// Stop accepting, drop every open connection and wait until the
+    /// listening socket has been released.
+    pub async fn stop(&self) {
+        let _ = self.shutdown.send(true);
+        let task = self.task.lock().unwrap().take();
+        if let Some(task) = task {
+            let _ = task.await;
+        }
+    }
+}
+
+/// Start forwarding `listen_ip:local_port` -> `target_host:target_port`.
+///
+/// The listening socket is bound before returning, so a port conflict is
+/// reported immediately as an error.  When `allowed_sources` is non-empty,
+/// every accepted connection is checked against it before the target is
+/// dialed; refused peers are counted and logged.  Connection and byte
+/// counters are recorded into `stats`, which outlives the forwarder.
+///
+/// The forwarder's tasks log inside the caller's current span, so callers
+/// should run this within the tunnel's span (see `logging::tunnel_span`).
+pub async fn spawn_forwarder(
+    listen_ip: IpAddr,
+    local_port: u16,
+    target_host: &str,
+    target_port: u16,
+    allowed_sources: &[String],
+    stats: Arc<TunnelStats>,
+) -> Result<ForwarderHandle, String> {
+    let rules =
+        parse_sources(allowed_sources).map_err(|s| format!("Invalid allowed source '{s}'"))?;
+
+    let local_addr = SocketAddr::new(listen_ip, local_port);
+    let listener = TcpListener::bind(local_addr).await.map_err(|e| {
+        let msg = format!("Failed to listen on {local_addr}: {e}");
+        warn!("{msg}");
+        msg
+    })?;
+
+    let target = format!("{target_host}:{target_port}");
+    if rules.is_empty() {
+        info!("Listening on {local_addr} -> {target}");
+    } else {
+        info!(
+            "Listening on {local_addr} -> {target} (allowed: {})",
+            allowed_sources.join(", ")
+        );
+    }
+
+    let (shutdown, shutdown_rx) = watch::channel(false);
+    let task = tokio::spawn(
+        accept_loop(listener, target, Arc::new(rules), stats, shutdown_rx).in_current_span(),
+    );
+
+    Ok(ForwarderHandle {
+        local_addr,
+        shutdown: Arc::new(shutdown),
+        task: Arc::new(Mutex::new(Some(task))),
+    })
+}
+
+async fn accept_loop(
+    listener: TcpListener,
+    target: String,
+    rules: Arc<Vec<SourceRule>>,
+    stats: Arc<TunnelStats>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let local_addr = listener.local_addr().ok();
+
+    loop {
+        let (inbound, peer) = tokio::select! {
+            _ = shutdown.changed() => break,
+            accepted = listener.accept() => match accepted {
+                Ok(conn) => conn,
+                Err(e) => {
+                    warn!("Accept error: {e}");
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            },
+        };
+
+        let target = target.clone();
+        let rules = rules.clone();
+        let stats = stats.clone();
+        let mut shutdown = shutdown.clone();
+
+        let span = info_span!("connection", %peer);
+        tokio::spawn(async move {
+            if !rules.is_empty() {
+                let (allowed, who) = check_source(&rules, peer).await;
+                if !allowed {
+                    let total = stats.rejected.fetch_add(1, Ordering::Relaxed) + 1;
+                    warn!(
+                        "Rejected connection from {who} — not in allowed sources ({total} rejected so far)"
+                    );
+                    return;
+                }
+            }
+
+            debug!("Accepted connection from {peer}");
+            stats.connections.fetch_add(1, Ordering::Relaxed);
+            stats.active.fetch_add(1, Ordering::Relaxed);
+            let inbound = Metered {
+                inner: inbound,
+                stats: stats.clone(),
+            };
+            tokio::select! {
+                _ = shutdown.changed() => {}
+                _ = forward(inbound, peer, &target, &stats) => {}
+            }
+            stats.active.fetch_sub(1, Ordering::Relaxed);
+        }.instrument(span));
+    }
+
+    if let Some(local_addr) = local_addr {
+        info!("Stopped listening on {local_addr}");
+    }
+}
+
+async fn forward(
+    mut inbound: Metered<TcpStream>,
+    peer: SocketAddr,
+    target: &str,
+    stats: &TunnelStats,
+) {
+    let mut outbound = match timeout(Duration::from_secs(10), TcpStream::connect(target)).await {
+        Ok(Ok(stream)) => stream,
+        Ok(Err(e)) => {
+            stats.connect_failures.fetch_add(1, Ordering::Relaxed);
+            warn!("{peer}: connect to {target} failed: {e}");
+            return;
+        }
+        Err(_) => {
+            stats.connect_failures.fetch_add(1, Ordering::Relaxed);
+            warn!("{peer}: connect to {target} timed out");
+            return;
+        }
+    };
+
+    let _ = inbound.inner.set_nodelay(true);
+    let _ = outbound.set_nodelay(true);
+
+    match tokio::io::copy_bidirectional(&mut inbound, &mut outbound).await {
+        Ok((sent, received)) => {
+            debug!("{peer}: connection closed ({sent} bytes in, {received} bytes out)")
+        }
+        Err(e) => debug!("{peer}: connection closed: {e}"),
+    }
+}
+
+/// Client-side stream wrapper that counts bytes into the tunnel's stats as
+/// they flow, so long-lived connections show up in metrics before closing.
+struct Metered<S> {
+    inner: S,
+    stats: Arc<TunnelStats>,
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Metered<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let result = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = result {
+            let read = (buf.filled().len() - before) as u64;
+            self.stats.bytes_in.fetch_add(read, Ordering::Relaxed);
+        }
+        result
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Metered<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = result {
+            self.stats
+                .bytes_out
+                .fetch_add(written as u64, Ordering::Relaxed);
+        }
+        result
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(s: &str) -> SourceRule {
+        s.parse().unwrap_or_else(|_| panic!("'{s}' should parse"))
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_addresses() {
+        assert_eq!(
+            rule("100.101.102.103"),
+            SourceRule::Addr(ip("100.101.102.103"))
+        );
+        assert_eq!(
+            rule(" fd7a:115c:a1e0::1 "),
+            SourceRule::Addr(ip("fd7a:115c:a1e0::1"))
+        );
+    }
+
+    #[test]
+    fn parses_cidrs() {
+        assert_eq!(
+            rule("100.64.0.0/10"),
+            SourceRule::Cidr(ip("100.64.0.0"), 10)
+        );
+        assert_eq!(
+            rule("fd7a:115c:a1e0::/48"),
+            SourceRule::Cidr(ip("fd7a:115c:a1e0::"), 48)
+        );
+        assert_eq!(rule("0.0.0.0/0"), SourceRule::Cidr(ip("0.0.0.0"), 0));
+    }
+
+    #[test]
+    fn parses_tags() {
+        assert_eq!(rule("tag:dev"), SourceRule::Tag("tag:dev".to_string()));
+        assert_eq!(
+            rule("tag:ci-runner_2"),
+            SourceRule::Tag("tag:ci-runner_2".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        for input in [
+            "",
+            "tag:",
+            "tag:has space",
+            "tag:dev!",
+            "100.64.0.0/33",
+            "fd7a::/129",
+            "100.64.0.0/",
+            "100.64.0.0/x",
+            "/24",
+            "100.64.0",
+            "host.example",
+        ] {
+            assert!(
+                input.parse::<SourceRule>().is_err(),
+                "'{input}' should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_sources_reports_first_invalid_entry() {
+        let sources = vec![
+            "100.64.0.1".to_string(),
+            "bogus".to_string(),
+            "nope".to_string(),
+        ];
+        assert_eq!(parse_sources(&sources), Err("bogus".to_string()));
+        assert_eq!(parse_sources(&[]), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn matches_ipv4_cidr() {
+        let cgnat = rule("100.64.0.0/10");
+        assert!(cgnat.matches_ip(ip("100.64.0.1")));
+        assert!(cgnat.matches_ip(ip("100.127.255.255")));
+        assert!(!cgnat.matches_ip(ip("100.128.0.0")));
+        assert!(!cgnat.matches_ip(ip("fd7a:115c:a1e0::1")));
+        assert!(rule("0.0.0.0/0").matches_ip(ip("8.8.8.8")));
+        assert!(rule("10.0.0.5/32").matches_ip(ip("10.0.0.5")));
+        assert!(!rule("10.0.0.5/32").matches_ip(ip("10.0.0.6")));
+    }
+
+    #[test]
+    fn matches_ipv6_cidr() {
+        let tailnet = rule("fd7a:115c:a1e0::/48");
+        assert!(tailnet.matches_ip(ip("fd7a:115c:a1e0::1")));
+        assert!(tailnet.matches_ip(ip("fd7a:115c:a1e0:ffff::1")));
+        assert!(!tailnet.matches_ip(ip("fd7a:115c:a1e1::1")));
+        assert!(!tailnet.matches_ip(ip("100.64.0.1")));
+        assert!(rule("::/0").matches_ip(ip("2001:db8::1")));
+    }
+
+    #[test]
+    fn matches_ipv4_mapped_peers_as_ipv4() {
+        let mapped = ip("::ffff:100.64.0.1");
+        assert!(rule("100.64.0.0/10").matches_ip(mapped));
+        assert!(rule("100.64.0.1").matches_ip(mapped));
+    }
+
+    #[test]
+    fn tags_never_match_addresses() {
+        assert!(!rule("tag:dev").matches_ip(ip("100.64.0.1")));
+    }
+}
@@ -0,0 +1,82 @@
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+use tokio::net::TcpStream;
+use tokio::time::{timeout, Duration};
+use tracing::{info, warn, Instrument};
+
+use crate::events::{publish, ApiEvent};
+use crate::limits::env_u64;
+use crate::logging::tunnel_span;
+use crate::models::{Health, HealthCheck};
+use crate::state::SharedState;
+
+/// Start the periodic health checker.  Every `HEALTH_CHECK_INTERVAL` seconds
+/// (default 60, `0` disables) each running tunnel's target is dialed once
+/// and the connect latency or failure is recorded in the tunnel's stats.
+/// A failed check degrades the tunnel; `HEALTH_DOWN_AFTER` consecutive
+/// failures (default 3) mark it down.
+pub fn spawn_health_checks(state: SharedState) {
+    let interval = env_u64("HEALTH_CHECK_INTERVAL", 60);
+    let down_after = env_u64("HEALTH_DOWN_AFTER", 3).max(1);
+    if interval == 0 {
+        info!("Periodic health checks disabled");
+        return;
+    }
+    info!("Checking tunnel targets every {interval}s");
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            check_all(&state, down_after).await;
+        }
+    });
+}
+
+async fn check_all(state: &SharedState, down_after: u64) {
+    // Snapshot targets so the lock is not held while dialing.
+    let targets: Vec<_> = state
+        .read()
+        .await
+        .iter()
+        .filter(|t| t.forwarder.is_some() && t.health_check == HealthCheck::Tcp)
+        .map(|t| {
+            (
+                t.id.clone(),
+                tunnel_span(&t.id, &t.name, t.local_port),
+                format!("{}:{}", t.target_host, t.target_port),
+                t.stats.clone(),
+            )
+        })
+        .collect();
+
+    for (id, span, target, stats) in targets {
+        async {
+            let started = Instant::now();
+            let error = match timeout(Duration::from_secs(5), TcpStream::connect(&target)).await {
+                Ok(Ok(_)) => {
+                    stats.health_latency.observe(started.elapsed());
+                    None
+                }
+                Ok(Err(e)) => Some(format!("{target} unreachable: {e}")),
+                Err(_) => Some(format!("{target} timed out")),
+            };
+            if let Some(error) = &error {
+                stats.health_failures.fetch_add(1, Ordering::Relaxed);
+                warn!("Health check: target {error}");
+            }
+            if let Some(health) = stats.record_health(error.is_none(), down_after) {
+                if health == Health::Down {
+                    warn!(
+                        "Health check: target {target} is down after {down_after} failed check(s)"
+                    );
+                }
+                publish(ApiEvent::HealthChanged { id, health, error });
+            }
+        }
+        .instrument(span)
+        .await;
+    }
+}
@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, RwLock};
+
+use tracing::warn;
+
+use crate::assets;
+use crate::models::ApiMessage;
+
+/// Locale used when a notification channel does not set one.
+pub const FALLBACK_LOCALE: &str = "en";
+
+type Catalog = Arc<HashMap<String, String>>;
+
+static CATALOGS: LazyLock<RwLock<HashMap<String, Catalog>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// The translations for `locale` (e.g. `pt-BR`), loaded from the
+/// dashboard's `i18n/<locale>.json` on first use.  `None` when the locale does
+/// not exist.
+pub fn catalog(locale: &str) -> Option<Catalog> {
+    if locale.is_empty()
+        || !locale
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return None;
+    }
+    if let Some(catalog) = CATALOGS.read().unwrap().get(locale) {
+        return Some(catalog.clone());
+    }
+
+    let path = format!("i18n/{locale}.json");
+    let contents = assets::read(&path)?;
+    let strings: HashMap<String, String> = match serde_json::from_slice(&contents) {
+        Ok(strings) => strings,
+        Err(e) => {
+            warn!("Could not parse {path}: {e}");
+            return None;
+        }
+    };
+    let catalog = Arc::new(strings);
+    CATALOGS
+        .write()
+        .unwrap()
+        .insert(locale.to_string(), catalog.clone());
+    Some(catalog)
+}
+
+/// Render `message` in `locale`, falling back to English and then to the
+/// message id, with `{param}` placeholders substituted like the dashboard
+/// does.
+pub fn translate(locale: &str, message: &ApiMessage) -> String {
+    let template = [locale, FALLBACK_LOCALE]
+        .into_iter()
+        .filter_map(catalog)
+        .find_map(|c| c.get(&message.id).cloned())
+        .unwrap_or_else(|| message.id.clone());
+
+    message.params.iter().fold(template, |text, (key, value)| {
+        let value = match value {
+            serde_json::Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+        text.replace(&format!("{{{key}}}"), &value)
+    })
+}
@@ -0,0 +1,350 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{ConnectInfo, Extension, MatchedPath, Request, State},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use tokio::sync::{OnceCell, Semaphore, SemaphorePermit};
+use tracing::{info, warn};
+
+use crate::config::Settings;
+use crate::routes::{api_err_params, params1};
+use crate::tailscale;
+
+/// Read a positive integer from the environment, falling back to `default`.
+pub fn env_u64(name: &str, default: u64) -> u64 {
+    match std::env::var(name) {
+        Ok(value) => value.trim().parse().unwrap_or_else(|_| {
+            warn!("Invalid {name} '{value}' — using {default}");
+            default
+        }),
+        Err(_) => default,
+    }
+}
+
+/// Build a `429 Too Many Requests` response carrying an `ApiMessage` with a
+/// `retry_after` param (seconds) and the matching `Retry-After` header.
+pub fn too_many_requests(id: &str, retry_after: Duration) -> Response {
+    let secs = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+    let mut response = (
+        StatusCode::TOO_MANY_REQUESTS,
+        Json(api_err_params(id, params1("retry_after", secs))),
+    )
+        .into_response();
+    response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+    response
+}
+
+/// Identify the client behind a request for rate limiting and lockout.
+/// Tailnet clients reach the API through tailscaled, so loopback peers are
+/// resolved to their tailnet IP through the LocalAPI.
+pub async fn client_ip(peer: SocketAddr) -> IpAddr {
+    if peer.ip().is_loopback() {
+        if let Some(ip) = tailscale::whois(peer)
+            .await
+            .and_then(|node| node.ips().into_iter().next())
+        {
+            return ip;
+        }
+    }
+    peer.ip()
+}
+
+/// The client behind a request, set by [`identify_client`] as a request
+/// extension.  Its IP is resolved with [`client_ip`] on first use and
+/// shared by the middlewares and the handler, so each request looks the
+/// peer up at most once.
+#[derive(Debug, Clone)]
+pub struct RequestClient {
+    peer: SocketAddr,
+    ip: Arc<OnceCell<IpAddr>>,
+}
+
+impl RequestClient {
+    pub async fn ip(&self) -> IpAddr {
+        *self.ip.get_or_init(|| client_ip(self.peer)).await
+    }
+}
+
+/// Middleware attaching a [`RequestClient`] to each request.
+pub async fn identify_client(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    request.extensions_mut().insert(RequestClient {
+        peer,
+        ip: Arc::new(OnceCell::new()),
+    });
+    next.run(request).await
+}
+
+// ─── Token buckets ───────────────────────────────────────────────────────
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Per-client token buckets allowing `per_minute` requests with bursts of
+/// the same size.
+#[derive(Debug)]
+struct RateLimiter {
+    per_minute: f64,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+impl RateLimiter {
+    fn new(per_minute: u64) -> Self {
+        Self {
+            per_minute: per_minute.max(1) as f64,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take one token for `client`.  Returns how long to wait when the
+    /// bucket is empty.
+    fn check(&self, client: IpAddr) -> Result<(), Duration> {
+        self.check_at(client, Instant::now())
+    }
+
+    fn check_at(&self, client: IpAddr, now: Instant) -> Result<(), Duration> {
+        let rate = self.per_minute / 60.0;
+        let mut buckets = self.buckets.lock().unwrap();
+
+        // Forget clients whose bucket has been full for a while.
+        if buckets.len() > 256 {
+            let idle = Duration::from_secs(120);
+            buckets.retain(|_, b| now.duration_since(b.updated) < idle);
+        }
+
+        let bucket = buckets.entry(client).or_insert(Bucket {
+            tokens: self.per_minute,
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(self.per_minute);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+        }
+    }
+}
+
+// ─── Limits ──────────────────────────────────────────────────────────────
+
+/// How an API route is limited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RouteClass {
+    /// Changes state (create / update / delete).
+    Mutating,
+    /// Opens outbound connections or spawns processes.
+    Probing,
+    /// Both of the above (create / update run a reachability pre-check).
+    MutatingProbe,
+}
+
+fn classify(method: &Method, route: &str) -> Option<RouteClass> {
+    match (method, route) {
+        (&Method::POST, "/api/test")
+        | (&Method::POST, "/api/webhooks/:id/test")
+        | (&Method::POST, "/api/tailscale/peers/:ip/ping")
+        | (&Method::GET, "/api/discovery") => Some(RouteClass::Probing),
+        (&Method::POST, "/api/tunnels") | (&Method::PUT, "/api/tunnels/:id") => {
+            Some(RouteClass::MutatingProbe)
+        }
+        (&Method::GET, _) | (&Method::HEAD, _) | (&Method::OPTIONS, _) => None,
+        _ => Some(RouteClass::Mutating),
+    }
+}
+
+/// Shared rate-limiting state for the API.
+#[derive(Debug)]
+pub struct Limits {
+    mutations: RateLimiter,
+    probes: RateLimiter,
+    probe_slots: Semaphore,
+}
+
+impl Limits {
+    /// Build limits from `RATE_LIMIT_PER_MINUTE` (mutating requests per
+    /// client), `PROBE_RATE_LIMIT_PER_MINUTE` (probing requests per client)
+    /// and `MAX_CONCURRENT_PROBES` (probes running at once, all clients).
+    pub fn from_settings(settings: &Settings) -> Self {
+        let mutations = settings.rate_limit_per_minute;
+        let probes = settings.probe_rate_limit_per_minute;
+        let slots = settings.max_concurrent_probes as usize;
+        info!(
+            "{mutations} mutation(s)/min, {probes} probe(s)/min per client, {slots} concurrent probe(s)"
+        );
+        Self::new(mutations, probes, slots)
+    }
+
+    fn new(mutations: u64, probes: u64, slots: usize) -> Self {
+        Self {
+            mutations: RateLimiter::new(mutations),
+            probes: RateLimiter::new(probes),
+            probe_slots: Semaphore::new(slots),
+        }
+    }
+
+    /// One of the concurrent probe slots, held until dropped.
+    fn probe_slot(&self) -> Option<SemaphorePermit<'_>> {
+        self.probe_slots.try_acquire().ok()
+    }
+}
+
+/// Middleware enforcing per-client rate limits on mutating and probing
+/// routes, plus the global cap on concurrent probes.
+pub async fn rate_limit(
+    State(limits): State<Arc<Limits>>,
+    Extension(client): Extension<RequestClient>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+
+    let Some(class) = classify(request.method(), &route) else {
+        return next.run(request).await;
+    };
+
+    let client = client.ip().await;
+    let label = format!("{} {route}", request.method());
+
+    if class != RouteClass::Probing {
+        if let Err(wait) = limits.mutations.check(client) {
+            warn!(%client, "Rate limited on {label}");
+            return too_many_requests("api.error.rate_limited", wait);
+        }
+    }
+
+    if class == RouteClass::Mutating {
+        return next.run(request).await;
+    }
+
+    if let Err(wait) = limits.probes.check(client) {
+        warn!(%client, "Probe rate limited on {label}");
+        return too_many_requests("api.error.rate_limited", wait);
+    }
+
+    // Hold a probe slot for the whole request.
+    let Some(_permit) = limits.probe_slot() else {
+        warn!(%client, "Turned away from {label} — all probe slots busy");
+        return too_many_requests("api.error.probes_busy", Duration::from_secs(1));
+    };
+
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLIENT: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(100, 64, 0, 1));
+    const OTHER: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(100, 64, 0, 2));
+
+    #[test]
+    fn allows_a_burst_then_refills_over_time() {
+        let limiter = RateLimiter::new(6);
+        let start = Instant::now();
+        for _ in 0..6 {
+            assert_eq!(limiter.check_at(CLIENT, start), Ok(()));
+        }
+        // One token comes back every 10s
+        let wait = limiter.check_at(CLIENT, start).unwrap_err();
+        assert_eq!(wait.as_secs(), 10);
+        assert!(limiter
+            .check_at(CLIENT, start + Duration::from_secs(5))
+            .is_err());
+        let refilled = start + Duration::from_secs(15);
+        assert_eq!(limiter.check_at(CLIENT, refilled), Ok(()));
+        assert!(limiter.check_at(CLIENT, refilled).is_err());
+    }
+
+    #[test]
+    fn refill_is_capped_at_the_burst_size() {
+        let limiter = RateLimiter::new(2);
+        let start = Instant::now();
+        assert_eq!(limiter.check_at(CLIENT, start), Ok(()));
+        let later = start + Duration::from_secs(3600);
+        assert_eq!(limiter.check_at(CLIENT, later), Ok(()));
+        assert_eq!(limiter.check_at(CLIENT, later), Ok(()));
+        assert!(limiter.check_at(CLIENT, later).is_err());
+    }
+
+    #[test]
+    fn clients_have_their_own_buckets() {
+        let limiter = RateLimiter::new(1);
+        let now = Instant::now();
+        assert_eq!(limiter.check_at(CLIENT, now), Ok(()));
+        assert!(limiter.check_at(CLIENT, now).is_err());
+        assert_eq!(limiter.check_at(OTHER, now), Ok(()));
+    }
+
+    #[test]
+    fn caps_concurrent_probes() {
+        let limits = Limits::new(30, 10, 2);
+        let first = limits.probe_slot().expect("first slot");
+        let _second = limits.probe_slot().expect("second slot");
+        assert!(limits.probe_slot().is_none());
+        drop(first);
+        assert!(limits.probe_slot().is_some());
+    }
+
+    #[test]
+    fn classifies_routes() {
+        let class = |method: Method, route| classify(&method, route);
+        assert_eq!(class(Method::GET, "/api/tunnels"), None);
+        assert_eq!(
+            class(Method::GET, "/api/discovery"),
+            Some(RouteClass::Probing)
+        );
+        assert_eq!(class(Method::POST, "/api/test"), Some(RouteClass::Probing));
+        assert_eq!(
+            class(Method::POST, "/api/tailscale/peers/:ip/ping"),
+            Some(RouteClass::Probing)
+        );
+        assert_eq!(
+            class(Method::POST, "/api/tunnels"),
+            Some(RouteClass::MutatingProbe)
+        );
+        assert_eq!(
+            class(Method::PUT, "/api/tunnels/:id"),
+            Some(RouteClass::MutatingProbe)
+        );
+        assert_eq!(
+            class(Method::DELETE, "/api/tunnels/:id"),
+            Some(RouteClass::Mutating)
+        );
+        assert_eq!(
+            class(Method::POST, "/api/webhooks"),
+            Some(RouteClass::Mutating)
+        );
+    }
+
+    #[tokio::test]
+    async fn request_client_keeps_its_resolved_ip() {
+        let client = RequestClient {
+            peer: "127.0.0.1:40000".parse().unwrap(),
+            ip: Arc::new(OnceCell::new_with(Some(CLIENT))),
+        };
+        assert_eq!(client.clone().ip().await, CLIENT);
+        assert_eq!(client.ip().await, CLIENT);
+    }
+}
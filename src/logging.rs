@@ -0,0 +1,85 @@
+use std::io::IsTerminal;
+
+use axum::extract::{MatchedPath, Request};
+use tracing::{Level, Span};
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{fmt, EnvFilter};
+
+use crate::tunnel_log::TunnelLogLayer;
+
+/// Install the global `tracing` subscriber.
+///
+/// - `LOG_LEVEL` — level or filter directives, e.g. `debug` or
+///   `info,api::forwarder=debug` (default `info`)
+/// - `LOG_FORMAT` — `text` (default) or `json`, one object per line with
+///   every enclosing span (request, tunnel, connection) under `spans`
+///
+/// Per-tunnel ring buffers always receive this crate's `debug` events,
+/// whatever `LOG_LEVEL` says.
+pub fn init() {
+    let filter = EnvFilter::try_from_env("LOG_LEVEL").unwrap_or_else(|_| EnvFilter::new("info"));
+    let format = std::env::var("LOG_FORMAT").unwrap_or_default();
+
+    let output = if format.trim().eq_ignore_ascii_case("json") {
+        fmt::layer()
+            .json()
+            .flatten_event(true)
+            .with_current_span(false)
+            .with_span_list(true)
+            .boxed()
+    } else {
+        // Discloud's log viewer does not render colour codes.
+        fmt::layer()
+            .with_ansi(std::io::stdout().is_terminal())
+            .boxed()
+    };
+
+    let tunnel_logs = TunnelLogLayer.with_filter(filter_fn(|meta| {
+        *meta.level() <= Level::DEBUG && meta.target().starts_with(env!("CARGO_CRATE_NAME"))
+    }));
+
+    tracing_subscriber::registry()
+        .with(output.with_filter(filter))
+        .with(tunnel_logs)
+        .init();
+}
+
+/// Span wrapping one API request.  Handlers that act on a tunnel fill in
+/// the `tunnel.*` fields with [`record_tunnel`].
+pub fn request_span(request: &Request) -> Span {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str())
+        .unwrap_or_else(|| request.uri().path());
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        route,
+        tunnel.id = tracing::field::Empty,
+        tunnel.name = tracing::field::Empty,
+        tunnel.port = tracing::field::Empty,
+    )
+}
+
+/// Attach a tunnel's identity to the current request span.
+pub fn record_tunnel(id: &str, name: &str, port: u16) {
+    let span = Span::current();
+    span.record("tunnel.id", id);
+    span.record("tunnel.name", name);
+    span.record("tunnel.port", port);
+}
+
+/// Root span for everything logged on behalf of a running tunnel.  It has
+/// no parent, so forwarder logs are not attributed to the request that
+/// happened to start the tunnel.
+pub fn tunnel_span(id: &str, name: &str, port: u16) -> Span {
+    tracing::info_span!(
+        parent: None,
+        "tunnel",
+        tunnel.id = %id,
+        tunnel.name = %name,
+        tunnel.port = port,
+    )
+}
@@ -1,49 +1,195 @@
+mod assets;
+mod audit;
+mod auth;
+mod config;
+mod discord;
+mod discovery;
+mod events;
+mod forwarder;
+mod health;
+mod i18n;
+mod limits;
+mod logging;
+mod metrics;
 mod models;
+mod ports;
+mod readiness;
 mod routes;
+mod serve;
 mod state;
+mod tailscale;
+mod tailscaled;
+mod templates;
+mod tunnel_log;
+mod webhooks;
 
+use std::net::SocketAddr;
 use std::sync::Arc;
 
-use axum::{routing::get, routing::post, routing::put, Router};
+use axum::{middleware, routing::get, routing::post, routing::put, Extension, Router, ServiceExt};
+use tokio::signal::unix::{signal, SignalKind};
 use tokio::sync::RwLock;
+use tower::Layer;
 use tower_http::services::ServeDir;
+use tower_http::trace::TraceLayer;
+use tracing::info;
 
+use crate::auth::{require_token, Auth};
+use crate::events::get_events;
+use crate::limits::{identify_client, rate_limit, Limits};
+use crate::metrics::{get_metrics, track_requests};
+use crate::readiness::{get_healthz, get_readyz};
 use crate::routes::{
-    create_tunnel, delete_tunnel, get_config, list_tunnels, test_endpoint, update_tunnel,
+    create_tunnel, delete_tunnel, get_config, get_discovery, get_tailscale_login,
+    get_tailscale_peers, get_tailscale_status, get_tunnel_logs, list_tunnels, ping_tailscale_peer,
+    start_tailscale_login, test_endpoint, update_tunnel,
 };
 use crate::state::{load_tunnels, restore_tunnels, SharedState};
+use crate::templates::{
+    create_template, delete_template, list_templates, update_template, SharedTemplates, Templates,
+};
+use crate::webhooks::{
+    create_webhook, delete_webhook, list_deliveries, list_webhooks, test_webhook, update_webhook,
+    SharedWebhooks, Webhooks,
+};
 
 #[tokio::main]
 async fn main() {
-    println!("[main] Tailscale Tunnel Manager starting...");
+    let settings = config::init();
+    logging::init();
+    info!("Tailscale Tunnel Manager starting...");
+    metrics::init();
+    ports::init(settings.port);
+    config::check_ports(ports::policy());
 
     // Load persisted tunnels from disk
     let tunnels = load_tunnels().await;
-    println!("[main] Loaded {} tunnel(s) from disk", tunnels.len());
+    info!("Loaded {} tunnel(s) from disk", tunnels.len());
+
+    // Optionally supervise tailscaled; it logs in while the API starts
+    if tailscaled::enabled() {
+        tailscaled::start();
+    }
+
+    // Work out where "Tailscale only" tunnels should listen
+    let tailscale_ip = tailscale::discover_bind_ip().await;
+    info!("Tailscale-only tunnels will bind to {tailscale_ip}");
 
     let state: SharedState = Arc::new(RwLock::new(tunnels));
+    tailscale::spawn_identity_refresh(state.clone());
+
+    // Webhooks are dispatched from the start so restore failures are sent
+    let webhooks: SharedWebhooks = Arc::new(Webhooks::load().await);
+    webhooks::spawn_dispatcher(webhooks.clone(), state.clone());
+
+    // Turn Funnel off once it expires
+    serve::spawn_funnel_expiry(state.clone());
+
+    // Periodically probe tunnel targets for the metrics endpoint
+    health::spawn_health_checks(state.clone());
+
+    // Push connection counts to /api/events subscribers
+    events::spawn_connection_updates(state.clone());
 
-    // Restore enabled tunnels
-    restore_tunnels(&state).await;
+    // Built-in and user tunnel templates
+    let templates: SharedTemplates = Arc::new(Templates::load().await);
 
-    // Serve static frontend files from public/
-    let serve_dir = ServeDir::new("./public/");
+    // Rate limits and optional API token
+    let limits = Arc::new(Limits::from_settings(settings));
+    let auth = Arc::new(Auth::from_settings(settings));
+
+    let webhook_routes = Router::new()
+        .route("/api/webhooks", get(list_webhooks).post(create_webhook))
+        .route(
+            "/api/webhooks/:id",
+            put(update_webhook).delete(delete_webhook),
+        )
+        .route("/api/webhooks/:id/deliveries", get(list_deliveries))
+        .route("/api/webhooks/:id/test", post(test_webhook))
+        .with_state(webhooks);
+
+    let template_routes = Router::new()
+        .route("/api/templates", get(list_templates).post(create_template))
+        .route(
+            "/api/templates/:id",
+            put(update_template).delete(delete_template),
+        )
+        .with_state(templates.clone());
+
+    let restore_state = state.clone();
 
     // Build full application with flat routes
     let app = Router::new()
         .route("/api/config", get(get_config))
+        .route("/api/events", get(get_events))
+        .route("/api/discovery", get(get_discovery))
+        .route("/api/tailscale/status", get(get_tailscale_status))
+        .route("/api/tailscale/peers", get(get_tailscale_peers))
+        .route("/api/tailscale/peers/:ip/ping", post(ping_tailscale_peer))
+        .route(
+            "/api/tailscale/login",
+            get(get_tailscale_login).post(start_tailscale_login),
+        )
         .route("/api/tunnels", get(list_tunnels).post(create_tunnel))
         .route("/api/tunnels/:id", put(update_tunnel).delete(delete_tunnel))
+        .route("/api/tunnels/:id/logs", get(get_tunnel_logs))
         .route("/api/test", post(test_endpoint))
-        .with_state(state)
-        .fallback_service(serve_dir);
+        .route("/metrics", get(get_metrics))
+        .merge(webhook_routes)
+        .merge(template_routes)
+        .layer(Extension(templates))
+        .route_layer(middleware::from_fn_with_state(limits, rate_limit))
+        .route_layer(middleware::from_fn_with_state(auth, require_token))
+        .route_layer(middleware::from_fn(identify_client))
+        .route_layer(middleware::from_fn(track_requests))
+        .route_layer(TraceLayer::new_for_http().make_span_with(logging::request_span))
+        // Probes skip authentication, rate limits and request logging
+        .route("/healthz", get(get_healthz))
+        .route("/readyz", get(get_readyz))
+        .with_state(state);
+
+    // The dashboard: built in, or from PUBLIC_DIR while developing it
+    let app = match &settings.public_dir {
+        Some(dir) => {
+            info!("Serving the dashboard from {}", dir.display());
+            app.fallback_service(ServeDir::new(dir))
+        }
+        None => app.fallback(assets::serve),
+    };
 
-    let bind_addr = "0.0.0.0:3000";
-    println!("[main] Listening on {bind_addr}");
+    // Strip BASE_PATH before routing
+    let app = middleware::from_fn(config::strip_base_path).layer(app);
+
+    let bind_addr = settings.bind_addr();
+    info!("Listening on {bind_addr}{}/", settings.base_path);
 
     let listener = tokio::net::TcpListener::bind(bind_addr)
         .await
         .expect("Failed to bind to address");
 
-    axum::serve(listener, app).await.expect("Server error");
+    // Restore enabled tunnels while already serving; /readyz reports 503
+    // until it finishes
+    tokio::spawn(async move { restore_tunnels(&restore_state).await });
+
+    let server = axum::serve(
+        listener,
+        ServiceExt::<axum::extract::Request>::into_make_service_with_connect_info::<SocketAddr>(
+            app,
+        ),
+    );
+    // Returning drops the runtime, which also stops a supervised tailscaled
+    tokio::select! {
+        result = server => result.expect("Server error"),
+        () = shutdown_signal() => info!("Shutting down"),
+    }
+}
+
+/// Resolves on Ctrl+C or `SIGTERM`, which the binary receives directly when
+/// it runs as the container's init process.
+async fn shutdown_signal() {
+    let mut terminate = signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM");
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate.recv() => {}
+    }
 }
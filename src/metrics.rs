@@ -0,0 +1,428 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::models::{Health, Tunnel};
+use crate::state::SharedState;
+
+// ─── Histogram ───────────────────────────────────────────────────────────
+
+/// Upper bounds (seconds) of the health-check latency buckets.
+const LATENCY_BUCKETS: [f64; 10] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+/// Lock-free latency histogram with fixed buckets.
+#[derive(Debug, Default)]
+pub struct Histogram {
+    /// Per-bucket (non-cumulative) counts; the last slot is `+Inf`.
+    buckets: [AtomicU64; LATENCY_BUCKETS.len() + 1],
+    sum_micros: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Histogram {
+    pub fn observe(&self, value: Duration) {
+        let secs = value.as_secs_f64();
+        let slot = LATENCY_BUCKETS
+            .iter()
+            .position(|le| secs <= *le)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.buckets[slot].fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add(value.as_micros() as u64, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Append the `_bucket`, `_sum` and `_count` series of `name`, each
+    /// carrying the already-escaped `labels`.
+    fn write(&self, out: &mut String, name: &str, labels: &str) {
+        let mut cumulative = 0;
+        for (i, le) in LATENCY_BUCKETS.iter().enumerate() {
+            cumulative += self.buckets[i].load(Ordering::Relaxed);
+            let _ = writeln!(out, "{name}_bucket{{{labels},le=\"{le}\"}} {cumulative}");
+        }
+        cumulative += self.buckets[LATENCY_BUCKETS.len()].load(Ordering::Relaxed);
+        let _ = writeln!(out, "{name}_bucket{{{labels},le=\"+Inf\"}} {cumulative}");
+        let sum = self.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        let _ = writeln!(out, "{name}_sum{{{labels}}} {sum}");
+        let _ = writeln!(
+            out,
+            "{name}_count{{{labels}}} {}",
+            self.count.load(Ordering::Relaxed)
+        );
+    }
+}
+
+// ─── Per-tunnel counters ─────────────────────────────────────────────────
+
+/// Counters kept for the lifetime of a tunnel.  They live on the `Tunnel`
+/// itself, so they survive forwarder restarts and disappear on delete.
+#[derive(Debug, Default)]
+pub struct TunnelStats {
+    /// Connections refused because the peer is not in `allowed_sources`.
+    pub rejected: AtomicU64,
+    /// Connections currently being forwarded.
+    pub active: AtomicU64,
+    /// Connections accepted and forwarded (or attempted).
+    pub connections: AtomicU64,
+    /// Bytes received from clients.
+    pub bytes_in: AtomicU64,
+    /// Bytes sent back to clients.
+    pub bytes_out: AtomicU64,
+    /// Failed or timed-out connects to the target.
+    pub connect_failures: AtomicU64,
+    /// Times an update replaced the running forwarder.
+    pub restarts: AtomicU64,
+    /// Latency of successful periodic health checks.
+    pub health_latency: Histogram,
+    /// Periodic health checks that could not reach the target.
+    pub health_failures: AtomicU64,
+    /// Last health state: 0 unknown, then [`Health`] in declaration order.
+    health: AtomicU8,
+    /// Consecutive failed health checks.
+    failed_checks: AtomicU64,
+}
+
+impl TunnelStats {
+    /// State of the health checks, `None` before the first one.
+    pub fn health(&self) -> Option<Health> {
+        match self.health.load(Ordering::Relaxed) {
+            1 => Some(Health::Healthy),
+            2 => Some(Health::Degraded),
+            3 => Some(Health::Down),
+            _ => None,
+        }
+    }
+
+    /// Record a health check outcome.  The first failure degrades the
+    /// tunnel and `down_after` consecutive failures mark it down.  Returns
+    /// the new state when it changed.
+    pub fn record_health(&self, ok: bool, down_after: u64) -> Option<Health> {
+        let health = if ok {
+            self.failed_checks.store(0, Ordering::Relaxed);
+            Health::Healthy
+        } else if self.failed_checks.fetch_add(1, Ordering::Relaxed) + 1 >= down_after {
+            Health::Down
+        } else {
+            Health::Degraded
+        };
+        let value = health as u8 + 1;
+        (self.health.swap(value, Ordering::Relaxed) != value).then_some(health)
+    }
+}
+
+// ─── API request counters ────────────────────────────────────────────────
+
+static STARTED_AT: LazyLock<SystemTime> = LazyLock::new(SystemTime::now);
+
+/// Requests served, keyed by (method, route, status).
+static API_REQUESTS: Mutex<BTreeMap<(String, String, u16), u64>> = Mutex::new(BTreeMap::new());
+
+/// Record the process start time.  Call once at startup.
+pub fn init() {
+    LazyLock::force(&STARTED_AT);
+}
+
+/// Time since [`init`].
+pub fn uptime() -> Duration {
+    STARTED_AT.elapsed().unwrap_or_default()
+}
+
+/// Middleware counting API requests by method, matched route and status.
+pub async fn track_requests(request: Request, next: Next) -> Response {
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+
+    let response = next.run(request).await;
+
+    let key = (method, route, response.status().as_u16());
+    *API_REQUESTS.lock().unwrap().entry(key).or_insert(0) += 1;
+    response
+}
+
+// ─── Exposition ──────────────────────────────────────────────────────────
+
+/// Escape a Prometheus label value.
+fn label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn describe(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+/// Resident set size from `/proc/self/statm`, in bytes.
+fn resident_memory_bytes() -> Option<u64> {
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    Some(pages * 4096)
+}
+
+/// A per-tunnel metric: name, help text and how to read it.
+type PerTunnel = (&'static str, &'static str, fn(&Tunnel) -> u64);
+
+// ─── GET /metrics ────────────────────────────────────────────────────────
+
+/// Render every metric in the Prometheus text exposition format.
+pub async fn get_metrics(State(state): State<SharedState>) -> impl IntoResponse {
+    let tunnels = state.read().await;
+    let mut out = String::new();
+
+    // ── Process ─────────────────────────────────────────────────────────
+    describe(
+        &mut out,
+        "tunnel_manager_build_info",
+        "gauge",
+        "Build information.",
+    );
+    let _ = writeln!(
+        out,
+        "tunnel_manager_build_info{{version=\"{}\"}} 1",
+        env!("CARGO_PKG_VERSION")
+    );
+
+    let started = STARTED_AT
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64();
+    describe(
+        &mut out,
+        "process_start_time_seconds",
+        "gauge",
+        "Start time of the process since unix epoch in seconds.",
+    );
+    let _ = writeln!(out, "process_start_time_seconds {started}");
+
+    if let Some(rss) = resident_memory_bytes() {
+        describe(
+            &mut out,
+            "process_resident_memory_bytes",
+            "gauge",
+            "Resident memory size in bytes.",
+        );
+        let _ = writeln!(out, "process_resident_memory_bytes {rss}");
+    }
+
+    describe(
+        &mut out,
+        "tunnel_manager_api_requests_total",
+        "counter",
+        "API requests served, by method, route and status.",
+    );
+    for ((method, route, status), count) in API_REQUESTS.lock().unwrap().iter() {
+        let _ = writeln!(
+            out,
+            "tunnel_manager_api_requests_total{{method=\"{method}\",route=\"{}\",status=\"{status}\"}} {count}",
+            label(route)
+        );
+    }
+
+    // ── Tunnels ─────────────────────────────────────────────────────────
+    describe(
+        &mut out,
+        "tunnel_manager_tunnels",
+        "gauge",
+        "Number of configured tunnels.",
+    );
+    let _ = writeln!(out, "tunnel_manager_tunnels {}", tunnels.len());
+
+    let labels: Vec<String> = tunnels
+        .iter()
+        .map(|t| format!("id=\"{}\",name=\"{}\"", label(&t.id), label(&t.name)))
+        .collect();
+
+    let gauges: [PerTunnel; 3] = [
+        (
+            "tunnel_manager_tunnel_enabled",
+            "Whether the tunnel is enabled.",
+            |t| t.enabled as u64,
+        ),
+        (
+            "tunnel_manager_tunnel_running",
+            "Whether the tunnel's forwarder is listening.",
+            |t| t.forwarder.is_some() as u64,
+        ),
+        (
+            "tunnel_manager_tunnel_active_connections",
+            "Connections currently being forwarded.",
+            |t| t.stats.active.load(Ordering::Relaxed),
+        ),
+    ];
+    for (name, help, value) in gauges {
+        describe(&mut out, name, "gauge", help);
+        for (t, l) in tunnels.iter().zip(&labels) {
+            let _ = writeln!(out, "{name}{{{l}}} {}", value(t));
+        }
+    }
+
+    let counters: [PerTunnel; 5] = [
+        (
+            "tunnel_manager_tunnel_connections_total",
+            "Connections accepted and forwarded.",
+            |t| t.stats.connections.load(Ordering::Relaxed),
+        ),
+        (
+            "tunnel_manager_tunnel_rejected_connections_total",
+            "Connections refused by the source allowlist.",
+            |t| t.stats.rejected.load(Ordering::Relaxed),
+        ),
+        (
+            "tunnel_manager_tunnel_connect_failures_total",
+            "Failed connects from the forwarder to the target.",
+            |t| t.stats.connect_failures.load(Ordering::Relaxed),
+        ),
+        (
+            "tunnel_manager_tunnel_restarts_total",
+            "Running forwarders replaced by updates.",
+            |t| t.stats.restarts.load(Ordering::Relaxed),
+        ),
+        (
+            "tunnel_manager_tunnel_health_check_failures_total",
+            "Periodic health checks that could not reach the target.",
+            |t| t.stats.health_failures.load(Ordering::Relaxed),
+        ),
+    ];
+    for (name, help, value) in counters {
+        describe(&mut out, name, "counter", help);
+        for (t, l) in tunnels.iter().zip(&labels) {
+            let _ = writeln!(out, "{name}{{{l}}} {}", value(t));
+        }
+    }
+
+    let name = "tunnel_manager_tunnel_bytes_total";
+    describe(
+        &mut out,
+        name,
+        "counter",
+        "Bytes forwarded; \"in\" is client to target, \"out\" is target to client.",
+    );
+    for (t, l) in tunnels.iter().zip(&labels) {
+        let _ = writeln!(
+            out,
+            "{name}{{{l},direction=\"in\"}} {}",
+            t.stats.bytes_in.load(Ordering::Relaxed)
+        );
+        let _ = writeln!(
+            out,
+            "{name}{{{l},direction=\"out\"}} {}",
+            t.stats.bytes_out.load(Ordering::Relaxed)
+        );
+    }
+
+    let name = "tunnel_manager_tunnel_health_check_duration_seconds";
+    describe(
+        &mut out,
+        name,
+        "histogram",
+        "Latency of successful TCP health checks against the target.",
+    );
+    for (t, l) in tunnels.iter().zip(&labels) {
+        t.stats.health_latency.write(&mut out, name, l);
+    }
+
+    (
+        [(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        out,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn series<'a>(out: &'a str, prefix: &str) -> Vec<&'a str> {
+        out.lines().filter(|l| l.starts_with(prefix)).collect()
+    }
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let h = Histogram::default();
+        h.observe(Duration::from_millis(3));
+        h.observe(Duration::from_millis(5));
+        h.observe(Duration::from_millis(40));
+        h.observe(Duration::from_millis(700));
+        h.observe(Duration::from_secs(9));
+
+        let mut out = String::new();
+        h.write(&mut out, "lat", "id=\"a\"");
+        assert_eq!(
+            series(&out, "lat_bucket"),
+            [
+                "lat_bucket{id=\"a\",le=\"0.005\"} 2",
+                "lat_bucket{id=\"a\",le=\"0.01\"} 2",
+                "lat_bucket{id=\"a\",le=\"0.025\"} 2",
+                "lat_bucket{id=\"a\",le=\"0.05\"} 3",
+                "lat_bucket{id=\"a\",le=\"0.1\"} 3",
+                "lat_bucket{id=\"a\",le=\"0.25\"} 3",
+                "lat_bucket{id=\"a\",le=\"0.5\"} 3",
+                "lat_bucket{id=\"a\",le=\"1\"} 4",
+                "lat_bucket{id=\"a\",le=\"2.5\"} 4",
+                "lat_bucket{id=\"a\",le=\"5\"} 4",
+                "lat_bucket{id=\"a\",le=\"+Inf\"} 5",
+            ]
+        );
+    }
+
+    #[test]
+    fn histogram_sum_and_count() {
+        let h = Histogram::default();
+        h.observe(Duration::from_millis(250));
+        h.observe(Duration::from_micros(1_500));
+
+        let mut out = String::new();
+        h.write(&mut out, "lat", "id=\"a\"");
+        assert_eq!(series(&out, "lat_sum"), ["lat_sum{id=\"a\"} 0.2515"]);
+        assert_eq!(series(&out, "lat_count"), ["lat_count{id=\"a\"} 2"]);
+    }
+
+    #[test]
+    fn empty_histogram_reports_zeroes() {
+        let mut out = String::new();
+        Histogram::default().write(&mut out, "lat", "id=\"a\"");
+        assert!(series(&out, "lat_bucket").iter().all(|l| l.ends_with(" 0")));
+        assert_eq!(series(&out, "lat_sum"), ["lat_sum{id=\"a\"} 0"]);
+        assert_eq!(series(&out, "lat_count"), ["lat_count{id=\"a\"} 0"]);
+    }
+
+    #[test]
+    fn label_values_are_escaped() {
+        assert_eq!(label("plain"), "plain");
+        assert_eq!(label(r#"say "hi""#), r#"say \"hi\""#);
+        assert_eq!(label(r"C:\db"), r"C:\\db");
+        assert_eq!(label("two\nlines"), r"two\nlines");
+        assert_eq!(label("\\\""), r#"\\\""#);
+    }
+
+    #[test]
+    fn escaped_labels_carry_into_histogram_series() {
+        let h = Histogram::default();
+        h.observe(Duration::from_millis(1));
+        let labels = format!("name=\"{}\"", label("a\"b\\c\nd"));
+        let mut out = String::new();
+        h.write(&mut out, "lat", &labels);
+        assert_eq!(
+            series(&out, "lat_count"),
+            [r#"lat_count{name="a\"b\\c\nd"} 1"#]
+        );
+        assert_eq!(out.lines().count(), LATENCY_BUCKETS.len() + 3);
+    }
+}
@@ -1,7 +1,14 @@
 use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
 
+use crate::forwarder::ForwarderHandle;
+use crate::metrics::TunnelStats;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Tunnel {
     pub id: String,
@@ -10,15 +17,327 @@ pub struct Tunnel {
     pub target_host: String,
     pub target_port: u16,
     pub enabled: bool,
-    #[serde(skip_serializing)]
-    #[serde(default)]
-    pub pid: Option<u32>,
+    /// Tailnet IPs, CIDRs or `tag:` names allowed to connect.  Empty means
+    /// any client that can reach the port.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_sources: Vec<String>,
+    /// Where the forwarder listens.  `None` follows the global
+    /// `LISTEN_ADDRESS` setting.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub listen_address: Option<ListenAddress>,
+    /// What the target speaks, used to build connection strings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_type: Option<ServiceType>,
+    /// Template the tunnel was created from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template_id: Option<String>,
+    /// Client URI with `{host}` and `{port}` placeholders, from a template
+    /// whose format differs from the service type's.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connection_format: Option<String>,
+    #[serde(default, skip_serializing_if = "HealthCheck::is_default")]
+    pub health_check: HealthCheck,
+    /// Also publish the target through Tailscale Serve.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub serve: Option<ServeSettings>,
+    /// MagicDNS name the Serve entry is published under, while active.
+    #[serde(skip)]
+    pub serve_host: Option<String>,
+    /// Also expose the Serve entry on the public internet through Funnel.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub funnel: bool,
+    /// Unix time at which Funnel is turned off again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub funnel_expires_at: Option<u64>,
+    #[serde(skip)]
+    pub forwarder: Option<ForwarderHandle>,
+    /// Runtime counters, shared with the forwarder and kept across restarts.
+    #[serde(skip)]
+    pub stats: Arc<TunnelStats>,
     /// Named `warning_id` to avoid a serde flatten collision with
     /// `TunnelResponse.warning` (which carries the full `ApiMessage`).
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub warning_id: Option<String>,
 }
 
+/// Which local address a tunnel's forwarder binds to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum ListenAddress {
+    /// Every interface (`0.0.0.0`), including the Discloud VLAN.
+    All,
+    /// Only reachable through Tailscale.  Resolves to the node's Tailscale
+    /// IP when it is assigned to a local interface, or to loopback under
+    /// userspace networking, where tailscaled delivers via `127.0.0.1`.
+    Tailscale,
+    /// Loopback only (`127.0.0.1`).
+    Loopback,
+    /// An explicit local address.
+    Ip(IpAddr),
+}
+
+impl FromStr for ListenAddress {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "all" => Ok(ListenAddress::All),
+            "tailscale" => Ok(ListenAddress::Tailscale),
+            "loopback" => Ok(ListenAddress::Loopback),
+            other => other
+                .parse()
+                .map(ListenAddress::Ip)
+                .map_err(|_| s.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for ListenAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ListenAddress::All => f.write_str("all"),
+            ListenAddress::Tailscale => f.write_str("tailscale"),
+            ListenAddress::Loopback => f.write_str("loopback"),
+            ListenAddress::Ip(ip) => write!(f, "{ip}"),
+        }
+    }
+}
+
+impl TryFrom<String> for ListenAddress {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<ListenAddress> for String {
+    fn from(addr: ListenAddress) -> Self {
+        addr.to_string()
+    }
+}
+
+/// How Tailscale Serve publishes a tunnel's target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServeMode {
+    /// Raw TCP on a tailnet port.
+    Tcp,
+    /// TLS with the node's certificate, forwarded as plain TCP.
+    TlsTerminatedTcp,
+    /// HTTPS reverse proxy on a URL path.
+    Https,
+}
+
+impl fmt::Display for ServeMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ServeMode::Tcp => "tcp",
+            ServeMode::TlsTerminatedTcp => "tls_terminated_tcp",
+            ServeMode::Https => "https",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServeSettings {
+    pub mode: ServeMode,
+    /// Tailnet port Serve listens on.  Defaults to 443 for `https` and to
+    /// the tunnel's local port otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// URL path for `https`, defaulting to `/`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+impl ServeSettings {
+    pub fn port(&self, local_port: u16) -> u16 {
+        match (self.port, self.mode) {
+            (Some(port), _) => port,
+            (None, ServeMode::Https) => 443,
+            (None, _) => local_port,
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        self.path.as_deref().unwrap_or("/")
+    }
+
+    /// Address clients use, e.g. `https://node.tailnet.ts.net/app`.
+    pub fn url(&self, host: &str, local_port: u16) -> String {
+        match (self.mode, self.port(local_port)) {
+            (ServeMode::Https, 443) => format!("https://{host}{}", self.path()),
+            (ServeMode::Https, port) => format!("https://{host}:{port}{}", self.path()),
+            (_, port) => format!("{host}:{port}"),
+        }
+    }
+
+    /// Whether this entry and `other` would claim the same Serve slot.
+    pub fn conflicts_with(&self, local_port: u16, other: &ServeSettings, other_port: u16) -> bool {
+        if self.port(local_port) != other.port(other_port) {
+            return false;
+        }
+        // HTTPS entries share a port as long as their paths differ.
+        self.mode != ServeMode::Https
+            || other.mode != ServeMode::Https
+            || self.path() == other.path()
+    }
+}
+
+/// Protocol spoken by a tunnel's target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ServiceType {
+    Postgres,
+    Mysql,
+    Redis,
+    Mongodb,
+}
+
+impl FromStr for ServiceType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "postgres" => Ok(ServiceType::Postgres),
+            "mysql" => Ok(ServiceType::Mysql),
+            "redis" => Ok(ServiceType::Redis),
+            "mongodb" => Ok(ServiceType::Mongodb),
+            _ => Err(s.to_string()),
+        }
+    }
+}
+
+impl ServiceType {
+    /// Client URI with `{host}` and `{port}` placeholders; `<user>`,
+    /// `<password>` and `<database>` are left for the user to fill in.
+    pub fn uri_format(self) -> &'static str {
+        match self {
+            ServiceType::Postgres => "postgresql://<user>:<password>@{host}:{port}/<database>",
+            ServiceType::Mysql => "mysql://<user>:<password>@{host}:{port}/<database>",
+            ServiceType::Redis => "redis://:<password>@{host}:{port}/0",
+            ServiceType::Mongodb => {
+                "mongodb://<user>:<password>@{host}:{port}/<database>?authSource=admin"
+            }
+        }
+    }
+
+    /// Shell command of the service's CLI client, like [`Self::uri_format`].
+    fn cli_format(self) -> &'static str {
+        match self {
+            ServiceType::Postgres => "psql -h {host} -p {port} -U <user> -d <database>",
+            ServiceType::Mysql => "mysql -h {host} -P {port} -u <user> -p <database>",
+            ServiceType::Redis => "redis-cli -h {host} -p {port} -a <password>",
+            ServiceType::Mongodb => "mongosh --host {host} --port {port} -u <user> -p",
+        }
+    }
+
+    /// Ready-to-paste client URI and CLI command for `host:port`.
+    pub fn connection_strings(self, host: &str, port: u16) -> Vec<ConnectionString> {
+        vec![
+            ConnectionString {
+                kind: ConnectionStringKind::Uri,
+                value: fill_format(self.uri_format(), host, port),
+            },
+            ConnectionString {
+                kind: ConnectionStringKind::Cli,
+                value: fill_format(self.cli_format(), host, port),
+            },
+        ]
+    }
+}
+
+/// Substitute `{host}` and `{port}` in a connection string format.
+pub fn fill_format(format: &str, host: &str, port: u16) -> String {
+    format
+        .replace("{host}", host)
+        .replace("{port}", &port.to_string())
+}
+
+/// How the periodic health checks probe a tunnel's target.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthCheck {
+    /// Open a TCP connection.
+    #[default]
+    Tcp,
+    /// Not checked, e.g. for targets that log every connection.
+    Off,
+}
+
+impl HealthCheck {
+    fn is_default(&self) -> bool {
+        *self == HealthCheck::default()
+    }
+}
+
+/// A tunnel template: built into the binary, or created through the API
+/// and persisted to `TEMPLATES_PATH`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Template {
+    pub id: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_type: Option<ServiceType>,
+    pub target_host: String,
+    pub target_port: u16,
+    /// Local port suggested for new tunnels; the target port when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_port: Option<u16>,
+    #[serde(default)]
+    pub health_check: HealthCheck,
+    /// Client URI with `{host}` and `{port}` placeholders.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connection_format: Option<String>,
+    /// i18n key of the description, for built-in templates.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description_key: Option<String>,
+    /// Free-text description, for user templates.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Built-in templates cannot be changed or deleted.
+    #[serde(default, skip_deserializing)]
+    pub builtin: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionStringKind {
+    /// Client connection URI, e.g. `postgresql://…`.
+    Uri,
+    /// Shell command for the service's CLI client.
+    Cli,
+}
+
+/// A connection string for a tunnel with a [`ServiceType`].
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionString {
+    pub kind: ConnectionStringKind,
+    pub value: String,
+}
+
+/// What a [`ConnectionUrl`] addresses the node by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionUrlKind {
+    /// Tailscale Serve address, e.g. `https://node.tailnet.ts.net/`.
+    Serve,
+    /// Short MagicDNS name, e.g. `tailscale-discloud-1`.
+    Hostname,
+    /// Full MagicDNS name, e.g. `tailscale-discloud-1.tail1234.ts.net`.
+    MagicDns,
+    Ipv4,
+    Ipv6,
+}
+
+/// One way to reach a tunnel from the tailnet.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionUrl {
+    pub kind: ConnectionUrlKind,
+    pub url: String,
+}
+
 /// The response returned for a single tunnel (create / update / toggle).
 /// Wraps the core Tunnel with computed fields and optional warnings.
 #[derive(Debug, Clone, Serialize)]
@@ -29,6 +348,12 @@ pub struct TunnelResponse {
     /// Only present when the tunnel is enabled.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub connection_url: Option<String>,
+    /// Every address the tunnel is reachable at, when enabled.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub connection_urls: Vec<ConnectionUrl>,
+    /// Client URI and CLI command, when enabled with a `service_type`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub connection_strings: Vec<ConnectionString>,
     /// Optional warning message (the tunnel was created/updated but
     /// something non-fatal was detected, such as the target port not
     /// responding).
@@ -44,6 +369,33 @@ pub struct TunnelListItem {
     pub tunnel: Tunnel,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub connection_url: Option<String>,
+    /// Every address the tunnel is reachable at, when enabled.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub connection_urls: Vec<ConnectionUrl>,
+    /// Client URI and CLI command, when enabled with a `service_type`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub connection_strings: Vec<ConnectionString>,
+    /// Address the forwarder is actually bound to, when running.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listen_ip: Option<IpAddr>,
+    /// Connections refused by `allowed_sources` since the forwarder started.
+    pub rejected_connections: u64,
+    /// Connections currently being forwarded.
+    pub active_connections: u64,
+    /// State of the periodic health checks, once one has run.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub health: Option<Health>,
+}
+
+/// Outcome of the periodic health checks of a running tunnel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Health {
+    Healthy,
+    /// The last check failed.
+    Degraded,
+    /// Several consecutive checks failed.
+    Down,
 }
 
 /// A structured message with an i18n key and interpolation parameters.
@@ -80,6 +432,124 @@ pub struct ApiErrorResponse {
     pub error: ApiMessage,
 }
 
+/// An outgoing webhook endpoint, persisted to `WEBHOOKS_PATH`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    pub id: String,
+    pub url: String,
+    #[serde(default)]
+    pub kind: WebhookKind,
+    /// Key for the `X-Webhook-Signature-256` HMAC.
+    pub secret: String,
+    pub events: Vec<WebhookEvent>,
+    pub enabled: bool,
+    /// Language of Discord messages; `NOTIFY_LOCALE` when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+    /// Tunnels whose events are not sent to this webhook.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub muted_tunnels: Vec<String>,
+}
+
+/// Payload format of a webhook.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookKind {
+    /// Signed JSON event, one request per event.
+    #[default]
+    Generic,
+    /// Localized Discord embeds, batched per webhook.
+    Discord,
+}
+
+/// Tunnel state changes a webhook can subscribe to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WebhookEvent {
+    #[serde(rename = "tunnel.created")]
+    TunnelCreated,
+    #[serde(rename = "tunnel.deleted")]
+    TunnelDeleted,
+    /// The forwarder started, or the target recovered after failed checks.
+    #[serde(rename = "tunnel.up")]
+    TunnelUp,
+    /// The forwarder could not start, or the target stayed unreachable.
+    #[serde(rename = "tunnel.down")]
+    TunnelDown,
+    /// A health check failed.
+    #[serde(rename = "health.degraded")]
+    HealthDegraded,
+    /// Sent by `POST /api/webhooks/:id/test` only.
+    #[serde(rename = "ping")]
+    Ping,
+}
+
+impl WebhookEvent {
+    /// Events a webhook may subscribe to, in documentation order.
+    pub const SUBSCRIBABLE: [WebhookEvent; 5] = [
+        Self::TunnelCreated,
+        Self::TunnelDeleted,
+        Self::TunnelUp,
+        Self::TunnelDown,
+        Self::HealthDegraded,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::TunnelCreated => "tunnel.created",
+            Self::TunnelDeleted => "tunnel.deleted",
+            Self::TunnelUp => "tunnel.up",
+            Self::TunnelDown => "tunnel.down",
+            Self::HealthDegraded => "health.degraded",
+            Self::Ping => "ping",
+        }
+    }
+}
+
+impl FromStr for WebhookEvent {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::SUBSCRIBABLE
+            .into_iter()
+            .find(|e| e.as_str() == s.trim())
+            .ok_or(())
+    }
+}
+
+/// A webhook as returned by the API.  The secret is only included in
+/// the response that created or replaced it.
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookResponse {
+    pub id: String,
+    pub url: String,
+    pub kind: WebhookKind,
+    pub events: Vec<WebhookEvent>,
+    pub enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+    pub muted_tunnels: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+}
+
+/// One webhook delivery, after its last attempt.
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookDelivery {
+    pub id: String,
+    pub webhook_id: String,
+    /// More than one when Discord notifications were batched.
+    pub events: Vec<WebhookEvent>,
+    /// Unix seconds of the first attempt.
+    pub timestamp: u64,
+    pub attempts: u32,
+    pub success: bool,
+    /// HTTP status of the last attempt, when one was received.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
 /// Result of a target reachability pre-flight check.
 #[derive(Debug)]
 pub enum ReachabilityResult {
@@ -96,18 +566,62 @@ pub enum ReachabilityResult {
 
 #[derive(Debug, Deserialize)]
 pub struct CreateTunnelRequest {
+    /// Fields left out (or `0` / empty) are taken from the template.
+    pub template_id: Option<String>,
+    #[serde(default)]
     pub name: String,
+    /// `0` when omitted or `"auto"`: a free port is picked.
+    #[serde(default, deserialize_with = "port_or_auto")]
     pub local_port: u16,
+    #[serde(default)]
     pub target_host: String,
+    #[serde(default)]
     pub target_port: u16,
     #[serde(default = "default_enabled")]
     pub enabled: bool,
+    #[serde(default)]
+    pub allowed_sources: Vec<String>,
+    /// `"all"`, `"tailscale"`, `"loopback"` or an IP; omitted or empty
+    /// means the global default.
+    pub listen_address: Option<String>,
+    /// `"postgres"`, `"mysql"`, `"redis"` or `"mongodb"`; omitted or empty
+    /// means none.
+    pub service_type: Option<String>,
+    pub serve: Option<ServeSettings>,
+    /// Requires `serve` and the admin token.
+    #[serde(default)]
+    pub funnel: bool,
+    /// Unix time at which Funnel is turned off; required with `funnel`.
+    pub funnel_expires_at: Option<u64>,
+    /// Defaults to the template's, or `tcp`.
+    pub health_check: Option<HealthCheck>,
 }
 
 fn default_enabled() -> bool {
     true
 }
 
+/// A port number, or `"auto"` / `null` as `0`.
+fn port_or_auto<'de, D>(deserializer: D) -> Result<u16, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum PortOrAuto {
+        Port(u16),
+        Text(String),
+    }
+    match Option::<PortOrAuto>::deserialize(deserializer)? {
+        None => Ok(0),
+        Some(PortOrAuto::Port(port)) => Ok(port),
+        Some(PortOrAuto::Text(text)) if text.trim().eq_ignore_ascii_case("auto") => Ok(0),
+        Some(PortOrAuto::Text(text)) => Err(serde::de::Error::custom(format!(
+            "invalid local_port '{text}', expected a port or \"auto\""
+        ))),
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct UpdateTunnelRequest {
     pub name: Option<String>,
@@ -115,6 +629,82 @@ pub struct UpdateTunnelRequest {
     pub target_host: Option<String>,
     pub target_port: Option<u16>,
     pub enabled: Option<bool>,
+    pub allowed_sources: Option<Vec<String>>,
+    /// An empty string resets the tunnel to the global default.
+    pub listen_address: Option<String>,
+    /// An empty string clears the service type.
+    pub service_type: Option<String>,
+    /// `null` stops publishing through Tailscale Serve.
+    #[serde(default, deserialize_with = "double_option")]
+    pub serve: Option<Option<ServeSettings>>,
+    pub funnel: Option<bool>,
+    pub funnel_expires_at: Option<u64>,
+    pub health_check: Option<HealthCheck>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateTemplateRequest {
+    pub name: String,
+    pub target_host: String,
+    pub target_port: u16,
+    pub local_port: Option<u16>,
+    pub service_type: Option<String>,
+    #[serde(default)]
+    pub health_check: HealthCheck,
+    pub connection_format: Option<String>,
+    pub description: Option<String>,
+}
+
+/// Omitted fields are left unchanged; an empty string clears
+/// `service_type`, `connection_format` and `description`, and a
+/// `local_port` of `0` resets it to automatic.
+#[derive(Debug, Deserialize)]
+pub struct UpdateTemplateRequest {
+    pub name: Option<String>,
+    pub target_host: Option<String>,
+    pub target_port: Option<u16>,
+    pub local_port: Option<u16>,
+    pub service_type: Option<String>,
+    pub health_check: Option<HealthCheck>,
+    pub connection_format: Option<String>,
+    pub description: Option<String>,
+}
+
+/// Tell an explicit `null` (`Some(None)`) apart from a missing field.
+fn double_option<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: serde::Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    #[serde(default)]
+    pub kind: WebhookKind,
+    /// Generated when omitted.
+    pub secret: Option<String>,
+    /// Event names; omitted means every subscribable event.
+    pub events: Option<Vec<String>>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    pub locale: Option<String>,
+    #[serde(default)]
+    pub muted_tunnels: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateWebhookRequest {
+    pub url: Option<String>,
+    pub kind: Option<WebhookKind>,
+    pub secret: Option<String>,
+    pub events: Option<Vec<String>>,
+    pub enabled: Option<bool>,
+    /// An empty string resets to `NOTIFY_LOCALE`.
+    pub locale: Option<String>,
+    pub muted_tunnels: Option<Vec<String>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -123,15 +713,230 @@ pub struct TestConnectionRequest {
     pub target_port: u16,
 }
 
+/// Query string of `GET /api/tunnels/:id/logs`.
+#[derive(Debug, Deserialize)]
+pub struct TunnelLogsQuery {
+    /// Keep the response open and stream new lines as server-sent events.
+    #[serde(default)]
+    pub follow: bool,
+    /// Only return the last `lines` buffered lines.
+    pub lines: Option<usize>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct TestConnectionResponse {
     pub success: bool,
     pub log: String,
 }
 
+/// How traffic currently reaches a peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PeerConnection {
+    Direct,
+    /// Through a DERP relay.
+    Relay,
+    /// No recent traffic, so no path has been set up.
+    Idle,
+}
+
+/// A peer in GET /api/tailscale/peers (and, online only, in
+/// GET /api/tailscale/status).
+#[derive(Debug, Serialize)]
+pub struct TailscalePeer {
+    pub hostname: String,
+    pub dns_name: String,
+    pub ips: Vec<IpAddr>,
+    pub os: String,
+    pub online: bool,
+    /// RFC 3339 timestamp, for offline peers.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    pub connection: PeerConnection,
+    /// `ip:port` of the direct path.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    /// Home DERP relay of the peer, e.g. `fra`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relay: Option<String>,
+}
+
+/// Response for POST /api/tailscale/peers/:ip/ping
+#[derive(Debug, Default, Serialize)]
+pub struct PingResponse {
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<f64>,
+    /// Path the pong took: `direct` or `relay`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connection: Option<PeerConnection>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relay: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Where a discovered service was found, most specific first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscoverySource {
+    /// Listed by the Discloud API.
+    Discloud,
+    /// A usual or configured hostname that resolved.
+    Dns,
+    /// Found by scanning the VLAN subnet.
+    Scan,
+}
+
+/// A service found on the VLAN, ready to become a tunnel.
+#[derive(Debug, Clone, Serialize)]
+pub struct DiscoveredService {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service_type: Option<ServiceType>,
+    pub target_host: String,
+    pub target_port: u16,
+    /// Address that accepted the connection.
+    pub address: IpAddr,
+    pub source: DiscoverySource,
+    /// Id of a tunnel that already forwards to this service.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tunnel_id: Option<String>,
+}
+
+/// Query string of `GET /api/discovery`.
+#[derive(Debug, Deserialize)]
+pub struct DiscoveryQuery {
+    /// Scan again instead of returning the cached result.
+    #[serde(default)]
+    pub refresh: bool,
+}
+
+/// Response for GET /api/discovery
+#[derive(Debug, Clone, Serialize)]
+pub struct DiscoveryResponse {
+    pub services: Vec<DiscoveredService>,
+    /// Subnet that was scanned, e.g. `10.0.0.0/24`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subnet: Option<String>,
+    /// Unix timestamp of the scan.
+    pub scanned_at: u64,
+    pub duration_ms: u64,
+    /// Sources that failed; the others still ran.
+    pub errors: Vec<String>,
+}
+
+/// Response for GET /api/tailscale/status
+#[derive(Debug, Serialize)]
+pub struct TailscaleStatusResponse {
+    /// `Running`, `NeedsLogin`, `Stopped`, …
+    pub backend_state: String,
+    pub ips: Vec<IpAddr>,
+    /// MagicDNS name without the trailing dot.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dns_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tailnet: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub magic_dns_suffix: Option<String>,
+    /// RFC 3339 timestamp; absent when key expiry is disabled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_expiry: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth_url: Option<String>,
+    pub health: Vec<String>,
+    pub peers: Vec<TailscalePeer>,
+    pub total_peers: usize,
+}
+
+/// Response for GET and POST /api/tailscale/login
+#[derive(Debug, Serialize)]
+pub struct TailscaleLoginResponse {
+    pub backend_state: String,
+    /// URL the user opens to log the node in, once tailscaled issued one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth_url: Option<String>,
+    /// `auth_url` as an SVG QR code, for logging in from a phone.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth_qr_svg: Option<String>,
+}
+
+/// Response for GET /healthz
+#[derive(Debug, Serialize)]
+pub struct HealthzResponse {
+    pub status: &'static str,
+    pub version: String,
+    pub uptime_seconds: u64,
+}
+
+/// One check of GET /readyz.
+#[derive(Debug, Serialize)]
+pub struct ReadinessCheck {
+    pub name: String,
+    pub ok: bool,
+    pub detail: String,
+}
+
+/// Response for GET /readyz
+#[derive(Debug, Serialize)]
+pub struct ReadyzResponse {
+    pub ready: bool,
+    pub checks: Vec<ReadinessCheck>,
+}
+
 /// Response for GET /api/config
 #[derive(Debug, Serialize)]
 pub struct ConfigResponse {
     pub hostname: String,
     pub version: String,
+    /// Global default for tunnels without their own `listen_address`.
+    pub listen_address: ListenAddress,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local_port(json: &str) -> Result<u16, serde_json::Error> {
+        serde_json::from_str::<CreateTunnelRequest>(json).map(|r| r.local_port)
+    }
+
+    #[test]
+    fn local_port_accepts_numbers() {
+        assert_eq!(
+            local_port(r#"{"name": "db", "local_port": 5432}"#).unwrap(),
+            5432
+        );
+    }
+
+    #[test]
+    fn local_port_auto_missing_and_null_mean_pick_one() {
+        assert_eq!(local_port(r#"{"name": "db"}"#).unwrap(), 0);
+        assert_eq!(
+            local_port(r#"{"name": "db", "local_port": null}"#).unwrap(),
+            0
+        );
+        assert_eq!(
+            local_port(r#"{"name": "db", "local_port": "auto"}"#).unwrap(),
+            0
+        );
+        assert_eq!(
+            local_port(r#"{"name": "db", "local_port": " AUTO "}"#).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn local_port_rejects_invalid_values() {
+        for value in ["70000", "-1", "\"5432\"", "\"random\"", "true"] {
+            let json = format!(r#"{{"name": "db", "local_port": {value}}}"#);
+            assert!(local_port(&json).is_err(), "{value} should be rejected");
+        }
+    }
 }
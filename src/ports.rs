@@ -0,0 +1,268 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::sync::OnceLock;
+
+use axum::{http::StatusCode, response::Json};
+use tracing::{info, warn};
+
+use crate::models::ApiErrorResponse;
+use crate::routes::{api_err_params, params1};
+
+/// Ports below this need root (or `CAP_NET_BIND_SERVICE`) to bind.
+const FIRST_UNPRIVILEGED: u16 = 1024;
+
+/// Which local ports tunnels may listen on, from the environment:
+/// - `ALLOWED_PORTS` — comma-separated ports and `start-end` ranges
+///   (default `1024-65535`)
+/// - `RESERVED_PORTS` — ports never handed to a tunnel; the API's own
+///   port is always reserved
+#[derive(Debug)]
+pub struct PortPolicy {
+    allowed: Vec<RangeInclusive<u16>>,
+    reserved: BTreeSet<u16>,
+    api_port: u16,
+}
+
+/// Why a local port was refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortDenied {
+    /// The API itself listens there.
+    Api,
+    Reserved,
+    /// Below 1024 and not explicitly allowed.
+    Privileged,
+    NotAllowed,
+}
+
+impl fmt::Display for PortDenied {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Api => "used by the API",
+            Self::Reserved => "reserved",
+            Self::Privileged => "privileged",
+            Self::NotAllowed => "outside the allowed ranges",
+        })
+    }
+}
+
+static POLICY: OnceLock<PortPolicy> = OnceLock::new();
+
+/// Parse `5432,10000-19999` into ranges.  The error names the first
+/// invalid item.
+fn parse_ranges(value: &str) -> Result<Vec<RangeInclusive<u16>>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| {
+            let (start, end) = item.split_once('-').unwrap_or((item, item));
+            match (start.trim().parse::<u16>(), end.trim().parse::<u16>()) {
+                (Ok(start), Ok(end)) if start > 0 && start <= end => Ok(start..=end),
+                _ => Err(item.to_string()),
+            }
+        })
+        .collect()
+}
+
+fn format_ranges(ranges: &[RangeInclusive<u16>]) -> String {
+    let items: Vec<String> = ranges
+        .iter()
+        .map(|r| {
+            if r.start() == r.end() {
+                r.start().to_string()
+            } else {
+                format!("{}-{}", r.start(), r.end())
+            }
+        })
+        .collect();
+    items.join(", ")
+}
+
+impl PortPolicy {
+    pub fn new(
+        allowed: Vec<RangeInclusive<u16>>,
+        reserved: impl IntoIterator<Item = u16>,
+        api_port: u16,
+    ) -> Self {
+        Self {
+            allowed,
+            reserved: reserved.into_iter().collect(),
+            api_port,
+        }
+    }
+
+    fn from_env(api_port: u16) -> Self {
+        let default = vec![FIRST_UNPRIVILEGED..=u16::MAX];
+        let allowed = match std::env::var("ALLOWED_PORTS") {
+            Ok(value) => match parse_ranges(&value) {
+                Ok(ranges) if !ranges.is_empty() => ranges,
+                Ok(_) => default,
+                Err(item) => {
+                    warn!("Invalid ALLOWED_PORTS entry '{item}' — using 1024-65535");
+                    default
+                }
+            },
+            Err(_) => default,
+        };
+        let reserved = match std::env::var("RESERVED_PORTS") {
+            Ok(value) => parse_ranges(&value).unwrap_or_else(|item| {
+                warn!("Invalid RESERVED_PORTS entry '{item}' — ignoring it");
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+        Self::new(allowed, reserved.into_iter().flatten(), api_port)
+    }
+
+    pub fn check(&self, port: u16) -> Result<(), PortDenied> {
+        if port == self.api_port {
+            Err(PortDenied::Api)
+        } else if self.reserved.contains(&port) {
+            Err(PortDenied::Reserved)
+        } else if self.allowed.iter().any(|r| r.contains(&port)) {
+            Ok(())
+        } else if port < FIRST_UNPRIVILEGED {
+            Err(PortDenied::Privileged)
+        } else {
+            Err(PortDenied::NotAllowed)
+        }
+    }
+
+    /// [`check`](Self::check) as an API error.
+    pub fn validate(&self, port: u16) -> Result<(), (StatusCode, Json<ApiErrorResponse>)> {
+        let Err(denied) = self.check(port) else {
+            return Ok(());
+        };
+        warn!("Rejected: local port {port} is {denied}");
+        let mut params = params1("port", port);
+        let id = match denied {
+            PortDenied::Api => "api.error.port_api",
+            PortDenied::Reserved => "api.error.port_reserved",
+            PortDenied::Privileged => "api.error.port_privileged",
+            PortDenied::NotAllowed => {
+                params.insert("ranges".to_string(), format_ranges(&self.allowed).into());
+                "api.error.port_not_allowed"
+            }
+        };
+        Err((StatusCode::BAD_REQUEST, Json(api_err_params(id, params))))
+    }
+}
+
+impl fmt::Display for PortPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "allowed {}", format_ranges(&self.allowed))?;
+        let reserved: Vec<String> = std::iter::once(self.api_port)
+            .chain(self.reserved.iter().copied())
+            .map(|p| p.to_string())
+            .collect();
+        write!(f, ", reserved {}", reserved.join(", "))
+    }
+}
+
+/// Load the policy.  Called once at startup with the port the API binds.
+pub fn init(api_port: u16) {
+    let policy = PortPolicy::from_env(api_port);
+    info!("Local port policy: {policy}");
+    let _ = POLICY.set(policy);
+}
+
+/// The policy loaded by [`init`].
+pub fn policy() -> &'static PortPolicy {
+    POLICY.get().expect("port policy not initialized")
+}
+
+/// Whether a tunnel may listen on `port`.
+pub fn check(port: u16) -> Result<(), PortDenied> {
+    policy().check(port)
+}
+
+/// [`check`] as an API error.
+pub fn validate(port: u16) -> Result<(), (StatusCode, Json<ApiErrorResponse>)> {
+    policy().validate(port)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ports_and_ranges() {
+        assert_eq!(
+            parse_ranges("5432, 10000-19999,,6379-6379"),
+            Ok(vec![5432..=5432, 10000..=19999, 6379..=6379])
+        );
+        assert_eq!(parse_ranges(""), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn keeps_overlapping_ranges() {
+        let ranges = parse_ranges("1000-2000,1500-2500").unwrap();
+        assert_eq!(ranges, vec![1000..=2000, 1500..=2500]);
+        let policy = PortPolicy::new(ranges, [], 3000);
+        assert_eq!(policy.check(1750), Ok(()));
+        assert_eq!(policy.check(2500), Ok(()));
+        assert_eq!(policy.check(2501), Err(PortDenied::NotAllowed));
+    }
+
+    #[test]
+    fn rejects_invalid_items() {
+        assert_eq!(parse_ranges("2000-1000"), Err("2000-1000".to_string()));
+        assert_eq!(parse_ranges("5432,abc"), Err("abc".to_string()));
+        assert_eq!(parse_ranges("0"), Err("0".to_string()));
+        assert_eq!(parse_ranges("1-70000"), Err("1-70000".to_string()));
+        assert_eq!(parse_ranges("10-20-30"), Err("10-20-30".to_string()));
+    }
+
+    fn default_policy(reserved: &[u16]) -> PortPolicy {
+        PortPolicy::new(
+            vec![FIRST_UNPRIVILEGED..=u16::MAX],
+            reserved.iter().copied(),
+            3000,
+        )
+    }
+
+    #[test]
+    fn reserves_the_api_port() {
+        let policy = default_policy(&[]);
+        assert_eq!(policy.check(3000), Err(PortDenied::Api));
+        assert_eq!(policy.check(3001), Ok(()));
+        // Even when it is explicitly allowed
+        let policy = PortPolicy::new(vec![3000..=3000], [], 3000);
+        assert_eq!(policy.check(3000), Err(PortDenied::Api));
+    }
+
+    #[test]
+    fn rejects_privileged_ports_unless_allowed() {
+        assert_eq!(default_policy(&[]).check(80), Err(PortDenied::Privileged));
+        let policy = PortPolicy::new(vec![80..=80, 1024..=u16::MAX], [], 3000);
+        assert_eq!(policy.check(80), Ok(()));
+        assert_eq!(policy.check(443), Err(PortDenied::Privileged));
+    }
+
+    #[test]
+    fn reserved_ports_win_over_allowed_ones() {
+        let policy = default_policy(&[5432]);
+        assert_eq!(policy.check(5432), Err(PortDenied::Reserved));
+        assert_eq!(policy.check(5433), Ok(()));
+    }
+
+    fn rejection(policy: &PortPolicy, port: u16) -> ApiErrorResponse {
+        let (status, Json(body)) = policy.validate(port).unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body.error.params["port"], port);
+        body
+    }
+
+    #[test]
+    fn each_rejection_has_its_own_message() {
+        let policy = PortPolicy::new(vec![80..=80, 5000..=5999], [5432], 5000);
+        assert!(policy.validate(5001).is_ok());
+        assert_eq!(rejection(&policy, 5000).error.id, "api.error.port_api");
+        assert_eq!(rejection(&policy, 5432).error.id, "api.error.port_reserved");
+        assert_eq!(rejection(&policy, 22).error.id, "api.error.port_privileged");
+        let body = rejection(&policy, 6000);
+        assert_eq!(body.error.id, "api.error.port_not_allowed");
+        assert_eq!(body.error.params["ranges"], "80, 5000-5999");
+    }
+}
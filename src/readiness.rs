@@ -0,0 +1,185 @@
+use std::net::IpAddr;
+use std::sync::RwLock;
+
+use axum::{http::StatusCode, response::Json};
+use tokio::net::TcpListener;
+use tokio::time::{timeout, Duration};
+
+use crate::metrics;
+use crate::models::{HealthzResponse, ReadinessCheck, ReadyzResponse};
+use crate::state::listen_ip_for;
+use crate::tailscale;
+use crate::tailscaled;
+
+/// Outcome of the last load or save of the tunnels file.
+static PERSISTENCE: RwLock<Option<Result<String, String>>> = RwLock::new(None);
+
+/// Summary of the boot restore, once it finished.
+static RESTORE: RwLock<Option<String>> = RwLock::new(None);
+
+pub fn set_persistence(result: Result<String, String>) {
+    *PERSISTENCE.write().unwrap() = Some(result);
+}
+
+pub fn set_restored(summary: String) {
+    *RESTORE.write().unwrap() = Some(summary);
+}
+
+fn check(name: &str, result: Result<String, String>) -> ReadinessCheck {
+    let (ok, detail) = match result {
+        Ok(detail) => (true, detail),
+        Err(detail) => (false, detail),
+    };
+    ReadinessCheck {
+        name: name.to_string(),
+        ok,
+        detail,
+    }
+}
+
+fn check_persistence() -> Result<String, String> {
+    PERSISTENCE
+        .read()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(|| Err("Tunnels not loaded yet".to_string()))
+}
+
+fn check_restore() -> Result<String, String> {
+    RESTORE
+        .read()
+        .unwrap()
+        .clone()
+        .ok_or_else(|| "Boot restore still running".to_string())
+}
+
+async fn check_tailscale() -> Result<String, String> {
+    let status = timeout(Duration::from_secs(3), tailscale::status())
+        .await
+        .map_err(|_| "LocalAPI did not answer within 3s".to_string())??;
+    if status.backend_state != "Running" {
+        return Err(format!("Backend state is {}", status.backend_state));
+    }
+    let ips: Vec<String> = status
+        .tailscale_ips
+        .iter()
+        .map(|ip| ip.to_string())
+        .collect();
+    Ok(format!("Running ({})", ips.join(", ")))
+}
+
+/// Forwarders need to bind on the default and Tailscale-only addresses.
+async fn check_forwarder() -> Result<String, String> {
+    let mut ips: Vec<IpAddr> = vec![listen_ip_for(None), tailscale::bind_ip()];
+    ips.dedup();
+    for ip in &ips {
+        TcpListener::bind((*ip, 0))
+            .await
+            .map_err(|e| format!("Cannot bind on {ip}: {e}"))?;
+    }
+    let ips: Vec<String> = ips.iter().map(|ip| ip.to_string()).collect();
+    Ok(format!("Can bind on {}", ips.join(", ")))
+}
+
+// ─── GET /healthz ───────────────────────────────────────────────────────
+
+/// Liveness: answers as long as the process serves requests.
+pub async fn get_healthz() -> Json<HealthzResponse> {
+    Json(HealthzResponse {
+        status: "ok",
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        uptime_seconds: metrics::uptime().as_secs(),
+    })
+}
+
+// ─── GET /readyz ────────────────────────────────────────────────────────
+
+/// Readiness: `200` when every check passes, `503` otherwise, with the
+/// outcome of each check.
+pub async fn get_readyz() -> (StatusCode, Json<ReadyzResponse>) {
+    let (tailscale, forwarder) = tokio::join!(check_tailscale(), check_forwarder());
+    let mut checks = vec![
+        check("persistence", check_persistence()),
+        check("restore", check_restore()),
+        check("tailscale", tailscale),
+        check("forwarder", forwarder),
+    ];
+    if let Some(daemon) = tailscaled::check() {
+        checks.push(check("tailscaled", daemon));
+    }
+    readyz(checks)
+}
+
+fn readyz(checks: Vec<ReadinessCheck>) -> (StatusCode, Json<ReadyzResponse>) {
+    let ready = checks.iter().all(|c| c.ok);
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(ReadyzResponse { ready, checks }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn ready_when_every_check_passes() {
+        let (status, Json(body)) = readyz(vec![
+            check("persistence", Ok("Loaded 2 tunnel(s)".to_string())),
+            check("restore", Ok("2 active, 0 failed, 0 skipped".to_string())),
+        ]);
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            serde_json::to_value(&body).unwrap(),
+            json!({
+                "ready": true,
+                "checks": [
+                    { "name": "persistence", "ok": true, "detail": "Loaded 2 tunnel(s)" },
+                    { "name": "restore", "ok": true, "detail": "2 active, 0 failed, 0 skipped" },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn not_ready_when_any_check_fails() {
+        let (status, Json(body)) = readyz(vec![
+            check("persistence", Ok("Saved 1 tunnel(s)".to_string())),
+            check("tailscale", Err("Backend state is NeedsLogin".to_string())),
+        ]);
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        let body = serde_json::to_value(&body).unwrap();
+        assert_eq!(body["ready"], false);
+        assert_eq!(
+            body["checks"][1],
+            json!({ "name": "tailscale", "ok": false, "detail": "Backend state is NeedsLogin" })
+        );
+    }
+
+    #[test]
+    fn restore_is_pending_until_it_reports() {
+        assert_eq!(
+            check_restore(),
+            Err("Boot restore still running".to_string())
+        );
+        set_restored("1 active, 1 failed, 0 skipped".to_string());
+        assert_eq!(
+            check_restore(),
+            Ok("1 active, 1 failed, 0 skipped".to_string())
+        );
+    }
+
+    #[test]
+    fn persistence_reports_the_last_outcome() {
+        set_persistence(Ok("Saved 1 tunnel(s)".to_string()));
+        assert!(check_persistence().is_ok());
+        set_persistence(Err("Failed to write tunnels.json".to_string()));
+        assert_eq!(
+            check_persistence(),
+            Err("Failed to write tunnels.json".to_string())
+        );
+    }
+}
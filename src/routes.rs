@@ -1,40 +1,69 @@
 use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::Ordering;
 
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
-    response::Json,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Json, Response,
+    },
+    Extension,
 };
+use qrcode::render::svg;
+use qrcode::QrCode;
+use tokio::time::{sleep, timeout, Duration, Instant};
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::StreamExt;
+use tracing::{debug, error, info, warn, Instrument};
 use uuid::Uuid;
 
+use crate::audit;
+use crate::auth::Role;
+use crate::config::settings;
+use crate::discovery;
+use crate::events::{publish, ApiEvent};
+use crate::forwarder::{parse_sources, spawn_forwarder};
+use crate::limits::RequestClient;
+use crate::logging::{record_tunnel, tunnel_span};
 use crate::models::{
-    ApiErrorResponse, ApiMessage, ConfigResponse, CreateTunnelRequest, ReachabilityResult,
-    TestConnectionRequest, TestConnectionResponse, TunnelListItem, TunnelResponse,
-    UpdateTunnelRequest,
+    ApiErrorResponse, ApiMessage, ConfigResponse, CreateTunnelRequest, DiscoveryQuery,
+    DiscoveryResponse, ListenAddress, PeerConnection, PingResponse, ReachabilityResult, ServeMode,
+    ServeSettings, ServiceType, TailscaleLoginResponse, TailscalePeer, TailscaleStatusResponse,
+    Template, TestConnectionRequest, TestConnectionResponse, Tunnel, TunnelListItem,
+    TunnelLogsQuery, TunnelResponse, UpdateTunnelRequest,
 };
+use crate::ports;
+use crate::serve;
 use crate::state::{
-    check_target_reachability, connection_url_for, get_hostname, is_loopback_host,
-    is_port_available, kill_socat, save_tunnels, spawn_socat, test_connection, SharedState,
+    auto_port_range, check_target_reachability, connection_strings_for, connection_url_for,
+    connection_urls_for, default_listen_address, get_hostname, is_loopback_host, is_port_available,
+    list_item, listen_ip_for, pick_local_port, save_tunnels, test_connection, SharedState,
 };
+use crate::tailscale;
+use crate::templates::SharedTemplates;
+use crate::tunnel_log;
+use crate::webhooks::unix_now;
 
 // ─── Helpers ─────────────────────────────────────────────────────────────
 
 /// Build an `ApiErrorResponse` from an i18n key with no parameters.
-fn api_err(id: &str) -> ApiErrorResponse {
+pub fn api_err(id: &str) -> ApiErrorResponse {
     ApiErrorResponse {
         error: ApiMessage::new(id),
     }
 }
 
 /// Build an `ApiErrorResponse` from an i18n key with parameters.
-fn api_err_params(id: &str, params: HashMap<String, serde_json::Value>) -> ApiErrorResponse {
+pub fn api_err_params(id: &str, params: HashMap<String, serde_json::Value>) -> ApiErrorResponse {
     ApiErrorResponse {
         error: ApiMessage::with_params(id, params),
     }
 }
 
 /// Shortcut: create a single-entry params map.
-fn params1(key: &str, val: impl Into<serde_json::Value>) -> HashMap<String, serde_json::Value> {
+pub fn params1(key: &str, val: impl Into<serde_json::Value>) -> HashMap<String, serde_json::Value> {
     let mut m = HashMap::new();
     m.insert(key.to_string(), val.into());
     m
@@ -53,32 +82,467 @@ fn params2(
     m
 }
 
+/// Trim `allowed_sources` entries and drop blank ones, then make sure every
+/// remaining entry is an IP, a CIDR or a `tag:` name.
+/// On failure, returns the offending entry.
+fn normalize_sources(sources: &[String]) -> Result<Vec<String>, String> {
+    let sources: Vec<String> = sources
+        .iter()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+    parse_sources(&sources)?;
+    Ok(sources)
+}
+
+/// Parse an optional `listen_address` field.  Blank means "use the global
+/// default".  On failure, returns the offending value.
+fn parse_listen_address(value: Option<&str>) -> Result<Option<ListenAddress>, String> {
+    match value.map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(v) => v.parse().map(Some),
+    }
+}
+
+/// Parse an optional `service_type` field.  Blank means none.
+pub fn parse_service_type(value: Option<&str>) -> Result<Option<ServiceType>, String> {
+    match value.map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(v) => v.parse().map(Some),
+    }
+}
+
+pub fn invalid_service_type(invalid: String) -> (StatusCode, Json<ApiErrorResponse>) {
+    warn!("Rejected: invalid service type '{invalid}'");
+    (
+        StatusCode::BAD_REQUEST,
+        Json(api_err_params(
+            "api.error.service_type_invalid",
+            params1("value", invalid),
+        )),
+    )
+}
+
+/// Fill in what a create request left out (`0` or empty) from its
+/// template.
+fn apply_template(payload: &mut CreateTunnelRequest, template: &Template) {
+    if payload.name.trim().is_empty() {
+        payload.name = template.name.clone();
+    }
+    if payload.target_host.trim().is_empty() {
+        payload.target_host = template.target_host.clone();
+    }
+    if payload.target_port == 0 {
+        payload.target_port = template.target_port;
+    }
+    if payload.local_port == 0 {
+        payload.local_port = template.local_port.unwrap_or(0);
+    }
+    payload.health_check.get_or_insert(template.health_check);
+}
+
 // ─── Type aliases for route return types ─────────────────────────────────
 
-type ApiResult<T> = Result<T, (StatusCode, Json<ApiErrorResponse>)>;
+pub type ApiResult<T> = Result<T, (StatusCode, Json<ApiErrorResponse>)>;
+
+/// Check a tunnel's `serve` settings.  Serve traffic bypasses the
+/// forwarder, so it cannot be combined with an allowlist, and each Serve
+/// port (or HTTPS path) belongs to one tunnel — the whole port when either
+/// side uses Funnel.
+fn validate_serve<'a>(
+    serve: &ServeSettings,
+    local_port: u16,
+    allowed_sources: &[String],
+    funnel: bool,
+    others: impl IntoIterator<Item = &'a Tunnel>,
+) -> ApiResult<()> {
+    let port = serve.port(local_port);
+    if port == 0 {
+        warn!("Rejected: serve port is 0");
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(api_err("api.error.serve_port_range")),
+        ));
+    }
+    if !serve.path().starts_with('/') {
+        warn!("Rejected: invalid serve path '{}'", serve.path());
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(api_err_params(
+                "api.error.serve_path_invalid",
+                params1("value", serve.path()),
+            )),
+        ));
+    }
+    if !allowed_sources.is_empty() {
+        warn!("Rejected: serve combined with allowed sources");
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(api_err("api.error.serve_allowed_sources")),
+        ));
+    }
+    let taken = others.into_iter().any(|t| {
+        t.local_port == port
+            || t.serve.as_ref().is_some_and(|other| {
+                serve.conflicts_with(local_port, other, t.local_port)
+                    || ((funnel || t.funnel) && other.port(t.local_port) == port)
+            })
+    });
+    if taken {
+        warn!("Rejected: serve port {port} already in use");
+        return Err((
+            StatusCode::CONFLICT,
+            Json(api_err_params(
+                "api.error.serve_conflict",
+                params1("port", port),
+            )),
+        ));
+    }
+    Ok(())
+}
+
+/// Check a tunnel's Funnel settings.  Funnel needs a TLS Serve entry on a
+/// port Tailscale allows it on, and always expires, at most
+/// `FUNNEL_MAX_HOURS` from now.
+fn validate_funnel(
+    serve: Option<&ServeSettings>,
+    local_port: u16,
+    expires_at: Option<u64>,
+) -> ApiResult<()> {
+    let Some(serve) = serve.filter(|s| s.mode != ServeMode::Tcp) else {
+        warn!("Rejected: funnel without https or tls_terminated_tcp serve");
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(api_err("api.error.funnel_requires_serve")),
+        ));
+    };
+    let port = serve.port(local_port);
+    if !serve::FUNNEL_PORTS.contains(&port) {
+        warn!("Rejected: funnel on port {port}");
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(api_err_params(
+                "api.error.funnel_port",
+                params1("port", port),
+            )),
+        ));
+    }
+    let max_hours = settings().funnel_max_hours;
+    let now = unix_now();
+    if !expires_at.is_some_and(|at| at > now && at <= now + max_hours * 3600) {
+        warn!("Rejected: funnel expiry {expires_at:?} outside the next {max_hours}h");
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(api_err_params(
+                "api.error.funnel_expiry",
+                params1("max_hours", max_hours),
+            )),
+        ));
+    }
+    Ok(())
+}
+
+/// Exposing a tunnel on the internet takes the admin token.
+fn require_admin(role: Role) -> ApiResult<()> {
+    if role != Role::Admin {
+        warn!("Rejected: funnel requires the admin token");
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(api_err("api.error.funnel_admin_only")),
+        ));
+    }
+    Ok(())
+}
+
+/// Public URL of a tunnel while it is exposed through Funnel.
+fn funnel_url(tunnel: &Tunnel) -> Option<String> {
+    let host = tunnel.serve_host.as_deref()?;
+    if !serve::funnel_active(tunnel) {
+        return None;
+    }
+    Some(tunnel.serve.as_ref()?.url(host, tunnel.local_port))
+}
+
+/// Audit detail for a public tunnel: its URL and expiry.
+fn funnel_detail(tunnel: &Tunnel) -> Option<String> {
+    let url = funnel_url(tunnel)?;
+    Some(format!(
+        "{url} public until {}",
+        tunnel.funnel_expires_at.unwrap_or_default()
+    ))
+}
+
+/// Warning returned while a tunnel is public, with the minutes left.
+fn funnel_warning(tunnel: &Tunnel) -> Option<ApiMessage> {
+    let url = funnel_url(tunnel)?;
+    let remaining = tunnel.funnel_expires_at?.saturating_sub(unix_now());
+    Some(ApiMessage::with_params(
+        "api.warning.funnel_public",
+        params2("url", url, "minutes", remaining.div_ceil(60)),
+    ))
+}
+
+/// Publish a running tunnel through Tailscale Serve.  Failures leave the
+/// forwarder running and come back as a warning.
+async fn start_serve(tunnel: &mut Tunnel) -> Option<ApiMessage> {
+    tunnel.serve.as_ref()?;
+    match serve::apply(tunnel).await {
+        Ok(host) => {
+            tunnel.serve_host = Some(host);
+            None
+        }
+        Err(e) => {
+            error!("Serve failed: {e}");
+            Some(ApiMessage::with_params(
+                "api.warning.serve_failed",
+                params1("detail", e),
+            ))
+        }
+    }
+}
+
+/// Remove a tunnel's Serve entry, if it has one.
+async fn stop_serve(tunnel: &Tunnel) {
+    if let Some(host) = &tunnel.serve_host {
+        if let Err(e) = serve::remove(tunnel, host).await {
+            error!("Could not remove serve entry: {e}");
+        }
+    }
+}
 
 // ─── GET /api/config ─────────────────────────────────────────────────────
 
 pub async fn get_config() -> Json<ConfigResponse> {
     let hostname = get_hostname();
     let version = env!("CARGO_PKG_VERSION").to_string();
-    println!("[GET /api/config] hostname={hostname} version={version}");
-    Json(ConfigResponse { hostname, version })
+    let listen_address = default_listen_address();
+    debug!(%hostname, %version, %listen_address, "Config requested");
+    Json(ConfigResponse {
+        hostname,
+        version,
+        listen_address,
+    })
+}
+
+// ─── GET /api/tailscale/status ───────────────────────────────────────────
+
+fn tailscale_unavailable(e: String) -> (StatusCode, Json<ApiErrorResponse>) {
+    warn!("Tailscale status unavailable: {e}");
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(api_err_params(
+            "api.error.tailscale_unavailable",
+            params1("error", e),
+        )),
+    )
+}
+
+pub async fn get_tailscale_status() -> ApiResult<Json<TailscaleStatusResponse>> {
+    let status = tailscale::status().await.map_err(tailscale_unavailable)?;
+
+    tailscale::update_identity(&status);
+    let peers = status.peer.unwrap_or_default();
+    let total_peers = peers.len();
+    let mut online: Vec<TailscalePeer> = peers
+        .into_values()
+        .filter(|p| p.online)
+        .map(peer_info)
+        .collect();
+    online.sort_by(|a, b| a.hostname.cmp(&b.hostname));
+
+    let tailnet = status.current_tailnet;
+    let self_node = status.self_node;
+    let non_empty = |s: String| (!s.is_empty()).then_some(s);
+    debug!(
+        backend_state = %status.backend_state,
+        online = online.len(),
+        total_peers,
+        "Tailscale status requested"
+    );
+
+    Ok(Json(TailscaleStatusResponse {
+        backend_state: status.backend_state,
+        ips: status.tailscale_ips,
+        dns_name: self_node
+            .as_ref()
+            .and_then(|s| non_empty(s.dns_name.trim_end_matches('.').to_string())),
+        tailnet: tailnet.as_ref().and_then(|t| non_empty(t.name.clone())),
+        magic_dns_suffix: tailnet
+            .filter(|t| t.magic_dns_enabled)
+            .and_then(|t| non_empty(t.magic_dns_suffix)),
+        key_expiry: self_node.and_then(|s| s.key_expiry),
+        auth_url: non_empty(status.auth_url),
+        health: status.health.unwrap_or_default(),
+        peers: online,
+        total_peers,
+    }))
+}
+
+// ─── GET /api/tailscale/peers ────────────────────────────────────────────
+
+fn peer_info(peer: tailscale::PeerStatus) -> TailscalePeer {
+    let non_empty = |s: String| (!s.is_empty()).then_some(s);
+    let connection = if !peer.active {
+        PeerConnection::Idle
+    } else if !peer.cur_addr.is_empty() {
+        PeerConnection::Direct
+    } else {
+        PeerConnection::Relay
+    };
+    TailscalePeer {
+        hostname: peer.host_name,
+        dns_name: peer.dns_name.trim_end_matches('.').to_string(),
+        ips: peer.tailscale_ips.unwrap_or_default(),
+        os: peer.os,
+        online: peer.online,
+        // Go's zero time means "now" for online peers.
+        last_seen: peer
+            .last_seen
+            .filter(|t| !peer.online && !t.starts_with("0001-")),
+        tags: peer.tags.unwrap_or_default(),
+        connection,
+        endpoint: non_empty(peer.cur_addr),
+        relay: non_empty(peer.relay),
+    }
+}
+
+/// Every peer of the tailnet, online ones first.
+pub async fn get_tailscale_peers() -> ApiResult<Json<Vec<TailscalePeer>>> {
+    let status = tailscale::status().await.map_err(tailscale_unavailable)?;
+    let mut peers: Vec<TailscalePeer> = status
+        .peer
+        .unwrap_or_default()
+        .into_values()
+        .map(peer_info)
+        .collect();
+    peers.sort_by(|a, b| {
+        b.online
+            .cmp(&a.online)
+            .then_with(|| a.hostname.cmp(&b.hostname))
+    });
+    Ok(Json(peers))
+}
+
+// ─── POST /api/tailscale/peers/:ip/ping ──────────────────────────────────
+
+/// How long to wait for a pong before reporting the peer unreachable.
+const PING_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Tailscale-ping a peer, showing whether it answers and over which path.
+pub async fn ping_tailscale_peer(Path(ip): Path<String>) -> ApiResult<Json<PingResponse>> {
+    let addr: IpAddr = ip.parse().map_err(|_| {
+        warn!("Rejected: invalid ping address '{ip}'");
+        (
+            StatusCode::BAD_REQUEST,
+            Json(api_err_params(
+                "api.error.ping_ip_invalid",
+                params1("value", ip.clone()),
+            )),
+        )
+    })?;
+
+    let result = match timeout(PING_TIMEOUT, tailscale::ping(addr)).await {
+        Ok(result) => result.map_err(tailscale_unavailable)?,
+        Err(_) => {
+            info!("Ping {addr}: no reply");
+            return Ok(Json(PingResponse {
+                error: Some(format!("No reply within {}s", PING_TIMEOUT.as_secs())),
+                ..Default::default()
+            }));
+        }
+    };
+
+    let non_empty = |s: String| (!s.is_empty()).then_some(s);
+    let node_name = non_empty(result.node_name);
+    if !result.err.is_empty() {
+        info!("Ping {addr}: {}", result.err);
+        return Ok(Json(PingResponse {
+            node_name,
+            error: Some(result.err),
+            ..Default::default()
+        }));
+    }
+    let connection = if result.endpoint.is_empty() {
+        PeerConnection::Relay
+    } else {
+        PeerConnection::Direct
+    };
+    let latency_ms = (result.latency_seconds * 10_000.0).round() / 10.0;
+    info!("Ping {addr}: pong in {latency_ms}ms ({connection:?})");
+    Ok(Json(PingResponse {
+        success: true,
+        node_name,
+        latency_ms: Some(latency_ms),
+        connection: Some(connection),
+        endpoint: non_empty(result.endpoint),
+        relay: non_empty(result.derp_region_code),
+        error: None,
+    }))
+}
+
+// ─── GET / POST /api/tailscale/login ─────────────────────────────────────
+
+/// How long POST waits for tailscaled to issue an auth URL.
+const AUTH_URL_WAIT: Duration = Duration::from_secs(10);
+
+fn login_response(status: tailscale::Status) -> TailscaleLoginResponse {
+    let auth_url = Some(status.auth_url).filter(|url| !url.is_empty());
+    let auth_qr_svg = auth_url.as_deref().and_then(|url| {
+        QrCode::new(url)
+            .map_err(|e| warn!("Could not encode auth URL as QR code: {e}"))
+            .ok()
+            .map(|qr| qr.render::<svg::Color>().min_dimensions(200, 200).build())
+    });
+    TailscaleLoginResponse {
+        backend_state: status.backend_state,
+        auth_url,
+        auth_qr_svg,
+    }
+}
+
+/// Login state, polled by the dashboard while a login is in progress.
+pub async fn get_tailscale_login() -> ApiResult<Json<TailscaleLoginResponse>> {
+    let status = tailscale::status().await.map_err(tailscale_unavailable)?;
+    Ok(Json(login_response(status)))
+}
+
+/// Start an interactive login and wait briefly for the auth URL.  Refused
+/// once the node is logged in, so a running node cannot be re-registered
+/// to someone else's account from the dashboard.
+pub async fn start_tailscale_login() -> ApiResult<Json<TailscaleLoginResponse>> {
+    let mut status = tailscale::status().await.map_err(tailscale_unavailable)?;
+    if status.backend_state == "Running" {
+        warn!("Rejected: Tailscale login while already running");
+        return Err((
+            StatusCode::CONFLICT,
+            Json(api_err("api.error.tailscale_logged_in")),
+        ));
+    }
+
+    if status.auth_url.is_empty() {
+        info!("Starting interactive Tailscale login");
+        tailscale::start_login()
+            .await
+            .map_err(tailscale_unavailable)?;
+        let deadline = Instant::now() + AUTH_URL_WAIT;
+        while status.auth_url.is_empty()
+            && status.backend_state != "Running"
+            && Instant::now() < deadline
+        {
+            sleep(Duration::from_millis(500)).await;
+            status = tailscale::status().await.map_err(tailscale_unavailable)?;
+        }
+    }
+    Ok(Json(login_response(status)))
 }
 
 // ─── GET /api/tunnels ────────────────────────────────────────────────────
 
 pub async fn list_tunnels(State(state): State<SharedState>) -> Json<Vec<TunnelListItem>> {
     let tunnels = state.read().await;
-    println!("[GET /api/tunnels] Returning {} tunnel(s)", tunnels.len());
+    debug!("Returning {} tunnel(s)", tunnels.len());
 
-    let items: Vec<TunnelListItem> = tunnels
-        .iter()
-        .map(|t| TunnelListItem {
-            connection_url: connection_url_for(t),
-            tunnel: t.clone(),
-        })
-        .collect();
+    let items: Vec<TunnelListItem> = tunnels.iter().map(list_item).collect();
 
     Json(items)
 }
@@ -87,16 +551,36 @@ pub async fn list_tunnels(State(state): State<SharedState>) -> Json<Vec<TunnelLi
 
 pub async fn create_tunnel(
     State(state): State<SharedState>,
-    Json(payload): Json<CreateTunnelRequest>,
+    Extension(role): Extension<Role>,
+    Extension(templates): Extension<SharedTemplates>,
+    Extension(client): Extension<RequestClient>,
+    Json(mut payload): Json<CreateTunnelRequest>,
 ) -> ApiResult<(StatusCode, Json<TunnelResponse>)> {
-    println!(
-        "[POST /api/tunnels] name='{}' local_port={} target={}:{} enabled={}",
+    let template = match payload.template_id.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(id) => {
+            let template = templates.get(id).await.ok_or_else(|| {
+                warn!("Rejected: unknown template '{id}'");
+                (
+                    StatusCode::BAD_REQUEST,
+                    Json(api_err_params(
+                        "api.error.template_not_found",
+                        params1("id", id.to_string()),
+                    )),
+                )
+            })?;
+            apply_template(&mut payload, &template);
+            Some(template)
+        }
+    };
+    info!(
+        "Create requested: name='{}' local_port={} target={}:{} enabled={}",
         payload.name, payload.local_port, payload.target_host, payload.target_port, payload.enabled
     );
 
     // ── Validation ──────────────────────────────────────────────────────
     if payload.name.trim().is_empty() {
-        eprintln!("[POST /api/tunnels] Rejected: empty name");
+        warn!("Rejected: empty name");
         return Err((
             StatusCode::BAD_REQUEST,
             Json(api_err("api.error.name_empty")),
@@ -104,33 +588,60 @@ pub async fn create_tunnel(
     }
 
     if payload.target_host.trim().is_empty() {
-        eprintln!("[POST /api/tunnels] Rejected: empty target_host");
+        warn!("Rejected: empty target_host");
         return Err((
             StatusCode::BAD_REQUEST,
             Json(api_err("api.error.target_host_empty")),
         ));
     }
 
-    if payload.local_port == 0 {
-        eprintln!("[POST /api/tunnels] Rejected: local_port is 0");
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(api_err("api.error.local_port_range")),
-        ));
-    }
-
     if payload.target_port == 0 {
-        eprintln!("[POST /api/tunnels] Rejected: target_port is 0");
+        warn!("Rejected: target_port is 0");
         return Err((
             StatusCode::BAD_REQUEST,
             Json(api_err("api.error.target_port_range")),
         ));
     }
 
+    let allowed_sources = normalize_sources(&payload.allowed_sources).map_err(|invalid| {
+        warn!("Rejected: invalid allowed source '{invalid}'");
+        (
+            StatusCode::BAD_REQUEST,
+            Json(api_err_params(
+                "api.error.allowed_source_invalid",
+                params1("value", invalid),
+            )),
+        )
+    })?;
+
+    let listen_address =
+        parse_listen_address(payload.listen_address.as_deref()).map_err(|invalid| {
+            warn!("Rejected: invalid listen address '{invalid}'");
+            (
+                StatusCode::BAD_REQUEST,
+                Json(api_err_params(
+                    "api.error.listen_address_invalid",
+                    params1("value", invalid),
+                )),
+            )
+        })?;
+    let listen_ip = listen_ip_for(listen_address);
+    let service_type = parse_service_type(payload.service_type.as_deref())
+        .map_err(invalid_service_type)?
+        .or(template.as_ref().and_then(|t| t.service_type));
+    // Kept while the tunnel has the template's service type, and only when
+    // it says more than that type's own format.
+    let connection_format = template
+        .as_ref()
+        .filter(|t| t.service_type == service_type)
+        .and_then(|t| t.connection_format.clone())
+        .filter(|format| service_type.map(|s| s.uri_format()) != Some(format.as_str()));
+    let auto_port = payload.local_port == 0;
+
     // ── Self-loop detection ─────────────────────────────────────────────
     if payload.local_port == payload.target_port && is_loopback_host(&payload.target_host) {
-        eprintln!(
-            "[POST /api/tunnels] Rejected: self-loop (localhost:{} -> localhost:{})",
+        warn!(
+            "Rejected: self-loop (localhost:{} -> localhost:{})",
             payload.local_port, payload.target_port
         );
         return Err((
@@ -142,10 +653,15 @@ pub async fn create_tunnel(
         ));
     }
 
+    // ── Port policy ─────────────────────────────────────────────────────
+    if !auto_port {
+        ports::validate(payload.local_port)?;
+    }
+
     // ── Port availability (system-level) ────────────────────────────────
-    if !is_port_available(payload.local_port).await {
-        eprintln!(
-            "[POST /api/tunnels] Rejected: port {} is in use on the system",
+    if !auto_port && !is_port_available(listen_ip, payload.local_port).await {
+        warn!(
+            "Rejected: port {} is in use on {listen_ip}",
             payload.local_port
         );
         return Err((
@@ -159,10 +675,60 @@ pub async fn create_tunnel(
 
     let mut tunnels = state.write().await;
 
+    // ── Automatic port ──────────────────────────────────────────────────
+    // Picked while holding the state lock, so concurrent creates cannot
+    // pick the same port.
+    if auto_port {
+        // Never the target port of a local target, which would loop.
+        let preferred =
+            Some(payload.target_port).filter(|_| !is_loopback_host(&payload.target_host));
+        payload.local_port = pick_local_port(listen_ip, preferred, |port| {
+            tunnels.iter().any(|t| t.local_port == port)
+        })
+        .await
+        .ok_or_else(|| {
+            let range = auto_port_range();
+            warn!(
+                "Rejected: no free port in {}-{} on {listen_ip}",
+                range.start(),
+                range.end()
+            );
+            (
+                StatusCode::CONFLICT,
+                Json(api_err_params(
+                    "api.error.no_free_port",
+                    params1("range", format!("{}-{}", range.start(), range.end())),
+                )),
+            )
+        })?;
+        info!("Assigned local port {}", payload.local_port);
+    }
+
+    if payload.funnel {
+        validate_funnel(
+            payload.serve.as_ref(),
+            payload.local_port,
+            payload.funnel_expires_at,
+        )?;
+        if payload.enabled {
+            require_admin(role)?;
+        }
+    }
+
+    if let Some(serve) = &payload.serve {
+        validate_serve(
+            serve,
+            payload.local_port,
+            &allowed_sources,
+            payload.funnel,
+            tunnels.iter(),
+        )?;
+    }
+
     // ── Port availability (within our state) ────────────────────────────
     if tunnels.iter().any(|t| t.local_port == payload.local_port) {
-        eprintln!(
-            "[POST /api/tunnels] Rejected: port {} already assigned to another tunnel",
+        warn!(
+            "Rejected: port {} already assigned to another tunnel",
             payload.local_port
         );
         return Err((
@@ -175,20 +741,33 @@ pub async fn create_tunnel(
     }
 
     // ── Build tunnel struct ─────────────────────────────────────────────
-    let mut tunnel = crate::models::Tunnel {
+    let mut tunnel = Tunnel {
         id: Uuid::new_v4().to_string(),
         name: payload.name.trim().to_string(),
         local_port: payload.local_port,
         target_host: payload.target_host.trim().to_string(),
         target_port: payload.target_port,
         enabled: payload.enabled,
-        pid: None,
+        allowed_sources,
+        listen_address,
+        service_type,
+        template_id: template.map(|t| t.id),
+        connection_format,
+        health_check: payload.health_check.unwrap_or_default(),
+        serve: payload.serve,
+        serve_host: None,
+        funnel: payload.funnel,
+        funnel_expires_at: payload.funnel_expires_at.filter(|_| payload.funnel),
+        forwarder: None,
+        stats: Default::default(),
         warning_id: None,
     };
+    record_tunnel(&tunnel.id, &tunnel.name, tunnel.local_port);
+    tunnel_log::register(&tunnel.id);
 
     let mut warning: Option<ApiMessage> = None;
 
-    // ── Spawn socat if enabled (with reachability pre-check) ────────────
+    // ── Start forwarder if enabled (with reachability pre-check) ────────
     if tunnel.enabled {
         // Pre-flight reachability check
         match check_target_reachability(&tunnel.target_host, tunnel.target_port).await {
@@ -198,8 +777,8 @@ pub async fn create_tunnel(
             }
             ReachabilityResult::HostReachablePortClosed => {
                 // Host responds but nothing on that port — warn, continue.
-                println!(
-                    "[POST /api/tunnels] Warning: {}:{} — host reachable but port closed",
+                warn!(
+                    "{}:{} — host reachable but port closed",
                     tunnel.target_host, tunnel.target_port
                 );
                 tunnel.warning_id = Some("api.warning.port_closed".to_string());
@@ -214,10 +793,11 @@ pub async fn create_tunnel(
                 ));
             }
             ReachabilityResult::HostUnreachable(reason) => {
-                eprintln!(
-                    "[POST /api/tunnels] Rejected: host {} unreachable — {reason}",
+                warn!(
+                    "Rejected: host {} unreachable — {reason}",
                     tunnel.target_host
                 );
+                tunnel_log::remove(&tunnel.id);
                 return Err((
                     StatusCode::BAD_GATEWAY,
                     Json(api_err_params(
@@ -228,23 +808,33 @@ pub async fn create_tunnel(
             }
         }
 
-        match spawn_socat(tunnel.local_port, &tunnel.target_host, tunnel.target_port).await {
-            Ok(pid) => {
-                println!(
-                    "[POST /api/tunnels] socat started for '{}' with PID {}",
-                    tunnel.name, pid
-                );
-                tunnel.pid = Some(pid);
+        match spawn_forwarder(
+            listen_ip,
+            tunnel.local_port,
+            &tunnel.target_host,
+            tunnel.target_port,
+            &tunnel.allowed_sources,
+            tunnel.stats.clone(),
+        )
+        .instrument(tunnel_span(&tunnel.id, &tunnel.name, tunnel.local_port))
+        .await
+        {
+            Ok(handle) => {
+                info!("Forwarder started");
+                tunnel.forwarder = Some(handle);
+                if let Some(serve_warning) = start_serve(&mut tunnel).await {
+                    warning = Some(serve_warning);
+                } else if let Some(funnel_warning) = funnel_warning(&tunnel) {
+                    warning = Some(funnel_warning);
+                }
             }
             Err(e) => {
-                eprintln!(
-                    "[POST /api/tunnels] socat failed for '{}': {e}",
-                    tunnel.name
-                );
+                error!("Forwarder failed: {e}");
+                tunnel_log::remove(&tunnel.id);
                 return Err((
                     StatusCode::INTERNAL_SERVER_ERROR,
                     Json(api_err_params(
-                        "api.error.socat_failed",
+                        "api.error.forwarder_failed",
                         params1("detail", e),
                     )),
                 ));
@@ -256,16 +846,21 @@ pub async fn create_tunnel(
 
     // ── Persist ─────────────────────────────────────────────────────────
     if let Err(e) = save_tunnels(&tunnels).await {
-        eprintln!("[POST /api/tunnels] Persistence failed (tunnel IS running): {e}");
+        error!("Persistence failed (tunnel IS running): {e}");
     }
 
-    println!(
-        "[POST /api/tunnels] Created tunnel '{}' id={}",
-        tunnel.name, tunnel.id
-    );
+    info!("Tunnel created");
+    if let Some(detail) = funnel_detail(&tunnel) {
+        audit::record(Some(client.ip().await), "funnel.enabled", &tunnel, &detail).await;
+    }
+    publish(ApiEvent::TunnelCreated {
+        tunnel: list_item(&tunnel),
+    });
 
     let response = TunnelResponse {
         connection_url: connection_url_for(&tunnel),
+        connection_urls: connection_urls_for(&tunnel),
+        connection_strings: connection_strings_for(&tunnel),
         tunnel,
         warning,
     };
@@ -277,15 +872,17 @@ pub async fn create_tunnel(
 
 pub async fn update_tunnel(
     State(state): State<SharedState>,
+    Extension(role): Extension<Role>,
+    Extension(client): Extension<RequestClient>,
     Path(id): Path<String>,
     Json(payload): Json<UpdateTunnelRequest>,
 ) -> ApiResult<Json<TunnelResponse>> {
-    println!("[PUT /api/tunnels/{id}] payload: {payload:?}");
+    debug!(?payload, "Update requested");
 
     let mut tunnels = state.write().await;
 
     let index = tunnels.iter().position(|t| t.id == id).ok_or_else(|| {
-        eprintln!("[PUT /api/tunnels/{id}] Not found");
+        warn!(tunnel.id = %id, "Tunnel not found");
         (
             StatusCode::NOT_FOUND,
             Json(api_err_params(
@@ -296,6 +893,9 @@ pub async fn update_tunnel(
     })?;
 
     let tunnel = &tunnels[index];
+    record_tunnel(&id, &tunnel.name, tunnel.local_port);
+    let was_running = tunnel.forwarder.is_some();
+    let was_public = funnel_url(tunnel).is_some();
 
     // ── Compute new values ──────────────────────────────────────────────
     let new_name = payload
@@ -311,32 +911,105 @@ pub async fn update_tunnel(
         .unwrap_or_else(|| tunnel.target_host.clone());
     let new_target_port = payload.target_port.unwrap_or(tunnel.target_port);
     let new_enabled = payload.enabled.unwrap_or(tunnel.enabled);
-
-    // ── Port validation if changed ──────────────────────────────────────
-    if new_local_port != tunnel.local_port {
-        if tunnels
-            .iter()
-            .enumerate()
-            .any(|(i, t)| i != index && t.local_port == new_local_port)
-        {
-            eprintln!(
-                "[PUT /api/tunnels/{id}] Port {} already assigned to another tunnel",
-                new_local_port
-            );
-            return Err((
-                StatusCode::CONFLICT,
+    let new_allowed_sources = match payload.allowed_sources.as_deref() {
+        Some(sources) => normalize_sources(sources).map_err(|invalid| {
+            warn!("Rejected: invalid allowed source '{invalid}'");
+            (
+                StatusCode::BAD_REQUEST,
                 Json(api_err_params(
-                    "api.error.port_assigned",
-                    params1("port", new_local_port),
+                    "api.error.allowed_source_invalid",
+                    params1("value", invalid),
                 )),
-            ));
+            )
+        })?,
+        None => tunnel.allowed_sources.clone(),
+    };
+    let new_listen_address = match payload.listen_address.as_deref() {
+        Some(value) => parse_listen_address(Some(value)).map_err(|invalid| {
+            warn!("Rejected: invalid listen address '{invalid}'");
+            (
+                StatusCode::BAD_REQUEST,
+                Json(api_err_params(
+                    "api.error.listen_address_invalid",
+                    params1("value", invalid),
+                )),
+            )
+        })?,
+        None => tunnel.listen_address,
+    };
+    let new_service_type = match payload.service_type.as_deref() {
+        Some(value) => parse_service_type(Some(value)).map_err(invalid_service_type)?,
+        None => tunnel.service_type,
+    };
+    let new_serve = match payload.serve {
+        Some(serve) => serve,
+        None => tunnel.serve.clone(),
+    };
+    let new_funnel = payload.funnel.unwrap_or(tunnel.funnel);
+    let new_funnel_expires_at = if new_funnel {
+        payload.funnel_expires_at.or(tunnel.funnel_expires_at)
+    } else {
+        None
+    };
+    if new_funnel {
+        validate_funnel(new_serve.as_ref(), new_local_port, new_funnel_expires_at)?;
+        // Any change to a public tunnel, except taking it down, is an
+        // admin action.
+        if new_enabled {
+            require_admin(role)?;
+        }
+    }
+    if let Some(serve) = &new_serve {
+        let others = tunnels
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != index)
+            .map(|(_, t)| t);
+        validate_serve(
+            serve,
+            new_local_port,
+            &new_allowed_sources,
+            new_funnel,
+            others,
+        )?;
+    }
+    let new_listen_ip = listen_ip_for(new_listen_address);
+    let old_listen_ip = match &tunnel.forwarder {
+        Some(handle) => handle.local_addr.ip(),
+        None => listen_ip_for(tunnel.listen_address),
+    };
+    let port_changed = new_local_port != tunnel.local_port;
+    let rebind = port_changed || new_listen_ip != old_listen_ip;
+    // The tunnel's own listener makes an overlapping address on the same
+    // port look taken, so that one is probed once the listener is stopped.
+    let probe_after_stop = rebind
+        && was_running
+        && !port_changed
+        && (old_listen_ip.is_unspecified() || new_listen_ip.is_unspecified());
+
+    // ── Port validation if the port or listen address changed ───────────
+    if rebind {
+        if port_changed {
+            ports::validate(new_local_port)?;
+
+            if tunnels
+                .iter()
+                .enumerate()
+                .any(|(i, t)| i != index && t.local_port == new_local_port)
+            {
+                warn!("Port {} already assigned to another tunnel", new_local_port);
+                return Err((
+                    StatusCode::CONFLICT,
+                    Json(api_err_params(
+                        "api.error.port_assigned",
+                        params1("port", new_local_port),
+                    )),
+                ));
+            }
         }
 
-        if !is_port_available(new_local_port).await {
-            eprintln!(
-                "[PUT /api/tunnels/{id}] Port {} in use on the system",
-                new_local_port
-            );
+        if !probe_after_stop && !is_port_available(new_listen_ip, new_local_port).await {
+            warn!("Port {} in use on {new_listen_ip}", new_local_port);
             return Err((
                 StatusCode::CONFLICT,
                 Json(api_err_params(
@@ -349,8 +1022,8 @@ pub async fn update_tunnel(
 
     // ── Self-loop detection ─────────────────────────────────────────────
     if new_local_port == new_target_port && is_loopback_host(&new_target_host) {
-        eprintln!(
-            "[PUT /api/tunnels/{id}] Rejected: self-loop (localhost:{} -> localhost:{})",
+        warn!(
+            "Rejected: self-loop (localhost:{} -> localhost:{})",
             new_local_port, new_target_port
         );
         return Err((
@@ -383,9 +1056,7 @@ pub async fn update_tunnel(
                 warning_key = None;
             }
             ReachabilityResult::HostReachablePortClosed => {
-                println!(
-                    "[PUT /api/tunnels/{id}] Warning: {new_target_host}:{new_target_port} — host reachable but port closed"
-                );
+                warn!("{new_target_host}:{new_target_port} — host reachable but port closed");
                 warning_key = Some("api.warning.port_closed".to_string());
                 warning = Some(ApiMessage::with_params(
                     "api.warning.port_closed",
@@ -393,9 +1064,7 @@ pub async fn update_tunnel(
                 ));
             }
             ReachabilityResult::HostUnreachable(reason) => {
-                eprintln!(
-                    "[PUT /api/tunnels/{id}] Rejected: host {new_target_host} unreachable — {reason}"
-                );
+                warn!("Rejected: host {new_target_host} unreachable — {reason}");
                 return Err((
                     StatusCode::BAD_GATEWAY,
                     Json(api_err_params(
@@ -407,33 +1076,86 @@ pub async fn update_tunnel(
         }
     }
 
-    // ── Kill old socat ──────────────────────────────────────────────────
-    let old_pid = tunnel.pid;
-    if let Some(pid) = old_pid {
-        println!("[PUT /api/tunnels/{id}] Killing old socat PID {pid}");
-        if let Err(e) = kill_socat(pid).await {
-            eprintln!("[PUT /api/tunnels/{id}] Failed to kill old socat PID {pid}: {e}");
-        }
-
-        // If the port didn't change, give the OS time to release it.
-        if new_local_port == tunnel.local_port {
-            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    // ── Stop old forwarder ──────────────────────────────────────────────
+    stop_serve(tunnel).await;
+    if let Some(handle) = &tunnel.forwarder {
+        info!("Stopping old forwarder");
+        handle.stop().await;
+    }
+    if probe_after_stop && !is_port_available(new_listen_ip, new_local_port).await {
+        warn!("Port {new_local_port} in use on {new_listen_ip} — staying on {old_listen_ip}");
+        let tunnel = &mut tunnels[index];
+        tunnel.serve_host = None;
+        match spawn_forwarder(
+            old_listen_ip,
+            tunnel.local_port,
+            &tunnel.target_host,
+            tunnel.target_port,
+            &tunnel.allowed_sources,
+            tunnel.stats.clone(),
+        )
+        .instrument(tunnel_span(&id, &tunnel.name, tunnel.local_port))
+        .await
+        {
+            Ok(handle) => {
+                tunnel.forwarder = Some(handle);
+                start_serve(tunnel).await;
+            }
+            Err(e) => {
+                error!("Forwarder failed: {e}");
+                tunnel.forwarder = None;
+                publish(ApiEvent::StatusChanged {
+                    id: id.clone(),
+                    running: false,
+                    error: Some(e),
+                });
+            }
         }
+        return Err((
+            StatusCode::CONFLICT,
+            Json(api_err_params(
+                "api.error.port_in_use",
+                params1("port", new_local_port),
+            )),
+        ));
     }
 
-    // ── Spawn new socat if enabled ──────────────────────────────────────
-    let new_pid = if new_enabled {
-        match spawn_socat(new_local_port, &new_target_host, new_target_port).await {
-            Ok(pid) => {
-                println!("[PUT /api/tunnels/{id}] socat started PID {pid}");
-                Some(pid)
+    // ── Start new forwarder if enabled ──────────────────────────────────
+    let new_forwarder = if new_enabled {
+        match spawn_forwarder(
+            new_listen_ip,
+            new_local_port,
+            &new_target_host,
+            new_target_port,
+            &new_allowed_sources,
+            tunnel.stats.clone(),
+        )
+        .instrument(tunnel_span(&id, &new_name, new_local_port))
+        .await
+        {
+            Ok(handle) => {
+                info!("Forwarder started");
+                if was_running {
+                    tunnel.stats.restarts.fetch_add(1, Ordering::Relaxed);
+                }
+                Some(handle)
             }
             Err(e) => {
-                eprintln!("[PUT /api/tunnels/{id}] socat failed: {e}");
+                error!("Forwarder failed: {e}");
+                if was_running {
+                    // The old forwarder is already stopped.
+                    tunnels[index].forwarder = None;
+                    tunnels[index].serve_host = None;
+                    publish(ApiEvent::StatusChanged {
+                        id: id.clone(),
+                        running: false,
+                        error: Some(e.clone()),
+                    });
+                }
                 return Err((
                     StatusCode::INTERNAL_SERVER_ERROR,
                     Json(api_err_params(
-                        "api.error.socat_failed",
+                        "api.error.forwarder_failed",
                         params1("detail", e),
                     )),
                 ));
@@ -450,7 +1172,21 @@ pub async fn update_tunnel(
     tunnel.target_host = new_target_host;
     tunnel.target_port = new_target_port;
     tunnel.enabled = new_enabled;
-    tunnel.pid = new_pid;
+    tunnel.allowed_sources = new_allowed_sources;
+    tunnel.listen_address = new_listen_address;
+    tunnel.service_type = new_service_type;
+    if payload.service_type.is_some() {
+        // A template's format belongs to the service type it came with.
+        tunnel.connection_format = None;
+    }
+    if let Some(health_check) = payload.health_check {
+        tunnel.health_check = health_check;
+    }
+    tunnel.serve = new_serve;
+    tunnel.serve_host = None;
+    tunnel.funnel = new_funnel;
+    tunnel.funnel_expires_at = new_funnel_expires_at;
+    tunnel.forwarder = new_forwarder;
     // Persist the warning on the tunnel (or clear it).
     if needs_reachability_check {
         tunnel.warning_id = warning_key;
@@ -458,21 +1194,46 @@ pub async fn update_tunnel(
         // Disabled tunnels should not carry stale warnings.
         tunnel.warning_id = None;
     }
+    if tunnel.forwarder.is_some() {
+        if let Some(serve_warning) = start_serve(tunnel).await {
+            warning = Some(serve_warning);
+        } else if let Some(funnel_warning) = funnel_warning(tunnel) {
+            warning = Some(funnel_warning);
+        }
+    }
 
     let updated = tunnel.clone();
+    let item = list_item(tunnel);
 
     // ── Persist ─────────────────────────────────────────────────────────
     if let Err(e) = save_tunnels(&tunnels).await {
-        eprintln!("[PUT /api/tunnels/{id}] Persistence failed (tunnel IS running): {e}");
+        error!("Persistence failed (tunnel IS running): {e}");
     }
 
-    println!(
-        "[PUT /api/tunnels/{id}] Updated tunnel '{}' enabled={}",
-        updated.name, updated.enabled
-    );
+    info!("Tunnel updated (enabled={})", updated.enabled);
+    let audit_entry = match (was_public, funnel_detail(&updated)) {
+        (false, Some(detail)) => Some(("funnel.enabled", detail)),
+        (true, Some(detail)) => Some(("funnel.changed", detail)),
+        (true, None) => Some(("funnel.disabled", "Funnel turned off".to_string())),
+        (false, None) => None,
+    };
+    if let Some((action, detail)) = audit_entry {
+        audit::record(Some(client.ip().await), action, &updated, &detail).await;
+    }
+    let running = updated.forwarder.is_some();
+    publish(ApiEvent::TunnelUpdated { tunnel: item });
+    if running != was_running {
+        publish(ApiEvent::StatusChanged {
+            id: id.clone(),
+            running,
+            error: None,
+        });
+    }
 
     let response = TunnelResponse {
         connection_url: connection_url_for(&updated),
+        connection_urls: connection_urls_for(&updated),
+        connection_strings: connection_strings_for(&updated),
         tunnel: updated,
         warning,
     };
@@ -484,14 +1245,13 @@ pub async fn update_tunnel(
 
 pub async fn delete_tunnel(
     State(state): State<SharedState>,
+    Extension(client): Extension<RequestClient>,
     Path(id): Path<String>,
 ) -> ApiResult<StatusCode> {
-    println!("[DELETE /api/tunnels/{id}]");
-
     let mut tunnels = state.write().await;
 
     let index = tunnels.iter().position(|t| t.id == id).ok_or_else(|| {
-        eprintln!("[DELETE /api/tunnels/{id}] Not found");
+        warn!(tunnel.id = %id, "Tunnel not found");
         (
             StatusCode::NOT_FOUND,
             Json(api_err_params(
@@ -502,42 +1262,88 @@ pub async fn delete_tunnel(
     })?;
 
     let tunnel = &tunnels[index];
-    let name = tunnel.name.clone();
+    record_tunnel(&id, &tunnel.name, tunnel.local_port);
 
-    // ── Kill socat ──────────────────────────────────────────────────────
-    if let Some(pid) = tunnel.pid {
-        println!("[DELETE /api/tunnels/{id}] Killing socat PID {pid}");
-        if let Err(e) = kill_socat(pid).await {
-            eprintln!("[DELETE /api/tunnels/{id}] Failed to kill socat PID {pid}: {e}");
-        }
+    // ── Stop forwarder ──────────────────────────────────────────────────
+    stop_serve(tunnel).await;
+    if let Some(handle) = &tunnel.forwarder {
+        info!("Stopping forwarder");
+        handle.stop().await;
     }
 
-    tunnels.remove(index);
+    let removed = tunnels.remove(index);
+    if funnel_url(&removed).is_some() {
+        audit::record(
+            Some(client.ip().await),
+            "funnel.disabled",
+            &removed,
+            "Tunnel deleted",
+        )
+        .await;
+    }
 
     // ── Persist ─────────────────────────────────────────────────────────
     if let Err(e) = save_tunnels(&tunnels).await {
-        eprintln!("[DELETE /api/tunnels/{id}] Persistence failed: {e}");
+        error!("Persistence failed: {e}");
     }
 
-    println!("[DELETE /api/tunnels/{id}] Deleted tunnel '{name}'");
+    info!("Tunnel deleted");
+    tunnel_log::remove(&id);
+    publish(ApiEvent::TunnelDeleted { id });
 
     Ok(StatusCode::NO_CONTENT)
 }
 
+// ─── GET /api/tunnels/:id/logs ──────────────────────────────────────────
+
+/// Return the tunnel's buffered log lines as JSON, or with `?follow=true`
+/// stream them followed by new lines as server-sent `log` events.
+pub async fn get_tunnel_logs(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+    Query(query): Query<TunnelLogsQuery>,
+) -> ApiResult<Response> {
+    let not_found = || {
+        (
+            StatusCode::NOT_FOUND,
+            Json(api_err_params(
+                "api.error.tunnel_not_found",
+                params1("id", id.clone()),
+            )),
+        )
+    };
+
+    if !state.read().await.iter().any(|t| t.id == id) {
+        return Err(not_found());
+    }
+    let log = tunnel_log::get(&id).ok_or_else(not_found)?;
+    let (backlog, rx) = log.snapshot(query.lines);
+
+    if !query.follow {
+        return Ok(Json(backlog).into_response());
+    }
+
+    debug!(tunnel.id = %id, "Following tunnel log");
+    // Lines missed by a slow follower are skipped rather than ending the stream.
+    let live = BroadcastStream::new(rx).filter_map(Result::ok);
+    let events = tokio_stream::iter(backlog)
+        .chain(live)
+        .map(|entry| Event::default().event("log").json_data(entry));
+
+    Ok(Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response())
+}
+
 // ─── POST /api/test ─────────────────────────────────────────────────────
 
 pub async fn test_endpoint(
     Json(payload): Json<TestConnectionRequest>,
 ) -> Json<TestConnectionResponse> {
-    println!(
-        "[POST /api/test] target={}:{}",
-        payload.target_host, payload.target_port
-    );
-
     let (success, log) = test_connection(&payload.target_host, payload.target_port).await;
 
-    println!(
-        "[POST /api/test] {}:{} -> {}",
+    info!(
+        "{}:{} -> {}",
         payload.target_host,
         payload.target_port,
         if success { "OK" } else { "FAIL" }
@@ -545,3 +1351,26 @@ pub async fn test_endpoint(
 
     Json(TestConnectionResponse { success, log })
 }
+
+// ─── GET /api/discovery ─────────────────────────────────────────────────
+
+/// Services found on the Discloud VLAN, each marked with the tunnel that
+/// already forwards to it, if any.
+pub async fn get_discovery(
+    State(state): State<SharedState>,
+    Query(query): Query<DiscoveryQuery>,
+) -> Json<DiscoveryResponse> {
+    let mut response = discovery::discover(query.refresh).await;
+    let tunnels = state.read().await;
+    for service in &mut response.services {
+        let address = service.address.to_string();
+        service.tunnel_id = tunnels
+            .iter()
+            .find(|t| {
+                t.target_port == service.target_port
+                    && (t.target_host == service.target_host || t.target_host == address)
+            })
+            .map(|t| t.id.clone());
+    }
+    Json(response)
+}
@@ -0,0 +1,178 @@
+use serde_json::{json, Map, Value};
+use tokio::sync::Mutex;
+use tokio::time::{interval, Duration};
+use tracing::{error, info};
+
+use crate::audit;
+use crate::events::{publish, ApiEvent};
+use crate::models::{ServeMode, Tunnel};
+use crate::state::{list_item, save_tunnels, SharedState};
+use crate::tailscale;
+use crate::webhooks::unix_now;
+
+const SERVE_CONFIG: &str = "/localapi/v0/serve-config";
+
+/// Ports Tailscale accepts for Funnel.
+pub const FUNNEL_PORTS: [u16; 3] = [443, 8443, 10000];
+
+/// Whether `tunnel` should currently be reachable through Funnel.
+pub fn funnel_active(tunnel: &Tunnel) -> bool {
+    tunnel.funnel && tunnel.funnel_expires_at.is_some_and(|at| at > unix_now())
+}
+
+/// Serializes read-modify-write cycles of the Serve config.
+static LOCK: Mutex<()> = Mutex::const_new(());
+
+/// The object under `key`, created when missing or `null`.
+fn object<'a>(parent: &'a mut Map<String, Value>, key: &str) -> &'a mut Map<String, Value> {
+    let value = parent.entry(key).or_insert_with(|| json!({}));
+    if !value.is_object() {
+        *value = json!({});
+    }
+    value.as_object_mut().unwrap()
+}
+
+/// `host:port`, with IPv6 literals bracketed.
+fn backend(tunnel: &Tunnel) -> String {
+    if tunnel.target_host.contains(':') {
+        format!("[{}]:{}", tunnel.target_host, tunnel.target_port)
+    } else {
+        format!("{}:{}", tunnel.target_host, tunnel.target_port)
+    }
+}
+
+/// Read the Serve config, let `change` edit it, and write it back.
+/// The config is handled as raw JSON so entries this manager does not own
+/// (e.g. set with `tailscale serve`) survive untouched.
+async fn modify(change: impl FnOnce(&mut Map<String, Value>)) -> Result<(), String> {
+    let _guard = LOCK.lock().await;
+    let mut config = match tailscale::local_api_get::<Value>(SERVE_CONFIG).await? {
+        Value::Object(map) => map,
+        _ => Map::new(),
+    };
+    change(&mut config);
+    config.retain(|_, v| !matches!(v, Value::Object(m) if m.is_empty()));
+    tailscale::local_api_post(SERVE_CONFIG, &Value::Object(config)).await
+}
+
+/// Add the Serve entry of an enabled tunnel.  Returns the MagicDNS name it
+/// was published under, needed to remove it again.
+pub async fn apply(tunnel: &Tunnel) -> Result<String, String> {
+    let Some(serve) = &tunnel.serve else {
+        return Err("Tunnel has no serve settings".to_string());
+    };
+    let host = tailscale::identity()
+        .and_then(|identity| identity.dns_name)
+        .ok_or_else(|| "MagicDNS name unknown — is Tailscale logged in?".to_string())?;
+    let port = serve.port(tunnel.local_port).to_string();
+    let backend = backend(tunnel);
+    let funnel = funnel_active(tunnel);
+
+    modify(|config| {
+        let host_port = format!("{host}:{port}");
+        if funnel {
+            object(config, "AllowFunnel").insert(host_port, json!(true));
+        } else {
+            object(config, "AllowFunnel").remove(&host_port);
+        }
+        let tcp = object(config, "TCP");
+        match serve.mode {
+            ServeMode::Tcp => {
+                tcp.insert(port.clone(), json!({ "TCPForward": backend }));
+            }
+            ServeMode::TlsTerminatedTcp => {
+                tcp.insert(
+                    port.clone(),
+                    json!({ "TCPForward": backend, "TerminateTLS": host }),
+                );
+            }
+            ServeMode::Https => {
+                tcp.insert(port.clone(), json!({ "HTTPS": true }));
+                let web = object(object(config, "Web"), &format!("{host}:{port}"));
+                object(web, "Handlers").insert(
+                    serve.path().to_string(),
+                    json!({ "Proxy": format!("http://{backend}") }),
+                );
+            }
+        }
+    })
+    .await?;
+
+    info!(
+        "Serve {} on {host}:{port}{} -> {backend}{}",
+        serve.mode,
+        if serve.mode == ServeMode::Https {
+            serve.path()
+        } else {
+            ""
+        },
+        if funnel { " (public via Funnel)" } else { "" }
+    );
+    Ok(host)
+}
+
+/// Remove the Serve entry added by [`apply`] under `host`.
+pub async fn remove(tunnel: &Tunnel, host: &str) -> Result<(), String> {
+    let Some(serve) = &tunnel.serve else {
+        return Ok(());
+    };
+    let port = serve.port(tunnel.local_port).to_string();
+
+    modify(|config| {
+        let web_key = format!("{host}:{port}");
+        object(config, "AllowFunnel").remove(&web_key);
+        let mut port_in_use = false;
+        if serve.mode == ServeMode::Https {
+            let web = object(config, "Web");
+            let handlers = object(object(web, &web_key), "Handlers");
+            handlers.remove(serve.path());
+            port_in_use = !handlers.is_empty();
+            if !port_in_use {
+                web.remove(&web_key);
+            }
+        }
+        if !port_in_use {
+            object(config, "TCP").remove(&port);
+        }
+    })
+    .await?;
+
+    info!("Serve on {host}:{port} removed");
+    Ok(())
+}
+
+// ─── Funnel expiry ───────────────────────────────────────────────────────
+
+/// Turn Funnel off for tunnels whose expiry has passed, checking every
+/// 30 seconds.  A tunnel keeps its `funnel` flag until the Serve config
+/// has been updated, so a failed update is retried.
+pub fn spawn_funnel_expiry(state: SharedState) {
+    tokio::spawn(async move {
+        let mut ticker = interval(Duration::from_secs(30));
+        loop {
+            ticker.tick().await;
+            let mut tunnels = state.write().await;
+            let mut changed = false;
+            for tunnel in tunnels.iter_mut().filter(|t| t.funnel && !funnel_active(t)) {
+                if tunnel.serve_host.is_some() {
+                    if let Err(e) = apply(tunnel).await {
+                        error!(tunnel.id = %tunnel.id, "Could not turn off expired Funnel: {e}");
+                        continue;
+                    }
+                }
+                tunnel.funnel = false;
+                tunnel.funnel_expires_at = None;
+                audit::record(None, "funnel.expired", tunnel, "Funnel expired").await;
+                publish(ApiEvent::TunnelUpdated {
+                    tunnel: list_item(tunnel),
+                });
+                changed = true;
+            }
+            if changed {
+                if let Err(e) = save_tunnels(&tunnels).await {
+                    error!("Persistence failed after Funnel expiry: {e}");
+                }
+            }
+        }
+    });
+}
@@ -1,14 +1,31 @@
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr};
+use std::ops::RangeInclusive;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 use tokio::io::AsyncReadExt;
 use tokio::net::TcpListener;
 use tokio::process::Command;
 use tokio::sync::RwLock;
-
-use crate::models::{ReachabilityResult, Tunnel};
-
-fn tunnels_path() -> String {
-    std::env::var("TUNNELS_PATH").unwrap_or_else(|_| "./tunnels.json".to_string())
+use tracing::{debug, error, info, warn, Instrument};
+
+use crate::config::settings;
+use crate::events::{publish, ApiEvent};
+use crate::forwarder::spawn_forwarder;
+use crate::logging::tunnel_span;
+use crate::models::{
+    fill_format, ConnectionString, ConnectionStringKind, ConnectionUrl, ConnectionUrlKind,
+    ListenAddress, ReachabilityResult, Tunnel, TunnelListItem,
+};
+use crate::ports::{self, PortPolicy};
+use crate::readiness;
+use crate::serve;
+use crate::tailscale;
+use crate::tunnel_log;
+
+pub fn tunnels_path() -> String {
+    settings().tunnels_path.to_string_lossy().into_owned()
 }
 
 pub type SharedState = Arc<RwLock<Vec<Tunnel>>>;
@@ -19,19 +36,210 @@ pub fn get_hostname() -> String {
     std::env::var("TAILSCALE_HOSTNAME").unwrap_or_else(|_| "tailscale-discloud".to_string())
 }
 
-/// Build a connection URL for a tunnel: `<hostname>:<local_port>`.
-/// Returns `Some(url)` when the tunnel is enabled, `None` otherwise.
+/// Global listen address for tunnels without their own, from
+/// `LISTEN_ADDRESS` (default `all`).
+pub fn default_listen_address() -> ListenAddress {
+    settings().listen_address
+}
+
+/// Resolve a tunnel's listen setting (or the global default) to the IP
+/// its forwarder binds to.
+pub fn listen_ip_for(listen_address: Option<ListenAddress>) -> IpAddr {
+    match listen_address.unwrap_or_else(default_listen_address) {
+        ListenAddress::All => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        ListenAddress::Tailscale => tailscale::bind_ip(),
+        ListenAddress::Loopback => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        ListenAddress::Ip(ip) => ip,
+    }
+}
+
+/// Move running forwarders of "Tailscale only" tunnels to the current
+/// [`tailscale::bind_ip`], e.g. once tailscaled comes up after startup.
+pub async fn rebind_tailscale_tunnels(state: &SharedState) {
+    let bind_ip = tailscale::bind_ip();
+    let mut tunnels = state.write().await;
+    for tunnel in tunnels.iter_mut() {
+        let listen_address = tunnel.listen_address.unwrap_or_else(default_listen_address);
+        let Some(handle) = tunnel.forwarder.clone() else {
+            continue;
+        };
+        if listen_address != ListenAddress::Tailscale || handle.local_addr.ip() == bind_ip {
+            continue;
+        }
+        let span = tunnel_span(&tunnel.id, &tunnel.name, tunnel.local_port);
+        async {
+            handle.stop().await;
+            match spawn_forwarder(
+                bind_ip,
+                tunnel.local_port,
+                &tunnel.target_host,
+                tunnel.target_port,
+                &tunnel.allowed_sources,
+                tunnel.stats.clone(),
+            )
+            .await
+            {
+                Ok(handle) => {
+                    info!("Moved to {bind_ip}");
+                    tunnel.forwarder = Some(handle);
+                }
+                Err(e) => {
+                    error!("Could not move to {bind_ip}: {e}");
+                    tunnel.forwarder = None;
+                    publish(ApiEvent::StatusChanged {
+                        id: tunnel.id.clone(),
+                        running: false,
+                        error: Some(e),
+                    });
+                }
+            }
+        }
+        .instrument(span)
+        .await;
+    }
+}
+
+/// Whether tailnet connections to `ip` reach a forwarder bound to
+/// `listen_ip`.  `tailscale_bind` is the "Tailscale only" address: when it
+/// is loopback, tailscaled runs in userspace mode and delivers inbound
+/// connections by dialing `127.0.0.1`.
+fn reaches(listen_ip: IpAddr, ip: IpAddr, tailscale_bind: IpAddr) -> bool {
+    match listen_ip {
+        IpAddr::V4(v4) if v4.is_unspecified() => ip.is_ipv4(),
+        IpAddr::V6(v6) if v6.is_unspecified() => true,
+        listen_ip if listen_ip.is_loopback() => tailscale_bind.is_loopback(),
+        listen_ip => listen_ip == ip,
+    }
+}
+
+fn ip_url(ip: IpAddr, port: u16) -> ConnectionUrl {
+    match ip {
+        IpAddr::V4(_) => ConnectionUrl {
+            kind: ConnectionUrlKind::Ipv4,
+            url: format!("{ip}:{port}"),
+        },
+        IpAddr::V6(_) => ConnectionUrl {
+            kind: ConnectionUrlKind::Ipv6,
+            url: format!("[{ip}]:{port}"),
+        },
+    }
+}
+
+/// Every address a tunnel is reachable at from the tailnet, from the node
+/// identity reported by the LocalAPI: the Tailscale Serve address when
+/// published, short name, MagicDNS name, then Tailscale IPs.  Only
+/// addresses that reach the forwarder's listen address are included; a
+/// forwarder bound to another local IP is listed under that IP.  Falls
+/// back to `TAILSCALE_HOSTNAME` until the identity is known.  Empty when
+/// the tunnel is disabled.
+pub fn connection_urls_for(tunnel: &Tunnel) -> Vec<ConnectionUrl> {
+    if !tunnel.enabled {
+        return Vec::new();
+    }
+    let port = tunnel.local_port;
+    let url = |kind, url| ConnectionUrl { kind, url };
+    let listen_ip = match &tunnel.forwarder {
+        Some(handle) => handle.local_addr.ip(),
+        None => listen_ip_for(tunnel.listen_address),
+    };
+
+    let mut urls = Vec::new();
+    if let (Some(serve), Some(host)) = (&tunnel.serve, &tunnel.serve_host) {
+        urls.push(url(ConnectionUrlKind::Serve, serve.url(host, port)));
+    }
+    let Some(identity) = tailscale::identity() else {
+        urls.push(url(
+            ConnectionUrlKind::Hostname,
+            format!("{}:{port}", get_hostname()),
+        ));
+        return urls;
+    };
+
+    let tailscale_bind = tailscale::bind_ip();
+    let ips: Vec<IpAddr> = identity
+        .ips
+        .iter()
+        .copied()
+        .filter(|ip| reaches(listen_ip, *ip, tailscale_bind))
+        .collect();
+    if ips.is_empty() {
+        if !listen_ip.is_loopback() && !listen_ip.is_unspecified() {
+            urls.push(ip_url(listen_ip, port));
+        }
+        return urls;
+    }
+
+    urls.push(url(
+        ConnectionUrlKind::Hostname,
+        format!("{}:{port}", identity.short_name),
+    ));
+    if let Some(dns_name) = identity.dns_name {
+        urls.push(url(
+            ConnectionUrlKind::MagicDns,
+            format!("{dns_name}:{port}"),
+        ));
+    }
+    urls.extend(ips.into_iter().map(|ip| ip_url(ip, port)));
+    // A TCP Serve entry on the local port repeats the MagicDNS address.
+    let mut seen = HashSet::new();
+    urls.retain(|u| seen.insert(u.url.clone()));
+    urls
+}
+
+/// Client URI and CLI command for a tunnel with a `service_type` or a
+/// template's `connection_format`, using the preferred host.  Empty when
+/// disabled or without either.
+pub fn connection_strings_for(tunnel: &Tunnel) -> Vec<ConnectionString> {
+    if !tunnel.enabled || (tunnel.service_type.is_none() && tunnel.connection_format.is_none()) {
+        return Vec::new();
+    }
+    let host = tailscale::identity()
+        .map(|identity| identity.short_name)
+        .unwrap_or_else(get_hostname);
+    let mut strings = tunnel
+        .service_type
+        .map(|s| s.connection_strings(&host, tunnel.local_port))
+        .unwrap_or_default();
+    if let Some(format) = &tunnel.connection_format {
+        strings.retain(|s| s.kind != ConnectionStringKind::Uri);
+        strings.insert(
+            0,
+            ConnectionString {
+                kind: ConnectionStringKind::Uri,
+                value: fill_format(format, &host, tunnel.local_port),
+            },
+        );
+    }
+    strings
+}
+
+/// The preferred connection URL for a tunnel, e.g.
+/// `tailscale-discloud:5432`.  Returns `Some(url)` when the tunnel is
+/// enabled, `None` otherwise.
 pub fn connection_url_for(tunnel: &Tunnel) -> Option<String> {
-    if tunnel.enabled {
-        Some(format!("{}:{}", get_hostname(), tunnel.local_port))
-    } else {
-        None
+    connection_urls_for(tunnel)
+        .into_iter()
+        .next()
+        .map(|u| u.url)
+}
+
+/// A tunnel as listed by the API, with its live connection state.
+pub fn list_item(tunnel: &Tunnel) -> TunnelListItem {
+    TunnelListItem {
+        connection_url: connection_url_for(tunnel),
+        connection_urls: connection_urls_for(tunnel),
+        connection_strings: connection_strings_for(tunnel),
+        listen_ip: tunnel.forwarder.as_ref().map(|f| f.local_addr.ip()),
+        rejected_connections: tunnel.stats.rejected.load(Ordering::Relaxed),
+        active_connections: tunnel.stats.active.load(Ordering::Relaxed),
+        health: tunnel.stats.health(),
+        tunnel: tunnel.clone(),
     }
 }
 
 /// Returns `true` when `host` resolves to a loopback address (localhost,
 /// 127.x.x.x, ::1, 0.0.0.0).  Used to detect self-loop configurations
-/// where socat would forward traffic back to itself.
+/// where the forwarder would send traffic back to itself.
 pub fn is_loopback_host(host: &str) -> bool {
     let h = host.trim().to_lowercase();
     if h == "localhost" || h == "::1" || h == "0.0.0.0" {
@@ -61,7 +269,7 @@ pub async fn check_target_reachability(host: &str, port: u16) -> ReachabilityRes
     use tokio::time::{timeout, Duration};
 
     let addr = format!("{host}:{port}");
-    println!("[reachability] Checking {addr}");
+    debug!("Checking {addr}");
 
     match timeout(
         Duration::from_secs(3),
@@ -71,7 +279,7 @@ pub async fn check_target_reachability(host: &str, port: u16) -> ReachabilityRes
     {
         // Connected successfully — service is listening.
         Ok(Ok(_stream)) => {
-            println!("[reachability] {addr} — reachable, port open");
+            info!("{addr} — reachable, port open");
             ReachabilityResult::Reachable
         }
         // Connection attempt returned an error within the timeout.
@@ -79,19 +287,19 @@ pub async fn check_target_reachability(host: &str, port: u16) -> ReachabilityRes
             let kind = e.kind();
             if kind == ErrorKind::ConnectionRefused {
                 // RST received → host is alive but port is closed.
-                println!("[reachability] {addr} — host reachable, port closed (ConnectionRefused)");
+                info!("{addr} — host reachable, port closed (ConnectionRefused)");
                 ReachabilityResult::HostReachablePortClosed
             } else {
                 // Any other error (DNS failure, no route, network
                 // unreachable, permission denied, …) → treat as
                 // host unreachable.
-                eprintln!("[reachability] {addr} — unreachable: {e} (kind={kind:?})");
+                warn!("{addr} — unreachable: {e} (kind={kind:?})");
                 ReachabilityResult::HostUnreachable(e.to_string())
             }
         }
         // Timeout expired — host did not respond in time.
         Err(_) => {
-            eprintln!("[reachability] {addr} — unreachable: connection timed out");
+            warn!("{addr} — unreachable: connection timed out");
             ReachabilityResult::HostUnreachable("Connection timed out".to_string())
         }
     }
@@ -102,155 +310,103 @@ pub async fn check_target_reachability(host: &str, port: u16) -> ReachabilityRes
 pub async fn load_tunnels() -> Vec<Tunnel> {
     let path = tunnels_path();
     match tokio::fs::read_to_string(&path).await {
-        Ok(contents) => {
-            let tunnels: Vec<Tunnel> = serde_json::from_str(&contents).unwrap_or_default();
-            println!("[state] Loaded {} tunnel(s) from {}", tunnels.len(), path);
-            tunnels
+        Ok(contents) => match serde_json::from_str::<Vec<Tunnel>>(&contents) {
+            Ok(tunnels) => {
+                info!("Loaded {} tunnel(s) from {}", tunnels.len(), path);
+                readiness::set_persistence(Ok(format!(
+                    "Loaded {} tunnel(s) from {path}",
+                    tunnels.len()
+                )));
+                tunnels
+            }
+            Err(e) => {
+                error!("Could not parse {path}: {e} — starting with empty list");
+                readiness::set_persistence(Err(format!("Could not parse {path}: {e}")));
+                Vec::new()
+            }
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            warn!("Could not read {path}: {e} — starting with empty list");
+            readiness::set_persistence(Ok(format!("{path} does not exist yet")));
+            Vec::new()
         }
         Err(e) => {
-            eprintln!("[state] Could not read {path}: {e} — starting with empty list");
+            warn!("Could not read {path}: {e} — starting with empty list");
+            readiness::set_persistence(Err(format!("Could not read {path}: {e}")));
             Vec::new()
         }
     }
 }
 
 /// Persist the current tunnel list to disk.
-/// PID fields are skipped during serialization automatically.
+/// Forwarder handles are skipped during serialization automatically.
 pub async fn save_tunnels(tunnels: &[Tunnel]) -> Result<(), String> {
     let path = tunnels_path();
     let json = serde_json::to_string_pretty(tunnels).map_err(|e| {
-        let msg = format!("[state] JSON serialization error: {e}");
-        eprintln!("{msg}");
+        let msg = format!("JSON serialization error: {e}");
+        error!("{msg}");
         msg
     })?;
 
     tokio::fs::write(&path, json).await.map_err(|e| {
-        let msg = format!("[state] Failed to write {path}: {e}");
-        eprintln!("{msg}");
+        let msg = format!("Failed to write {path}: {e}");
+        error!("{msg}");
+        readiness::set_persistence(Err(msg.clone()));
         msg
     })?;
 
-    println!("[state] Persisted {} tunnel(s) to {}", tunnels.len(), path);
+    info!("Persisted {} tunnel(s) to {}", tunnels.len(), path);
+    readiness::set_persistence(Ok(format!("Saved {} tunnel(s) to {path}", tunnels.len())));
     Ok(())
 }
 
-/// Check whether a given TCP port is available by attempting to bind to it.
-pub async fn is_port_available(port: u16) -> bool {
-    TcpListener::bind(("0.0.0.0", port)).await.is_ok()
+/// Check whether a given TCP port is available on `ip` by attempting to
+/// bind to it there.
+pub async fn is_port_available(ip: IpAddr, port: u16) -> bool {
+    TcpListener::bind((ip, port)).await.is_ok()
 }
 
-/// Spawn a `socat` process that forwards `local_port` -> `target_host:target_port`.
-///
-/// After spawning, waits briefly and verifies the process is still alive.
-/// If socat exits immediately (bad args, port conflict, etc.) the stderr
-/// output is captured and returned as an error — no zombie / orphan is left.
-pub async fn spawn_socat(
-    local_port: u16,
-    target_host: &str,
-    target_port: u16,
-) -> Result<u32, String> {
-    let listen_arg = format!("TCP-LISTEN:{local_port},fork,reuseaddr");
-    let connect_arg = format!("TCP:{target_host}:{target_port}");
-
-    println!("[socat] Spawning: socat {listen_arg} {connect_arg}");
-
-    use std::os::unix::process::CommandExt;
-
-    let mut std_cmd = std::process::Command::new("socat");
-    std_cmd.process_group(0);
-
-    let mut child = Command::from(std_cmd)
-        .arg(&listen_arg)
-        .arg(&connect_arg)
-        .stdin(std::process::Stdio::null())
-        .stdout(std::process::Stdio::null())
-        .stderr(std::process::Stdio::piped())
-        .kill_on_drop(false)
-        .spawn()
-        .map_err(|e| {
-            let msg = format!("[socat] Failed to spawn socat: {e}");
-            eprintln!("{msg}");
-            msg
-        })?;
-
-    let pid = child
-        .id()
-        .ok_or_else(|| "[socat] Failed to obtain socat PID".to_string())?;
-
-    println!("[socat] Spawned with PID {pid} (and PGID {pid}), verifying it stays alive...");
-
-    tokio::time::sleep(std::time::Duration::from_millis(250)).await;
-
-    match child.try_wait() {
-        Ok(Some(status)) => {
-            let mut stderr_output = String::new();
-            if let Some(ref mut stderr) = child.stderr {
-                let _ = stderr.read_to_string(&mut stderr_output).await;
-            }
-            let stderr_output = stderr_output.trim().to_string();
-            let detail = if stderr_output.is_empty() {
-                format!("exit {status}")
-            } else {
-                format!("exit {status}: {stderr_output}")
-            };
-            let msg = format!("[socat] PID {pid} exited immediately — {detail}");
-            eprintln!("{msg}");
-            Err(msg)
-        }
-        Ok(_) => {
-            println!("[socat] PID {pid} is alive and listening on :{local_port}");
-
-            tokio::spawn(async move {
-                match child.wait().await {
-                    Ok(status) => eprintln!("[socat] PID {pid} exited with {status}"),
-                    Err(e) => eprintln!("[socat] PID {pid} wait error: {e}"),
-                }
-            });
-
-            Ok(pid)
-        }
-        Err(e) => {
-            let _ = Command::new("kill")
-                .arg("-9")
-                .arg(format!("-{}", pid))
-                .output()
-                .await;
-            let msg = format!("[socat] Failed to check PID {pid} status: {e}");
-            eprintln!("{msg}");
-            Err(msg)
-        }
-    }
+/// Ports picked for tunnels created without a `local_port`
+/// (`AUTO_PORT_RANGE`).
+pub fn auto_port_range() -> RangeInclusive<u16> {
+    settings().auto_port_range.clone()
 }
 
-/// Kill a socat process by PID using `kill -9`.
-/// Kill a socat process AND all its children by targeting the Process Group (PGID).
-pub async fn kill_socat(pid: u32) -> Result<(), String> {
-    println!("[socat] Killing Process Group for PID {pid}");
-
-    let status = tokio::process::Command::new("sh")
-        .arg("-c")
-        .arg(format!("kill -9 -{}", pid))
-        .status()
-        .await
-        .map_err(|e| {
-            let msg = format!("[socat] Failed to execute shell kill for PGID {pid}: {e}");
-            eprintln!("{msg}");
-            msg
-        })?;
+/// A free local port on `ip`: `preferred` when possible, otherwise the
+/// first port of [`auto_port_range`].  Ports for which `assigned` returns
+/// true belong to other tunnels and are skipped, as are ports the
+/// [port policy](crate::ports) refuses.
+pub async fn pick_local_port(
+    ip: IpAddr,
+    preferred: Option<u16>,
+    assigned: impl Fn(u16) -> bool,
+) -> Option<u16> {
+    pick_port(ip, preferred, auto_port_range(), ports::policy(), assigned).await
+}
 
-    if status.success() {
-        println!("[socat] Process group {pid} killed successfully");
-    } else {
-        eprintln!("[socat] kill command exited with {status} for PGID {pid} (may already be dead)");
+async fn pick_port(
+    ip: IpAddr,
+    preferred: Option<u16>,
+    range: RangeInclusive<u16>,
+    policy: &PortPolicy,
+    assigned: impl Fn(u16) -> bool,
+) -> Option<u16> {
+    for port in preferred.into_iter().chain(range) {
+        if port != 0
+            && policy.check(port).is_ok()
+            && !assigned(port)
+            && is_port_available(ip, port).await
+        {
+            return Some(port);
+        }
     }
-
-    Ok(())
+    None
 }
 
 /// Test connectivity to a host:port using `nc -zvw3`.
 /// Returns `(success, combined_log)`.
 pub async fn test_connection(target_host: &str, target_port: u16) -> (bool, String) {
-    println!("[test] Testing connection to {target_host}:{target_port}");
+    info!("Testing connection to {target_host}:{target_port}");
 
     let result = Command::new("nc")
         .arg("-zvw3")
@@ -294,23 +450,23 @@ pub async fn test_connection(target_host: &str, target_port: u16) -> (bool, Stri
             }
 
             if success {
-                println!("[test] {target_host}:{target_port} — OK");
+                info!("{target_host}:{target_port} — OK");
             } else {
-                eprintln!("[test] {target_host}:{target_port} — FAILED: {log}");
+                warn!("{target_host}:{target_port} — FAILED: {log}");
             }
 
             (success, log)
         }
         Err(e) => {
             let msg = format!("Failed to run nc: {e}");
-            eprintln!("[test] {msg}");
+            error!("{msg}");
             (false, msg)
         }
     }
 }
 
 /// Restore tunnels on boot: for each enabled tunnel whose port is free,
-/// attempt **once** to spawn socat.  If the spawn fails the tunnel is
+/// attempt **once** to start its forwarder.  If the spawn fails the tunnel is
 /// marked `enabled = false` so we never retry in an infinite loop.
 pub async fn restore_tunnels(state: &SharedState) {
     let mut tunnels = state.write().await;
@@ -318,56 +474,171 @@ pub async fn restore_tunnels(state: &SharedState) {
     let mut restored = 0u32;
     let mut failed = 0u32;
 
-    println!("[boot] Restoring {total} tunnel(s)...");
+    info!("Restoring {total} tunnel(s)...");
 
     for tunnel in tunnels.iter_mut() {
+        tunnel_log::register(&tunnel.id);
         if !tunnel.enabled {
-            tunnel.pid = None;
+            tunnel.forwarder = None;
             continue;
         }
 
-        if !is_port_available(tunnel.local_port).await {
-            eprintln!(
-                "[boot] Port {} is already in use — disabling tunnel '{}'",
-                tunnel.local_port, tunnel.name
-            );
-            tunnel.enabled = false;
-            tunnel.pid = None;
-            failed += 1;
-            continue;
-        }
-
-        match spawn_socat(tunnel.local_port, &tunnel.target_host, tunnel.target_port).await {
-            Ok(pid) => {
-                println!(
-                    "[boot] Restored '{}' (:{} -> {}:{}) PID {pid}",
-                    tunnel.name, tunnel.local_port, tunnel.target_host, tunnel.target_port
-                );
-                tunnel.pid = Some(pid);
-                restored += 1;
-            }
+        let span = tunnel_span(&tunnel.id, &tunnel.name, tunnel.local_port);
+        match restore_one(tunnel).instrument(span).await {
+            Ok(()) => restored += 1,
             Err(e) => {
-                eprintln!(
-                    "[boot] Failed to restore '{}': {e} — marking as disabled",
-                    tunnel.name
-                );
                 tunnel.enabled = false;
-                tunnel.pid = None;
+                tunnel.forwarder = None;
                 failed += 1;
+                publish(ApiEvent::StatusChanged {
+                    id: tunnel.id.clone(),
+                    running: false,
+                    error: Some(e),
+                });
             }
         }
     }
 
-    println!(
-        "[boot] Restore complete: {restored} active, {failed} failed, {} skipped",
+    let summary = format!(
+        "{restored} active, {failed} failed, {} skipped",
         total as u32 - restored - failed
     );
+    info!("Restore complete: {summary}");
+    readiness::set_restored(summary);
 
     // Persist updated state (disabled tunnels that failed to restore).
     if failed > 0 {
         let tunnels_slice: &[Tunnel] = &tunnels;
         if let Err(e) = save_tunnels(tunnels_slice).await {
-            eprintln!("[boot] Failed to persist updated state after restore: {e}");
+            error!("Failed to persist updated state after restore: {e}");
         }
     }
 }
+
+/// Start the forwarder of a single enabled tunnel during restore.
+/// Returns the reason as `Err` when the tunnel should be disabled.
+async fn restore_one(tunnel: &mut Tunnel) -> Result<(), String> {
+    let listen_ip = listen_ip_for(tunnel.listen_address);
+    if let Err(denied) = ports::check(tunnel.local_port) {
+        warn!(
+            "Port {} is not allowed ({denied}) — disabling tunnel",
+            tunnel.local_port
+        );
+        return Err(format!(
+            "Port {} is not allowed ({denied})",
+            tunnel.local_port
+        ));
+    }
+    if !is_port_available(listen_ip, tunnel.local_port).await {
+        warn!(
+            "Port {} is already in use on {listen_ip} — disabling tunnel",
+            tunnel.local_port
+        );
+        return Err(format!(
+            "Port {} is already in use on {listen_ip}",
+            tunnel.local_port
+        ));
+    }
+
+    match spawn_forwarder(
+        listen_ip,
+        tunnel.local_port,
+        &tunnel.target_host,
+        tunnel.target_port,
+        &tunnel.allowed_sources,
+        tunnel.stats.clone(),
+    )
+    .await
+    {
+        Ok(handle) => {
+            info!(
+                "Restored ({listen_ip}:{} -> {}:{})",
+                tunnel.local_port, tunnel.target_host, tunnel.target_port
+            );
+            tunnel.forwarder = Some(handle);
+            if tunnel.serve.is_some() {
+                match serve::apply(tunnel).await {
+                    Ok(host) => tunnel.serve_host = Some(host),
+                    Err(e) => warn!("Serve not restored: {e}"),
+                }
+            }
+            Ok(())
+        }
+        Err(e) => {
+            error!("Failed to restore: {e} — marking as disabled");
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    const LOOPBACK: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+    fn policy(reserved: &[u16]) -> PortPolicy {
+        PortPolicy::new(vec![1024..=u16::MAX], reserved.iter().copied(), 3000)
+    }
+
+    #[tokio::test]
+    async fn picks_preferred_port_first() {
+        let picked = pick_port(LOOPBACK, Some(47105), 47100..=47109, &policy(&[]), |_| {
+            false
+        })
+        .await;
+        assert_eq!(picked, Some(47105));
+    }
+
+    #[tokio::test]
+    async fn picks_in_range_skipping_assigned_and_reserved_ports() {
+        let picked = pick_port(LOOPBACK, None, 47110..=47119, &policy(&[47111]), |port| {
+            port == 47110
+        })
+        .await;
+        assert_eq!(picked, Some(47112));
+    }
+
+    #[tokio::test]
+    async fn skips_ports_in_use_and_refused_preferred_port() {
+        let _taken = TcpListener::bind((LOOPBACK, 47120)).await.unwrap();
+        let picked = pick_port(LOOPBACK, Some(3000), 47120..=47129, &policy(&[]), |_| false).await;
+        assert_eq!(picked, Some(47121));
+    }
+
+    #[tokio::test]
+    async fn gives_up_when_range_is_exhausted() {
+        let picked = pick_port(LOOPBACK, None, 47130..=47131, &policy(&[47130]), |port| {
+            port == 47131
+        })
+        .await;
+        assert_eq!(picked, None);
+    }
+
+    #[test]
+    fn all_ipv4_reaches_only_ipv4_addresses() {
+        let listen = ip("0.0.0.0");
+        assert!(reaches(listen, ip("100.64.0.5"), LOOPBACK));
+        assert!(!reaches(listen, ip("fd7a:115c:a1e0::5"), LOOPBACK));
+        assert!(reaches(ip("::"), ip("fd7a:115c:a1e0::5"), LOOPBACK));
+    }
+
+    #[test]
+    fn loopback_is_reached_only_in_userspace_mode() {
+        assert!(reaches(LOOPBACK, ip("100.64.0.5"), LOOPBACK));
+        assert!(reaches(LOOPBACK, ip("fd7a:115c:a1e0::5"), LOOPBACK));
+        assert!(!reaches(LOOPBACK, ip("100.64.0.5"), ip("100.64.0.5")));
+    }
+
+    #[test]
+    fn specific_ip_is_reached_only_through_itself() {
+        let listen = ip("100.64.0.5");
+        assert!(reaches(listen, ip("100.64.0.5"), listen));
+        assert!(!reaches(listen, ip("fd7a:115c:a1e0::5"), listen));
+        assert!(!reaches(ip("192.168.1.10"), ip("100.64.0.5"), LOOPBACK));
+    }
+}
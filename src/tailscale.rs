@@ -0,0 +1,463 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::RwLock;
+
+use axum::body::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::{header, Method, Request, StatusCode};
+use hyper_util::rt::TokioIo;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tokio::net::UnixStream;
+use tokio::process::Command;
+use tokio::time::Duration;
+use tracing::{info, warn};
+
+use crate::state::{rebind_tailscale_tunnels, SharedState};
+
+/// Path of the tailscaled LocalAPI socket, from `TAILSCALE_SOCKET`,
+/// falling back to the path a supervised `tailscaled` is started with.
+pub fn socket_path() -> String {
+    std::env::var("TAILSCALE_SOCKET")
+        .unwrap_or_else(|_| "/var/run/tailscale/tailscaled.sock".to_string())
+}
+
+/// Longest any LocalAPI request may take, so a hung tailscaled cannot
+/// stall its callers.
+const LOCAL_API_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Longest a WhoIs lookup may take.  It runs on API requests from
+/// loopback, before authentication and rate limiting.
+const WHOIS_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Perform a single request against the tailscaled LocalAPI over its Unix
+/// socket.  Returns the HTTP status and raw body.
+async fn local_api_request(
+    method: Method,
+    path: &str,
+    body: Option<Vec<u8>>,
+) -> Result<(StatusCode, Bytes), String> {
+    tokio::time::timeout(LOCAL_API_TIMEOUT, local_api_round_trip(method, path, body))
+        .await
+        .map_err(|_| {
+            format!(
+                "LocalAPI request {path} timed out after {}s",
+                LOCAL_API_TIMEOUT.as_secs()
+            )
+        })?
+}
+
+async fn local_api_round_trip(
+    method: Method,
+    path: &str,
+    body: Option<Vec<u8>>,
+) -> Result<(StatusCode, Bytes), String> {
+    let socket = socket_path();
+    let stream = UnixStream::connect(&socket)
+        .await
+        .map_err(|e| format!("Could not connect to {socket}: {e}"))?;
+
+    let (mut sender, conn) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+        .await
+        .map_err(|e| format!("LocalAPI handshake failed: {e}"))?;
+    tokio::spawn(async move {
+        if let Err(e) = conn.await {
+            warn!("LocalAPI connection error: {e}");
+        }
+    });
+
+    // tailscaled only accepts LocalAPI requests addressed to this host.
+    let request = Request::builder()
+        .method(method)
+        .uri(path)
+        .header(header::HOST, "local-tailscaled.sock")
+        .body(Full::new(Bytes::from(body.unwrap_or_default())))
+        .map_err(|e| format!("Invalid LocalAPI request: {e}"))?;
+
+    let response = sender
+        .send_request(request)
+        .await
+        .map_err(|e| format!("LocalAPI request {path} failed: {e}"))?;
+    let status = response.status();
+    let bytes = response
+        .into_body()
+        .collect()
+        .await
+        .map_err(|e| format!("LocalAPI response {path} unreadable: {e}"))?
+        .to_bytes();
+
+    Ok((status, bytes))
+}
+
+/// GET a LocalAPI endpoint and decode its JSON body.
+/// Non-2xx responses are turned into an error carrying the body text.
+pub async fn local_api_get<T: DeserializeOwned>(path: &str) -> Result<T, String> {
+    let (status, bytes) = local_api_request(Method::GET, path, None).await?;
+    decode(path, status, &bytes)
+}
+
+/// POST to a LocalAPI endpoint without a body and decode its JSON response.
+async fn local_api_post_empty<T: DeserializeOwned>(path: &str) -> Result<T, String> {
+    let (status, bytes) = local_api_request(Method::POST, path, None).await?;
+    decode(path, status, &bytes)
+}
+
+fn decode<T: DeserializeOwned>(path: &str, status: StatusCode, bytes: &[u8]) -> Result<T, String> {
+    if !status.is_success() {
+        let text = String::from_utf8_lossy(bytes).trim().to_string();
+        return Err(format!("LocalAPI {path} returned {status}: {text}"));
+    }
+    serde_json::from_slice(bytes).map_err(|e| format!("LocalAPI {path} returned bad JSON: {e}"))
+}
+
+/// POST a JSON body to a LocalAPI endpoint, ignoring the response body.
+pub async fn local_api_post<T: Serialize>(path: &str, body: &T) -> Result<(), String> {
+    let body = serde_json::to_vec(body).map_err(|e| format!("Invalid LocalAPI body: {e}"))?;
+    let (status, bytes) = local_api_request(Method::POST, path, Some(body)).await?;
+    if !status.is_success() {
+        let text = String::from_utf8_lossy(&bytes).trim().to_string();
+        return Err(format!("LocalAPI {path} returned {status}: {text}"));
+    }
+    Ok(())
+}
+
+// ─── WhoIs ───────────────────────────────────────────────────────────────
+
+/// Subset of `tailcfg.Node` returned inside a WhoIs response.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct WhoIsNode {
+    #[serde(default)]
+    pub name: String,
+    /// Tailnet addresses in CIDR form, e.g. `100.101.102.103/32`.
+    #[serde(default)]
+    pub addresses: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct WhoIsResponse {
+    pub node: WhoIsNode,
+}
+
+impl WhoIsNode {
+    /// The node's tailnet IPs with the `/32` or `/128` suffix stripped.
+    pub fn ips(&self) -> Vec<IpAddr> {
+        self.addresses
+            .iter()
+            .filter_map(|a| a.split('/').next()?.parse().ok())
+            .collect()
+    }
+}
+
+/// Identify the tailnet node behind a connection's peer address.
+///
+/// With `--tun=userspace-networking`, tailscaled delivers inbound tailnet
+/// connections by dialing `127.0.0.1`, so the socket peer is a loopback
+/// address; tailscaled keeps a map of those ephemeral ports and WhoIs
+/// resolves them back to the originating node.  Returns `None` when the
+/// peer is not a tailnet node (e.g. a Discloud VLAN client) or the
+/// LocalAPI is unavailable or does not answer within [`WHOIS_TIMEOUT`].
+pub async fn whois(peer: SocketAddr) -> Option<WhoIsNode> {
+    let addr = peer.to_string().replace('[', "%5B").replace(']', "%5D");
+    let path = format!("/localapi/v0/whois?addr={addr}");
+    tokio::time::timeout(WHOIS_TIMEOUT, local_api_get::<WhoIsResponse>(&path))
+        .await
+        .ok()?
+        .ok()
+        .map(|resp| resp.node)
+}
+
+// ─── Status ──────────────────────────────────────────────────────────────
+
+/// Subset of `ipnstate.Status` returned by `/localapi/v0/status`.
+/// tailscaled sends `null` for empty collections, hence the `Option`s.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Status {
+    /// `Running`, `NeedsLogin`, `Stopped`, …
+    #[serde(default)]
+    pub backend_state: String,
+    #[serde(default, rename = "TailscaleIPs")]
+    pub tailscale_ips: Vec<IpAddr>,
+    /// Interactive login URL while the backend needs a login.
+    #[serde(default, rename = "AuthURL")]
+    pub auth_url: String,
+    #[serde(default, rename = "Self")]
+    pub self_node: Option<PeerStatus>,
+    #[serde(default)]
+    pub current_tailnet: Option<TailnetStatus>,
+    #[serde(default)]
+    pub peer: Option<HashMap<String, PeerStatus>>,
+    /// Health warnings reported by tailscaled.
+    #[serde(default)]
+    pub health: Option<Vec<String>>,
+}
+
+/// Subset of `ipnstate.PeerStatus`, used for this node and its peers.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PeerStatus {
+    #[serde(default)]
+    pub host_name: String,
+    /// MagicDNS name with a trailing dot, e.g. `node.tailnet.ts.net.`.
+    #[serde(default, rename = "DNSName")]
+    pub dns_name: String,
+    #[serde(default, rename = "TailscaleIPs")]
+    pub tailscale_ips: Option<Vec<IpAddr>>,
+    #[serde(default, rename = "OS")]
+    pub os: String,
+    #[serde(default)]
+    pub online: bool,
+    /// RFC 3339 timestamp; absent when key expiry is disabled.
+    #[serde(default)]
+    pub key_expiry: Option<String>,
+    /// RFC 3339 timestamp; the zero time for peers that are online.
+    #[serde(default)]
+    pub last_seen: Option<String>,
+    #[serde(default)]
+    pub tags: Option<Vec<String>>,
+    /// Whether traffic was exchanged with the peer recently.
+    #[serde(default)]
+    pub active: bool,
+    /// `ip:port` of the direct path, empty when relayed.
+    #[serde(default)]
+    pub cur_addr: String,
+    /// Code of the peer's home DERP relay, e.g. `fra`.
+    #[serde(default)]
+    pub relay: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TailnetStatus {
+    #[serde(default, rename = "Name")]
+    pub name: String,
+    #[serde(default, rename = "MagicDNSSuffix")]
+    pub magic_dns_suffix: String,
+    #[serde(default, rename = "MagicDNSEnabled")]
+    pub magic_dns_enabled: bool,
+}
+
+pub async fn status() -> Result<Status, String> {
+    local_api_get("/localapi/v0/status").await
+}
+
+/// Subset of `ipnstate.PingResult`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PingResult {
+    #[serde(default)]
+    pub node_name: String,
+    #[serde(default)]
+    pub err: String,
+    #[serde(default)]
+    pub latency_seconds: f64,
+    /// `ip:port` the pong came from over a direct path.
+    #[serde(default)]
+    pub endpoint: String,
+    /// Relay the pong came through, when not direct.
+    #[serde(default, rename = "DERPRegionCode")]
+    pub derp_region_code: String,
+}
+
+/// Send one disco ping to a tailnet address, as `tailscale ping` does.
+pub async fn ping(ip: IpAddr) -> Result<PingResult, String> {
+    local_api_post_empty(&format!("/localapi/v0/ping?ip={ip}&type=disco")).await
+}
+
+/// Start an interactive login.  tailscaled fills in the status' `AuthURL`
+/// shortly after, and moves to `Running` once the user completes it.
+pub async fn start_login() -> Result<(), String> {
+    local_api_post("/localapi/v0/login-interactive", &serde_json::json!({})).await
+}
+
+// ─── Node identity ───────────────────────────────────────────────────────
+
+/// How tailnet clients can address this node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeIdentity {
+    /// First label of the MagicDNS name, e.g. `tailscale-discloud-1` when
+    /// Tailscale suffixed a duplicate hostname.
+    pub short_name: String,
+    /// Full MagicDNS name without the trailing dot, when MagicDNS is on.
+    pub dns_name: Option<String>,
+    pub ips: Vec<IpAddr>,
+}
+
+impl NodeIdentity {
+    fn from_status(status: &Status) -> Option<Self> {
+        let node = status.self_node.as_ref()?;
+        let fqdn = node.dns_name.trim_end_matches('.');
+        let short_name = match fqdn.split('.').next() {
+            Some(label) if !label.is_empty() => label.to_string(),
+            _ if !node.host_name.is_empty() => node.host_name.clone(),
+            _ => return None,
+        };
+        let magic_dns = status
+            .current_tailnet
+            .as_ref()
+            .is_none_or(|t| t.magic_dns_enabled);
+        Some(Self {
+            short_name,
+            dns_name: (magic_dns && fqdn.contains('.')).then(|| fqdn.to_string()),
+            ips: status.tailscale_ips.clone(),
+        })
+    }
+}
+
+/// Last identity seen in a LocalAPI status, used for connection URLs.
+static NODE_IDENTITY: RwLock<Option<NodeIdentity>> = RwLock::new(None);
+
+/// The node identity from the last successful status, if any.
+pub fn identity() -> Option<NodeIdentity> {
+    NODE_IDENTITY.read().unwrap().clone()
+}
+
+/// Remember the identity found in `status`.
+pub fn update_identity(status: &Status) {
+    let Some(identity) = NodeIdentity::from_status(status) else {
+        return;
+    };
+    let mut current = NODE_IDENTITY.write().unwrap();
+    if current.as_ref() != Some(&identity) {
+        info!(
+            short_name = %identity.short_name,
+            dns_name = identity.dns_name.as_deref().unwrap_or("-"),
+            "Tailscale node identity updated"
+        );
+        *current = Some(identity);
+    }
+}
+
+/// Re-read the node identity periodically, as the MagicDNS name and IPs
+/// only become known once tailscaled is logged in.  The "Tailscale only"
+/// bind address is worked out again with it, and running forwarders are
+/// moved when it changes.
+pub fn spawn_identity_refresh(state: SharedState) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            ticker.tick().await;
+            let Ok(status) = status().await else {
+                continue;
+            };
+            update_identity(&status);
+            if status.tailscale_ips.is_empty() {
+                continue;
+            }
+            let bind_ip = local_tailscale_ip(&status.tailscale_ips)
+                .unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST));
+            let previous = self::bind_ip();
+            if bind_ip != previous {
+                info!("Tailscale-only tunnels now bind to {bind_ip} (was {previous})");
+                *TAILSCALE_BIND_IP.write().unwrap() = Some(bind_ip);
+                rebind_tailscale_tunnels(&state).await;
+            }
+        }
+    });
+}
+
+// ─── Self address discovery ──────────────────────────────────────────────
+
+/// Address that "Tailscale only" forwarders bind to, set by
+/// [`discover_bind_ip`].
+static TAILSCALE_BIND_IP: RwLock<Option<IpAddr>> = RwLock::new(None);
+
+/// Returns `true` for addresses in Tailscale's CGNAT (`100.64.0.0/10`) or
+/// ULA (`fd7a:115c:a1e0::/48`) ranges.
+fn is_tailscale_ip(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => v4.octets()[0] == 100 && (v4.octets()[1] & 0xc0) == 64,
+        IpAddr::V6(v6) => v6.segments()[..3] == [0xfd7a, 0x115c, 0xa1e0],
+    }
+}
+
+/// Tailscale IPs assigned to local interfaces, read from `ip -o addr`.
+/// Only present when tailscaled runs with a kernel TUN device.
+async fn interface_tailscale_ips() -> Vec<IpAddr> {
+    let output = match Command::new("ip")
+        .arg("-o")
+        .arg("addr")
+        .arg("show")
+        .output()
+        .await
+    {
+        Ok(out) => String::from_utf8_lossy(&out.stdout).to_string(),
+        Err(e) => {
+            warn!("Could not list interfaces: {e}");
+            return Vec::new();
+        }
+    };
+
+    // Lines look like: "3: tailscale0    inet 100.101.102.103/32 scope global ..."
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            words.find(|w| *w == "inet" || *w == "inet6")?;
+            words.next()?.split('/').next()?.parse().ok()
+        })
+        .filter(is_tailscale_ip)
+        .collect()
+}
+
+/// The first of `ips` assigned to a local interface, if any.
+fn local_tailscale_ip(ips: &[IpAddr]) -> Option<IpAddr> {
+    ips.iter()
+        .copied()
+        .find(|ip| std::net::TcpListener::bind((*ip, 0)).is_ok())
+}
+
+/// Work out which local address "Tailscale only" forwarders should bind to.
+///
+/// The node's Tailscale IPs come from the LocalAPI, falling back to the
+/// interface list.  If one of them is assigned to a local interface
+/// (kernel TUN mode) it is used directly.  Otherwise tailscaled is running
+/// with userspace networking and delivers inbound connections by dialing
+/// `127.0.0.1`, so loopback is the Tailscale-only address.
+pub async fn discover_bind_ip() -> IpAddr {
+    let ips = match status().await {
+        Ok(status) if !status.tailscale_ips.is_empty() => {
+            update_identity(&status);
+            status.tailscale_ips
+        }
+        Ok(_) => interface_tailscale_ips().await,
+        Err(e) => {
+            warn!("LocalAPI status unavailable: {e}");
+            interface_tailscale_ips().await
+        }
+    };
+
+    let bind_ip = match local_tailscale_ip(&ips) {
+        Some(ip) => {
+            info!("Tailscale IP {ip} is on a local interface — binding to it");
+            ip
+        }
+        None if ips.is_empty() => {
+            warn!("No Tailscale IPs found — using loopback");
+            IpAddr::V4(Ipv4Addr::LOCALHOST)
+        }
+        None => {
+            let ips: Vec<String> = ips.iter().map(|ip| ip.to_string()).collect();
+            info!(
+                "Tailscale IPs [{}] not on a local interface (userspace networking) — using loopback",
+                ips.join(", ")
+            );
+            IpAddr::V4(Ipv4Addr::LOCALHOST)
+        }
+    };
+
+    *TAILSCALE_BIND_IP.write().unwrap() = Some(bind_ip);
+    bind_ip
+}
+
+/// The address discovered by [`discover_bind_ip`], or loopback if
+/// discovery has not run.
+pub fn bind_ip() -> IpAddr {
+    TAILSCALE_BIND_IP
+        .read()
+        .unwrap()
+        .unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST))
+}
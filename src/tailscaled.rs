@@ -0,0 +1,423 @@
+use std::process::Stdio;
+use std::sync::RwLock;
+
+use tokio::process::{Child, Command};
+use tokio::time::{sleep, timeout, Duration, Instant};
+use tracing::{error, info, warn};
+
+use crate::limits::env_u64;
+use crate::tailscale;
+
+/// Restart delays after tailscaled exits, doubling up to the maximum.
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// A run at least this long resets the restart delay.
+const STABLE_RUN: Duration = Duration::from_secs(60);
+
+/// Delay between login attempts while the auth key is rejected.
+const LOGIN_RETRY: Duration = Duration::from_secs(30);
+
+/// Interval at which the backend state is checked: for a completed login,
+/// e.g. one started from the dashboard, or for the node being logged out.
+const STATUS_POLL: Duration = Duration::from_secs(5);
+
+/// Time allowed for one `tailscale up`.
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Where the supervised daemon currently is in its lifecycle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Phase {
+    Starting,
+    LoggingIn,
+    Running,
+    /// Logged out and no auth key to log in with.
+    NeedsLogin,
+    LoginFailed(String),
+    /// Not running; restarting after a delay.
+    Down(String),
+}
+
+#[derive(Debug, Clone)]
+struct Daemon {
+    phase: Phase,
+    pid: Option<u32>,
+    restarts: u64,
+}
+
+/// State of the supervised daemon; `None` when tailscaled is managed
+/// elsewhere.
+static DAEMON: RwLock<Option<Daemon>> = RwLock::new(None);
+
+fn set_phase(phase: Phase, pid: Option<u32>) {
+    if let Some(daemon) = DAEMON.write().unwrap().as_mut() {
+        daemon.phase = phase;
+        daemon.pid = pid;
+    }
+}
+
+/// How tailscaled is started and logged in, from the environment:
+/// - `TAILSCALED_PATH` / `TAILSCALE_PATH` — binaries (default from `PATH`)
+/// - `TAILSCALE_STATE` — state file (default `/var/lib/tailscale/tailscaled.state`)
+/// - `TAILSCALED_TUN` — `--tun` value (default `userspace-networking`)
+/// - `TAILSCALE_AUTHKEY` / `TAILSCALE_HOSTNAME` — passed to `tailscale up`
+/// - `TAILSCALED_READY_TIMEOUT` — seconds to wait for the LocalAPI (default 30)
+#[derive(Debug)]
+struct Config {
+    tailscaled: String,
+    tailscale: String,
+    state: String,
+    socket: String,
+    tun: String,
+    authkey: Option<String>,
+    hostname: String,
+    ready_timeout: Duration,
+}
+
+impl Config {
+    fn from_env() -> Self {
+        let var = |name: &str, default: &str| {
+            std::env::var(name)
+                .ok()
+                .filter(|v| !v.trim().is_empty())
+                .unwrap_or_else(|| default.to_string())
+        };
+        Self {
+            tailscaled: var("TAILSCALED_PATH", "tailscaled"),
+            tailscale: var("TAILSCALE_PATH", "tailscale"),
+            state: var("TAILSCALE_STATE", "/var/lib/tailscale/tailscaled.state"),
+            socket: tailscale::socket_path(),
+            tun: var("TAILSCALED_TUN", "userspace-networking"),
+            authkey: std::env::var("TAILSCALE_AUTHKEY")
+                .ok()
+                .filter(|k| !k.trim().is_empty()),
+            hostname: var("TAILSCALE_HOSTNAME", "tailscale-discloud"),
+            ready_timeout: Duration::from_secs(env_u64("TAILSCALED_READY_TIMEOUT", 30)),
+        }
+    }
+}
+
+/// Whether this process should run tailscaled itself (`MANAGE_TAILSCALED`).
+pub fn enabled() -> bool {
+    std::env::var("MANAGE_TAILSCALED")
+        .is_ok_and(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+}
+
+/// Start supervising tailscaled in the background.  Tailscale-only
+/// tunnels move to the node's address once it is up, through
+/// [`tailscale::spawn_identity_refresh`].
+pub fn start() {
+    let config = Config::from_env();
+    info!(
+        "Managing tailscaled ({}, socket {}, state {})",
+        config.tailscaled, config.socket, config.state
+    );
+    *DAEMON.write().unwrap() = Some(Daemon {
+        phase: Phase::Starting,
+        pid: None,
+        restarts: 0,
+    });
+    tokio::spawn(supervise(config));
+}
+
+/// Readiness of the supervised daemon, for `/readyz`.  `None` when
+/// tailscaled is not managed by this process.
+pub fn check() -> Option<Result<String, String>> {
+    let daemon = DAEMON.read().unwrap().clone()?;
+    let restarts = format!("{} restart(s)", daemon.restarts);
+    Some(match daemon.phase {
+        Phase::Running => Ok(format!(
+            "Running (pid {}, {restarts})",
+            daemon.pid.unwrap_or_default()
+        )),
+        Phase::Starting => Err("Starting".to_string()),
+        Phase::LoggingIn => Err("Logging in".to_string()),
+        Phase::NeedsLogin => Err("Logged out — waiting for a login".to_string()),
+        Phase::LoginFailed(detail) => Err(format!("Login failed: {detail}")),
+        Phase::Down(detail) => Err(format!("{detail} ({restarts})")),
+    })
+}
+
+fn spawn_daemon(config: &Config) -> std::io::Result<Child> {
+    Command::new(&config.tailscaled)
+        .arg(format!("--tun={}", config.tun))
+        .arg(format!("--state={}", config.state))
+        .arg(format!("--socket={}", config.socket))
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+}
+
+/// Run tailscaled, restarting it with backoff whenever it exits.
+async fn supervise(config: Config) {
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        let started = Instant::now();
+        let reason = match spawn_daemon(&config) {
+            Ok(mut child) => {
+                let pid = child.id();
+                info!("tailscaled started (pid {})", pid.unwrap_or_default());
+                set_phase(Phase::Starting, pid);
+                tokio::select! {
+                    status = child.wait() => match status {
+                        Ok(status) => format!("Exited with {status}"),
+                        Err(e) => format!("Wait failed: {e}"),
+                    },
+                    reason = watch(&config, pid) => {
+                        let _ = child.kill().await;
+                        reason
+                    }
+                }
+            }
+            Err(e) => format!("Could not start {}: {e}", config.tailscaled),
+        };
+
+        if started.elapsed() >= STABLE_RUN {
+            backoff = MIN_BACKOFF;
+        }
+        error!(
+            "tailscaled: {reason} — restarting in {}s",
+            backoff.as_secs()
+        );
+        set_phase(Phase::Down(reason), None);
+        if let Some(daemon) = DAEMON.write().unwrap().as_mut() {
+            daemon.restarts += 1;
+        }
+        sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+/// What [`watch`] does with the backend state it just read.
+#[derive(Debug, PartialEq, Eq)]
+enum Step {
+    Set(Phase),
+    LogIn,
+}
+
+/// Decide the next step from the backend state, whether an auth key is
+/// configured and due for another attempt, and the current phase.
+fn next_step(status: &tailscale::Status, authkey: bool, retry_due: bool, current: &Phase) -> Step {
+    match status.backend_state.as_str() {
+        "Running" => Step::Set(Phase::Running),
+        // Connecting; logging in again would not help.
+        "Starting" => Step::Set(Phase::Starting),
+        // An interactive login is in progress; `tailscale up` would
+        // replace its URL.
+        _ if !status.auth_url.is_empty() => Step::Set(Phase::NeedsLogin),
+        _ if authkey && retry_due => Step::LogIn,
+        // Keep reporting the last failure until the next attempt.
+        _ if authkey && matches!(current, Phase::LoginFailed(_)) => Step::Set(current.clone()),
+        _ => Step::Set(Phase::NeedsLogin),
+    }
+}
+
+/// Wait for the LocalAPI, then follow the backend state for as long as
+/// the daemon runs: log in with the auth key, retrying failed logins, and
+/// log in again when the node is logged out.  Returns only when the
+/// LocalAPI never comes up.
+async fn watch(config: &Config, pid: Option<u32>) -> String {
+    let mut status = match wait_for_local_api(config.ready_timeout).await {
+        Ok(status) => status,
+        Err(reason) => return reason,
+    };
+    info!(
+        "tailscaled LocalAPI ready (backend state {})",
+        status.backend_state
+    );
+
+    let mut phase = Phase::Starting;
+    let mut last_attempt: Option<Instant> = None;
+    loop {
+        let retry_due = last_attempt.is_none_or(|at| at.elapsed() >= LOGIN_RETRY);
+        let next = match next_step(&status, config.authkey.is_some(), retry_due, &phase) {
+            Step::Set(next) => next,
+            Step::LogIn => {
+                set_phase(Phase::LoggingIn, pid);
+                last_attempt = Some(Instant::now());
+                let authkey = config.authkey.as_deref().unwrap_or_default();
+                match login(config, authkey).await {
+                    Ok(()) => Phase::Running,
+                    Err(detail) => {
+                        error!("tailscale up failed: {detail}");
+                        Phase::LoginFailed(detail)
+                    }
+                }
+            }
+        };
+
+        if next != phase {
+            match &next {
+                Phase::Running => info!("Tailscale is up"),
+                Phase::NeedsLogin if config.authkey.is_none() => {
+                    warn!("Tailscale is logged out and TAILSCALE_AUTHKEY is not set")
+                }
+                Phase::NeedsLogin => warn!("Tailscale is logged out — waiting for a login"),
+                _ => {}
+            }
+        }
+        phase = next;
+        set_phase(phase.clone(), pid);
+
+        sleep(STATUS_POLL).await;
+        if let Ok(current) = tailscale::status().await {
+            status = current;
+        }
+    }
+}
+
+/// Poll the LocalAPI until it answers.
+async fn wait_for_local_api(limit: Duration) -> Result<tailscale::Status, String> {
+    let deadline = Instant::now() + limit;
+    loop {
+        match tailscale::status().await {
+            Ok(status) => return Ok(status),
+            Err(e) if Instant::now() >= deadline => {
+                return Err(format!(
+                    "LocalAPI not ready after {}s: {e}",
+                    limit.as_secs()
+                ));
+            }
+            Err(_) => sleep(Duration::from_millis(250)).await,
+        }
+    }
+}
+
+/// Run `tailscale up` with the auth key and hostname from the environment.
+async fn login(config: &Config, authkey: &str) -> Result<(), String> {
+    let output = Command::new(&config.tailscale)
+        .arg(format!("--socket={}", config.socket))
+        .arg("up")
+        .arg(format!("--authkey={authkey}"))
+        .arg(format!("--hostname={}", config.hostname))
+        .arg("--accept-routes")
+        .arg("--accept-dns")
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let output = timeout(LOGIN_TIMEOUT, output)
+        .await
+        .map_err(|_| format!("No answer within {}s", LOGIN_TIMEOUT.as_secs()))?
+        .map_err(|e| format!("Could not run {}: {e}", config.tailscale))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Err(match stderr.trim() {
+        "" => format!("Exited with {}", output.status),
+        detail => detail.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(backend_state: &str, auth_url: &str) -> tailscale::Status {
+        serde_json::from_value(serde_json::json!({
+            "BackendState": backend_state,
+            "AuthURL": auth_url,
+        }))
+        .unwrap()
+    }
+
+    fn failed() -> Phase {
+        Phase::LoginFailed("invalid key".to_string())
+    }
+
+    #[test]
+    fn running_backend_is_running() {
+        for current in [Phase::Starting, Phase::NeedsLogin, failed()] {
+            assert_eq!(
+                next_step(&status("Running", ""), true, true, &current),
+                Step::Set(Phase::Running)
+            );
+        }
+    }
+
+    #[test]
+    fn logged_out_node_logs_in_with_the_auth_key() {
+        assert_eq!(
+            next_step(&status("NeedsLogin", ""), true, true, &Phase::Starting),
+            Step::LogIn
+        );
+        // Logged out after having run: log in again.
+        assert_eq!(
+            next_step(&status("NeedsLogin", ""), true, true, &Phase::Running),
+            Step::LogIn
+        );
+    }
+
+    #[test]
+    fn logged_out_node_without_auth_key_needs_login() {
+        assert_eq!(
+            next_step(&status("NeedsLogin", ""), false, true, &Phase::Running),
+            Step::Set(Phase::NeedsLogin)
+        );
+        assert_eq!(
+            next_step(&status("Stopped", ""), false, true, &Phase::Starting),
+            Step::Set(Phase::NeedsLogin)
+        );
+    }
+
+    #[test]
+    fn failed_login_is_reported_until_the_next_attempt() {
+        assert_eq!(
+            next_step(&status("NeedsLogin", ""), true, false, &failed()),
+            Step::Set(failed())
+        );
+        assert_eq!(
+            next_step(&status("NeedsLogin", ""), true, true, &failed()),
+            Step::LogIn
+        );
+    }
+
+    #[test]
+    fn logout_soon_after_a_login_does_not_keep_running() {
+        // The retry is not due yet, but the node must not stay Running.
+        assert_eq!(
+            next_step(&status("NeedsLogin", ""), true, false, &Phase::Running),
+            Step::Set(Phase::NeedsLogin)
+        );
+    }
+
+    #[test]
+    fn interactive_login_is_left_alone() {
+        let pending = status("NeedsLogin", "https://login.tailscale.com/a/abc");
+        assert_eq!(
+            next_step(&pending, true, true, &Phase::Running),
+            Step::Set(Phase::NeedsLogin)
+        );
+    }
+
+    #[test]
+    fn reconnecting_backend_is_starting() {
+        assert_eq!(
+            next_step(&status("Starting", ""), true, true, &Phase::Running),
+            Step::Set(Phase::Starting)
+        );
+    }
+
+    #[test]
+    fn check_reports_each_phase() {
+        assert_eq!(check(), None);
+        *DAEMON.write().unwrap() = Some(Daemon {
+            phase: Phase::Starting,
+            pid: None,
+            restarts: 0,
+        });
+        set_phase(Phase::Running, Some(42));
+        assert_eq!(
+            check(),
+            Some(Ok("Running (pid 42, 0 restart(s))".to_string()))
+        );
+        set_phase(Phase::NeedsLogin, Some(42));
+        assert_eq!(
+            check(),
+            Some(Err("Logged out — waiting for a login".to_string()))
+        );
+        set_phase(failed(), Some(42));
+        assert_eq!(check(), Some(Err("Login failed: invalid key".to_string())));
+        *DAEMON.write().unwrap() = None;
+    }
+}
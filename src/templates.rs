@@ -0,0 +1,574 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+};
+use tokio::sync::RwLock;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::config::settings;
+use crate::models::{
+    ApiErrorResponse, CreateTemplateRequest, HealthCheck, ServiceType, Template,
+    UpdateTemplateRequest,
+};
+use crate::ports::{self, PortPolicy};
+use crate::routes::{
+    api_err, api_err_params, invalid_service_type, params1, parse_service_type, ApiResult,
+};
+
+/// Templates shipped with the binary: type, id, name, default port.
+const BUILTIN: [(ServiceType, &str, &str, u16); 4] = [
+    (ServiceType::Postgres, "postgres", "PostgreSQL", 5432),
+    (ServiceType::Mysql, "mysql", "MySQL", 3306),
+    (ServiceType::Redis, "redis", "Redis", 6379),
+    (ServiceType::Mongodb, "mongodb", "MongoDB", 27017),
+];
+
+fn builtin() -> impl Iterator<Item = Template> {
+    BUILTIN
+        .into_iter()
+        .map(|(service_type, id, name, port)| Template {
+            id: id.to_string(),
+            name: name.to_string(),
+            service_type: Some(service_type),
+            target_host: id.to_string(),
+            target_port: port,
+            local_port: None,
+            health_check: HealthCheck::Tcp,
+            connection_format: Some(service_type.uri_format().to_string()),
+            description_key: Some(format!("templates.{id}.description")),
+            description: None,
+            builtin: true,
+        })
+}
+
+/// User templates, persisted next to the tunnels file unless
+/// `TEMPLATES_PATH` says otherwise.
+#[derive(Debug)]
+pub struct Templates {
+    user: RwLock<Vec<Template>>,
+    path: String,
+}
+
+pub type SharedTemplates = Arc<Templates>;
+
+fn templates_path() -> String {
+    let settings = settings();
+    match &settings.templates_path {
+        Some(path) => path.to_string_lossy().into_owned(),
+        None => settings
+            .tunnels_path
+            .with_file_name("templates.json")
+            .to_string_lossy()
+            .into_owned(),
+    }
+}
+
+impl Templates {
+    pub async fn load() -> Self {
+        Self::load_from(templates_path()).await
+    }
+
+    async fn load_from(path: String) -> Self {
+        let user: Vec<Template> = match tokio::fs::read_to_string(&path).await {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                warn!("Could not parse {path}: {e} — starting without user templates");
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+        let user = unique(user);
+        if !user.is_empty() {
+            info!("Loaded {} template(s) from {path}", user.len());
+        }
+        Self {
+            user: RwLock::new(user),
+            path,
+        }
+    }
+
+    /// A built-in or user template by id.
+    pub async fn get(&self, id: &str) -> Option<Template> {
+        match builtin().find(|t| t.id == id) {
+            Some(template) => Some(template),
+            None => self.user.read().await.iter().find(|t| t.id == id).cloned(),
+        }
+    }
+
+    async fn save(&self, templates: &[Template]) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(templates)
+            .map_err(|e| format!("JSON serialization error: {e}"))?;
+        tokio::fs::write(&self.path, json).await.map_err(|e| {
+            let msg = format!("Failed to write {}: {e}", self.path);
+            error!("{msg}");
+            msg
+        })
+    }
+}
+
+/// Drop user templates whose id is taken by a built-in or an earlier
+/// entry, which could be neither found nor changed.
+fn unique(templates: Vec<Template>) -> Vec<Template> {
+    let mut kept: Vec<Template> = Vec::with_capacity(templates.len());
+    for template in templates {
+        if builtin().any(|t| t.id == template.id) || kept.iter().any(|t| t.id == template.id) {
+            warn!(
+                "Ignoring template '{}': id {} is already taken",
+                template.name, template.id
+            );
+            continue;
+        }
+        kept.push(template);
+    }
+    kept
+}
+
+// ─── Handlers ────────────────────────────────────────────────────────────
+
+fn not_found(id: &str) -> (StatusCode, Json<ApiErrorResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(api_err_params(
+            "api.error.template_not_found",
+            params1("id", id.to_string()),
+        )),
+    )
+}
+
+fn bad_request(id: &str) -> (StatusCode, Json<ApiErrorResponse>) {
+    (StatusCode::BAD_REQUEST, Json(api_err(id)))
+}
+
+/// `None` for a blank value.
+fn clean(value: Option<String>) -> Option<String> {
+    value
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+fn validate(template: &Template, policy: &PortPolicy) -> ApiResult<()> {
+    if template.name.is_empty() {
+        return Err(bad_request("api.error.name_empty"));
+    }
+    if template.target_host.is_empty() {
+        return Err(bad_request("api.error.target_host_empty"));
+    }
+    if template.target_port == 0 {
+        return Err(bad_request("api.error.target_port_range"));
+    }
+    if let Some(port) = template.local_port {
+        policy.validate(port)?;
+    }
+    Ok(())
+}
+
+/// Reject changes to built-in templates.
+fn builtin_guard(id: &str) -> ApiResult<()> {
+    if builtin().any(|t| t.id == id) {
+        warn!("Rejected: built-in template '{id}' is read-only");
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(api_err_params(
+                "api.error.template_builtin",
+                params1("id", id.to_string()),
+            )),
+        ));
+    }
+    Ok(())
+}
+
+/// `GET /api/templates` — built-in templates first.
+pub async fn list_templates(State(templates): State<SharedTemplates>) -> Json<Vec<Template>> {
+    let user = templates.user.read().await;
+    Json(builtin().chain(user.iter().cloned()).collect())
+}
+
+/// `POST /api/templates`
+pub async fn create_template(
+    State(templates): State<SharedTemplates>,
+    Json(payload): Json<CreateTemplateRequest>,
+) -> ApiResult<(StatusCode, Json<Template>)> {
+    let template = Template {
+        id: Uuid::new_v4().to_string(),
+        name: payload.name.trim().to_string(),
+        service_type: parse_service_type(payload.service_type.as_deref())
+            .map_err(invalid_service_type)?,
+        target_host: payload.target_host.trim().to_string(),
+        target_port: payload.target_port,
+        local_port: payload.local_port.filter(|p| *p != 0),
+        health_check: payload.health_check,
+        connection_format: clean(payload.connection_format),
+        description_key: None,
+        description: clean(payload.description),
+        builtin: false,
+    };
+    validate(&template, ports::policy())?;
+
+    let mut user = templates.user.write().await;
+    user.push(template.clone());
+    if let Err(e) = templates.save(&user).await {
+        error!("Persistence failed: {e}");
+    }
+    info!(template.id = %template.id, "Template '{}' created", template.name);
+
+    Ok((StatusCode::CREATED, Json(template)))
+}
+
+/// `PUT /api/templates/:id`
+pub async fn update_template(
+    State(templates): State<SharedTemplates>,
+    Path(id): Path<String>,
+    Json(payload): Json<UpdateTemplateRequest>,
+) -> ApiResult<Json<Template>> {
+    builtin_guard(&id)?;
+    let service_type = payload
+        .service_type
+        .as_deref()
+        .map(|v| parse_service_type(Some(v)).map_err(invalid_service_type))
+        .transpose()?;
+
+    let mut user = templates.user.write().await;
+    let template = user
+        .iter_mut()
+        .find(|t| t.id == id)
+        .ok_or_else(|| not_found(&id))?;
+    let mut updated = template.clone();
+    if let Some(name) = payload.name {
+        updated.name = name.trim().to_string();
+    }
+    if let Some(host) = payload.target_host {
+        updated.target_host = host.trim().to_string();
+    }
+    if let Some(port) = payload.target_port {
+        updated.target_port = port;
+    }
+    if let Some(port) = payload.local_port {
+        updated.local_port = Some(port).filter(|p| *p != 0);
+    }
+    if let Some(service_type) = service_type {
+        updated.service_type = service_type;
+    }
+    if let Some(health_check) = payload.health_check {
+        updated.health_check = health_check;
+    }
+    if payload.connection_format.is_some() {
+        updated.connection_format = clean(payload.connection_format);
+    }
+    if payload.description.is_some() {
+        updated.description = clean(payload.description);
+    }
+    validate(&updated, ports::policy())?;
+    *template = updated.clone();
+
+    if let Err(e) = templates.save(&user).await {
+        error!("Persistence failed: {e}");
+    }
+    info!(template.id = %id, "Template updated");
+
+    Ok(Json(updated))
+}
+
+/// `DELETE /api/templates/:id` — tunnels created from it keep their
+/// settings.
+pub async fn delete_template(
+    State(templates): State<SharedTemplates>,
+    Path(id): Path<String>,
+) -> ApiResult<StatusCode> {
+    builtin_guard(&id)?;
+    let mut user = templates.user.write().await;
+    let index = user
+        .iter()
+        .position(|t| t.id == id)
+        .ok_or_else(|| not_found(&id))?;
+    user.remove(index);
+    if let Err(e) = templates.save(&user).await {
+        error!("Persistence failed: {e}");
+    }
+    info!(template.id = %id, "Template deleted");
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn template(id: &str, name: &str) -> Template {
+        Template {
+            id: id.to_string(),
+            name: name.to_string(),
+            service_type: Some(ServiceType::Postgres),
+            target_host: "db.internal".to_string(),
+            target_port: 5432,
+            local_port: None,
+            health_check: HealthCheck::Tcp,
+            connection_format: None,
+            description_key: None,
+            description: None,
+            builtin: false,
+        }
+    }
+
+    fn error_id<T: std::fmt::Debug>(result: ApiResult<T>) -> (StatusCode, String) {
+        let (status, Json(body)) = result.unwrap_err();
+        (status, body.error.id)
+    }
+
+    fn temp_path() -> String {
+        std::env::temp_dir()
+            .join(format!("templates-{}.json", Uuid::new_v4()))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    async fn store() -> SharedTemplates {
+        ports::init(8080);
+        Arc::new(Templates::load_from(temp_path()).await)
+    }
+
+    fn create_request(value: serde_json::Value) -> Json<CreateTemplateRequest> {
+        Json(serde_json::from_value(value).unwrap())
+    }
+
+    fn update_request(value: serde_json::Value) -> Json<UpdateTemplateRequest> {
+        Json(serde_json::from_value(value).unwrap())
+    }
+
+    #[test]
+    fn validate_requires_name_host_and_target_port() {
+        let policy = PortPolicy::new(vec![1024..=65535], [], 8080);
+        assert!(validate(&template("t", "Main DB"), &policy).is_ok());
+
+        let empty_name = template("t", "");
+        assert_eq!(
+            error_id(validate(&empty_name, &policy)),
+            (StatusCode::BAD_REQUEST, "api.error.name_empty".to_string())
+        );
+        let empty_host = Template {
+            target_host: String::new(),
+            ..template("t", "Main DB")
+        };
+        assert_eq!(
+            error_id(validate(&empty_host, &policy)).1,
+            "api.error.target_host_empty"
+        );
+        let no_port = Template {
+            target_port: 0,
+            ..template("t", "Main DB")
+        };
+        assert_eq!(
+            error_id(validate(&no_port, &policy)).1,
+            "api.error.target_port_range"
+        );
+    }
+
+    #[test]
+    fn validate_checks_the_local_port_against_the_policy() {
+        let policy = PortPolicy::new(vec![10000..=10999], [10500], 8080);
+        let with_port = |port| Template {
+            local_port: Some(port),
+            ..template("t", "Main DB")
+        };
+        assert!(validate(&with_port(10000), &policy).is_ok());
+        assert!(validate(&with_port(10999), &policy).is_ok());
+        assert_eq!(
+            error_id(validate(&with_port(11000), &policy)).1,
+            "api.error.port_not_allowed"
+        );
+        assert_eq!(
+            error_id(validate(&with_port(10500), &policy)).1,
+            "api.error.port_reserved"
+        );
+        assert_eq!(
+            error_id(validate(&with_port(8080), &policy)).1,
+            "api.error.port_api"
+        );
+        assert_eq!(
+            error_id(validate(&with_port(80), &policy)).1,
+            "api.error.port_privileged"
+        );
+    }
+
+    #[test]
+    fn duplicate_and_builtin_ids_are_dropped() {
+        let kept = unique(vec![
+            template("a", "First"),
+            template("postgres", "Shadows the built-in"),
+            template("b", "Second"),
+            template("a", "Repeats the first"),
+        ]);
+        let names: Vec<&str> = kept.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["First", "Second"]);
+    }
+
+    #[tokio::test]
+    async fn load_drops_duplicates_from_the_file() {
+        let path = temp_path();
+        let file = vec![template("a", "First"), template("a", "Again")];
+        tokio::fs::write(&path, serde_json::to_string(&file).unwrap())
+            .await
+            .unwrap();
+        let templates = Templates::load_from(path.clone()).await;
+        assert_eq!(templates.user.read().await.len(), 1);
+        assert_eq!(templates.get("a").await.unwrap().name, "First");
+        let _ = tokio::fs::remove_file(&path).await;
+    }
+
+    #[tokio::test]
+    async fn unreadable_files_start_empty() {
+        let missing = Templates::load_from(temp_path()).await;
+        assert!(missing.user.read().await.is_empty());
+
+        let path = temp_path();
+        tokio::fs::write(&path, "not json").await.unwrap();
+        let broken = Templates::load_from(path.clone()).await;
+        assert!(broken.user.read().await.is_empty());
+        let _ = tokio::fs::remove_file(&path).await;
+    }
+
+    #[tokio::test]
+    async fn created_templates_persist_across_loads() {
+        let templates = store().await;
+        let (status, Json(created)) = create_template(
+            State(templates.clone()),
+            create_request(json!({
+                "name": "  Analytics  ",
+                "target_host": "warehouse",
+                "target_port": 5432,
+                "local_port": 15432,
+                "service_type": "postgres",
+                "connection_format": " ",
+                "description": "Read replica",
+            })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(created.name, "Analytics");
+        assert_eq!(created.connection_format, None);
+        assert!(!created.builtin);
+
+        let reloaded = Templates::load_from(templates.path.clone()).await;
+        let saved = reloaded.get(&created.id).await.unwrap();
+        assert_eq!(saved.name, "Analytics");
+        assert_eq!(saved.target_host, "warehouse");
+        assert_eq!(saved.local_port, Some(15432));
+        assert_eq!(saved.service_type, Some(ServiceType::Postgres));
+        assert_eq!(saved.description.as_deref(), Some("Read replica"));
+        let _ = tokio::fs::remove_file(&templates.path).await;
+    }
+
+    #[tokio::test]
+    async fn create_rejects_invalid_templates() {
+        let templates = store().await;
+        let result = create_template(
+            State(templates.clone()),
+            create_request(json!({ "name": "x", "target_host": "h", "target_port": 0 })),
+        )
+        .await;
+        assert_eq!(error_id(result).1, "api.error.target_port_range");
+        let result = create_template(
+            State(templates.clone()),
+            create_request(
+                json!({ "name": "x", "target_host": "h", "target_port": 1, "local_port": 22 }),
+            ),
+        )
+        .await;
+        assert_eq!(error_id(result).1, "api.error.port_privileged");
+        assert!(templates.user.read().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn update_changes_given_fields_and_persists() {
+        let templates = store().await;
+        let (_, Json(created)) = create_template(
+            State(templates.clone()),
+            create_request(json!({
+                "name": "Cache",
+                "target_host": "redis",
+                "target_port": 6379,
+                "local_port": 16379,
+                "description": "Sessions",
+            })),
+        )
+        .await
+        .unwrap();
+
+        let Json(updated) = update_template(
+            State(templates.clone()),
+            Path(created.id.clone()),
+            update_request(json!({ "target_port": 6380, "local_port": 0, "description": "" })),
+        )
+        .await
+        .unwrap();
+        assert_eq!(updated.name, "Cache");
+        assert_eq!(updated.target_port, 6380);
+        assert_eq!(updated.local_port, None);
+        assert_eq!(updated.description, None);
+
+        let reloaded = Templates::load_from(templates.path.clone()).await;
+        assert_eq!(reloaded.get(&created.id).await.unwrap().target_port, 6380);
+
+        // An invalid update leaves the template unchanged.
+        let result = update_template(
+            State(templates.clone()),
+            Path(created.id.clone()),
+            update_request(json!({ "name": " " })),
+        )
+        .await;
+        assert_eq!(error_id(result).1, "api.error.name_empty");
+        assert_eq!(templates.get(&created.id).await.unwrap().name, "Cache");
+        let _ = tokio::fs::remove_file(&templates.path).await;
+    }
+
+    #[tokio::test]
+    async fn delete_removes_and_persists() {
+        let templates = store().await;
+        let (_, Json(created)) = create_template(
+            State(templates.clone()),
+            create_request(json!({ "name": "Tmp", "target_host": "h", "target_port": 1 })),
+        )
+        .await
+        .unwrap();
+        let status = delete_template(State(templates.clone()), Path(created.id.clone()))
+            .await
+            .unwrap();
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert!(templates.get(&created.id).await.is_none());
+        let reloaded = Templates::load_from(templates.path.clone()).await;
+        assert!(reloaded.user.read().await.is_empty());
+
+        let result = delete_template(State(templates.clone()), Path(created.id)).await;
+        assert_eq!(error_id(result).0, StatusCode::NOT_FOUND);
+        let _ = tokio::fs::remove_file(&templates.path).await;
+    }
+
+    #[tokio::test]
+    async fn builtin_templates_are_read_only() {
+        let templates = store().await;
+        let result = update_template(
+            State(templates.clone()),
+            Path("postgres".to_string()),
+            update_request(json!({ "name": "Mine" })),
+        )
+        .await;
+        assert_eq!(
+            error_id(result),
+            (
+                StatusCode::FORBIDDEN,
+                "api.error.template_builtin".to_string()
+            )
+        );
+        let result = delete_template(State(templates.clone()), Path("redis".to_string())).await;
+        assert_eq!(error_id(result).0, StatusCode::FORBIDDEN);
+
+        let Json(all) = list_templates(State(templates)).await;
+        let ids: Vec<&str> = all.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, ["postgres", "mysql", "redis", "mongodb"]);
+        assert!(all.iter().all(|t| t.builtin));
+    }
+}
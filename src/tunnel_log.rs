@@ -0,0 +1,329 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{self, Write};
+use std::sync::{Arc, LazyLock, Mutex, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+use crate::limits::env_u64;
+
+/// One line of a tunnel's log.
+#[derive(Debug, Clone, Serialize)]
+pub struct LogEntry {
+    /// Milliseconds since the unix epoch.
+    pub timestamp: u64,
+    pub level: String,
+    pub message: String,
+}
+
+/// In-memory ring buffer of recent log lines for one tunnel, plus a
+/// broadcast channel for followers.
+#[derive(Debug)]
+pub struct TunnelLog {
+    entries: Mutex<VecDeque<LogEntry>>,
+    capacity: usize,
+    tx: broadcast::Sender<LogEntry>,
+}
+
+impl TunnelLog {
+    fn new(capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(64);
+        Self {
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+            tx,
+        }
+    }
+
+    fn push(&self, entry: LogEntry) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(entry.clone());
+        // No receivers is the normal case.
+        let _ = self.tx.send(entry);
+    }
+
+    /// The last `lines` entries (all when `None`) and a receiver for every
+    /// entry pushed afterwards, taken atomically so nothing is missed.
+    pub fn snapshot(&self, lines: Option<usize>) -> (Vec<LogEntry>, broadcast::Receiver<LogEntry>) {
+        let entries = self.entries.lock().unwrap();
+        let skip = lines.map_or(0, |n| entries.len().saturating_sub(n));
+        (
+            entries.iter().skip(skip).cloned().collect(),
+            self.tx.subscribe(),
+        )
+    }
+}
+
+// ─── Registry ────────────────────────────────────────────────────────────
+
+static LOGS: LazyLock<RwLock<HashMap<String, Arc<TunnelLog>>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// Lines kept per tunnel, from `TUNNEL_LOG_LINES` (default 200).
+fn capacity() -> usize {
+    static CAPACITY: LazyLock<usize> =
+        LazyLock::new(|| env_u64("TUNNEL_LOG_LINES", 200).max(1) as usize);
+    *CAPACITY
+}
+
+/// Start collecting log lines for tunnel `id`.  Events of tunnels that
+/// are not registered are not buffered.
+pub fn register(id: &str) {
+    LOGS.write()
+        .unwrap()
+        .entry(id.to_string())
+        .or_insert_with(|| Arc::new(TunnelLog::new(capacity())));
+}
+
+/// Drop the buffer of tunnel `id`.  Followers see their stream end.
+pub fn remove(id: &str) {
+    LOGS.write().unwrap().remove(id);
+}
+
+pub fn get(id: &str) -> Option<Arc<TunnelLog>> {
+    LOGS.read().unwrap().get(id).cloned()
+}
+
+// ─── Tracing layer ───────────────────────────────────────────────────────
+
+/// Tunnel id attached to a span, either at creation (`tunnel` spans) or
+/// later through `Span::record` (request spans).
+struct TunnelId(String);
+
+#[derive(Default)]
+struct TunnelIdVisitor(Option<String>);
+
+impl Visit for TunnelIdVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "tunnel.id" {
+            self.0 = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "tunnel.id" {
+            self.0 = Some(format!("{value:?}"));
+        }
+    }
+}
+
+/// Renders an event as its message followed by any extra fields.
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            let _ = write!(self.fields, " {}={value}", field.name());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{value:?}");
+        } else {
+            let _ = write!(self.fields, " {}={value:?}", field.name());
+        }
+    }
+}
+
+/// Copies every event emitted inside a span carrying `tunnel.id` into that
+/// tunnel's ring buffer.
+pub struct TunnelLogLayer;
+
+impl TunnelLogLayer {
+    fn remember<S>(attrs: impl FnOnce(&mut TunnelIdVisitor), id: &Id, ctx: &Context<'_, S>)
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        let mut visitor = TunnelIdVisitor::default();
+        attrs(&mut visitor);
+        if let (Some(tunnel_id), Some(span)) = (visitor.0, ctx.span(id)) {
+            span.extensions_mut().replace(TunnelId(tunnel_id));
+        }
+    }
+}
+
+impl<S> Layer<S> for TunnelLogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        Self::remember(|v| attrs.record(v), id, &ctx);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        Self::remember(|v| values.record(v), id, &ctx);
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let Some(scope) = ctx.event_scope(event) else {
+            return;
+        };
+        let Some(tunnel_id) = scope
+            .into_iter()
+            .find_map(|span| span.extensions().get::<TunnelId>().map(|t| t.0.clone()))
+        else {
+            return;
+        };
+        let Some(log) = get(&tunnel_id) else {
+            return;
+        };
+
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        log.push(LogEntry {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            level: event.metadata().level().to_string(),
+            message: visitor.message + &visitor.fields,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logging::{record_tunnel, tunnel_span};
+    use tracing::field::Empty;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::Registry;
+
+    /// Run `f` with only [`TunnelLogLayer`] installed, on this thread.
+    fn with_layer(f: impl FnOnce()) {
+        tracing::subscriber::with_default(Registry::default().with(TunnelLogLayer), f);
+    }
+
+    /// A registered tunnel id no other test uses.
+    fn tunnel() -> String {
+        let id = uuid::Uuid::new_v4().to_string();
+        register(&id);
+        id
+    }
+
+    fn messages(id: &str) -> Vec<String> {
+        let (entries, _) = get(id).unwrap().snapshot(None);
+        entries.into_iter().map(|e| e.message).collect()
+    }
+
+    #[test]
+    fn events_in_a_tunnel_span_are_buffered() {
+        let id = tunnel();
+        with_layer(|| {
+            tracing::info!("before the span");
+            let span = tunnel_span(&id, "db", 15432);
+            let _enter = span.enter();
+            tracing::info!("Forwarder listening");
+            tracing::warn!(peer = "100.64.0.9", "Connection refused");
+            // Child spans inherit the tunnel.
+            tracing::info_span!("connection").in_scope(|| tracing::debug!("copied 12 bytes"));
+        });
+
+        let (entries, _) = get(&id).unwrap().snapshot(None);
+        let lines: Vec<(&str, &str)> = entries
+            .iter()
+            .map(|e| (e.level.as_str(), e.message.as_str()))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                ("INFO", "Forwarder listening"),
+                ("WARN", "Connection refused peer=100.64.0.9"),
+                ("DEBUG", "copied 12 bytes"),
+            ]
+        );
+        assert!(entries.iter().all(|e| e.timestamp > 0));
+        remove(&id);
+    }
+
+    #[test]
+    fn tunnel_recorded_on_a_request_span_is_followed() {
+        let id = tunnel();
+        with_layer(|| {
+            let span = tracing::info_span!(
+                "request",
+                tunnel.id = Empty,
+                tunnel.name = Empty,
+                tunnel.port = Empty
+            );
+            let _enter = span.enter();
+            tracing::info!("not yet attributed");
+            record_tunnel(&id, "db", 15432);
+            tracing::info!("Tunnel updated");
+        });
+        assert_eq!(messages(&id), ["Tunnel updated"]);
+        remove(&id);
+    }
+
+    #[test]
+    fn unregistered_and_removed_tunnels_are_not_buffered() {
+        let unregistered = uuid::Uuid::new_v4().to_string();
+        let id = tunnel();
+        with_layer(|| {
+            tunnel_span(&unregistered, "x", 1).in_scope(|| tracing::info!("dropped"));
+            tunnel_span(&id, "db", 15432).in_scope(|| tracing::info!("kept"));
+        });
+        assert!(get(&unregistered).is_none());
+        assert_eq!(messages(&id), ["kept"]);
+
+        remove(&id);
+        assert!(get(&id).is_none());
+    }
+
+    #[test]
+    fn buffer_keeps_the_newest_lines_up_to_its_capacity() {
+        let id = tunnel();
+        let total = capacity() + 5;
+        with_layer(|| {
+            let span = tunnel_span(&id, "db", 15432);
+            let _enter = span.enter();
+            for i in 0..total {
+                tracing::info!("line {i}");
+            }
+        });
+
+        let lines = messages(&id);
+        assert_eq!(lines.len(), capacity());
+        assert_eq!(lines[0], "line 5");
+        assert_eq!(lines.last().unwrap(), &format!("line {}", total - 1));
+
+        let (last_two, _) = get(&id).unwrap().snapshot(Some(2));
+        let last_two: Vec<String> = last_two.into_iter().map(|e| e.message).collect();
+        assert_eq!(
+            last_two,
+            [format!("line {}", total - 2), format!("line {}", total - 1)]
+        );
+        remove(&id);
+    }
+
+    #[test]
+    fn followers_receive_lines_after_the_snapshot() {
+        let id = tunnel();
+        let log = get(&id).unwrap();
+        with_layer(|| tunnel_span(&id, "db", 15432).in_scope(|| tracing::info!("old")));
+        let (entries, mut rx) = log.snapshot(None);
+        assert_eq!(entries.len(), 1);
+
+        with_layer(|| tunnel_span(&id, "db", 15432).in_scope(|| tracing::info!("new")));
+        assert_eq!(rx.try_recv().unwrap().message, "new");
+        assert!(rx.try_recv().is_err());
+        remove(&id);
+    }
+}
@@ -0,0 +1,1081 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use tokio::sync::{broadcast, RwLock};
+use tokio::time::Duration;
+use tracing::{error, info, info_span, warn, Instrument};
+use uuid::Uuid;
+
+use crate::config::settings;
+use crate::discord::{self, BatchEntry};
+use crate::events::{self, ApiEvent};
+use crate::i18n;
+use crate::limits::env_u64;
+use crate::models::{
+    ApiErrorResponse, CreateWebhookRequest, Health, Tunnel, UpdateWebhookRequest, Webhook,
+    WebhookDelivery, WebhookEvent, WebhookKind, WebhookResponse,
+};
+use crate::routes::{api_err, api_err_params, params1, ApiResult};
+use crate::state::SharedState;
+
+/// Deliveries kept in memory for `GET /api/webhooks/:id/deliveries`.
+const DELIVERY_LOG_SIZE: usize = 200;
+
+/// Webhook endpoints, their delivery log and the HTTP client used to
+/// call them.
+#[derive(Debug)]
+pub struct Webhooks {
+    hooks: RwLock<Vec<Webhook>>,
+    deliveries: Mutex<VecDeque<WebhookDelivery>>,
+    /// Discord notifications waiting for their batch to be sent, by webhook.
+    batches: Mutex<HashMap<String, Vec<BatchEntry>>>,
+    client: reqwest::Client,
+    max_attempts: u32,
+    batch_window: Duration,
+    locale: String,
+}
+
+pub type SharedWebhooks = Arc<Webhooks>;
+
+fn webhooks_path() -> String {
+    settings().webhooks_path.to_string_lossy().into_owned()
+}
+
+impl Webhooks {
+    /// Load endpoints from `WEBHOOKS_PATH` and read the delivery settings:
+    /// - `WEBHOOK_MAX_ATTEMPTS` — attempts per delivery (default 5)
+    /// - `WEBHOOK_TIMEOUT_SECONDS` — per-attempt timeout (default 10)
+    /// - `DISCORD_BATCH_SECONDS` — Discord messages per webhook are sent at
+    ///   most this often, batching the events in between (default 10)
+    /// - `NOTIFY_LOCALE` — language of Discord messages (default `en`)
+    pub async fn load() -> Self {
+        let path = webhooks_path();
+        let hooks: Vec<Webhook> = match tokio::fs::read_to_string(&path).await {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                warn!("Could not parse {path}: {e} — starting without webhooks");
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+        if !hooks.is_empty() {
+            info!("Loaded {} webhook(s) from {path}", hooks.len());
+        }
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(
+                env_u64("WEBHOOK_TIMEOUT_SECONDS", 10).max(1),
+            ))
+            .user_agent(concat!(
+                "tailscale-tunnel-manager/",
+                env!("CARGO_PKG_VERSION")
+            ))
+            .build()
+            .expect("Failed to build HTTP client");
+
+        let locale = std::env::var("NOTIFY_LOCALE")
+            .ok()
+            .map(|l| l.trim().to_string())
+            .filter(|l| {
+                let known = i18n::catalog(l).is_some();
+                if !known {
+                    warn!(
+                        "Unknown NOTIFY_LOCALE '{l}' — using {}",
+                        i18n::FALLBACK_LOCALE
+                    );
+                }
+                known
+            })
+            .unwrap_or_else(|| i18n::FALLBACK_LOCALE.to_string());
+
+        Self {
+            hooks: RwLock::new(hooks),
+            deliveries: Mutex::new(VecDeque::with_capacity(DELIVERY_LOG_SIZE)),
+            batches: Mutex::new(HashMap::new()),
+            client,
+            max_attempts: env_u64("WEBHOOK_MAX_ATTEMPTS", 5).clamp(1, 20) as u32,
+            batch_window: Duration::from_secs(env_u64("DISCORD_BATCH_SECONDS", 10)),
+            locale,
+        }
+    }
+
+    fn record(&self, delivery: WebhookDelivery) {
+        let mut deliveries = self.deliveries.lock().unwrap();
+        if deliveries.len() == DELIVERY_LOG_SIZE {
+            deliveries.pop_front();
+        }
+        deliveries.push_back(delivery);
+    }
+
+    fn locale_of<'a>(&'a self, hook: &'a Webhook) -> &'a str {
+        hook.locale.as_deref().unwrap_or(&self.locale)
+    }
+}
+
+async fn save_webhooks(hooks: &[Webhook]) -> Result<(), String> {
+    let path = webhooks_path();
+    let json = serde_json::to_string_pretty(hooks)
+        .map_err(|e| format!("JSON serialization error: {e}"))?;
+    tokio::fs::write(&path, json).await.map_err(|e| {
+        let msg = format!("Failed to write {path}: {e}");
+        error!("{msg}");
+        msg
+    })
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// ─── Payload and signing ─────────────────────────────────────────────────
+
+/// The tunnel an event is about.
+#[derive(Debug, Clone, Serialize)]
+pub struct TunnelRef {
+    pub id: String,
+    pub name: String,
+    pub local_port: u16,
+    pub target_host: String,
+    pub target_port: u16,
+}
+
+impl From<&Tunnel> for TunnelRef {
+    fn from(t: &Tunnel) -> Self {
+        Self {
+            id: t.id.clone(),
+            name: t.name.clone(),
+            local_port: t.local_port,
+            target_host: t.target_host.clone(),
+            target_port: t.target_port,
+        }
+    }
+}
+
+/// JSON body POSTed to webhook endpoints.
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookPayload {
+    /// Delivery id, also sent as `X-Webhook-Delivery`.
+    pub id: String,
+    pub event: WebhookEvent,
+    /// Unix seconds when the event happened.
+    pub timestamp: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tunnel: Option<TunnelRef>,
+    /// Why the tunnel went down, when known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+/// `sha256=<hex HMAC-SHA256 of body>`, the `X-Webhook-Signature-256` value.
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Wait before attempt `attempt + 1`: 1s, 2s, 4s… capped at one minute.
+fn backoff(attempt: u32) -> Duration {
+    Duration::from_secs(1u64 << (attempt - 1).min(6)).min(Duration::from_secs(60))
+}
+
+/// Longest `Retry-After` honoured; endpoints asking for more are retried
+/// after this long instead.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(300);
+
+/// The wait a `429` asks for: Discord's `retry_after` for Discord
+/// webhooks, else the `Retry-After` header.
+async fn requested_wait(hook: &Webhook, response: reqwest::Response) -> Option<Duration> {
+    let header = retry_after(response.headers());
+    match hook.kind {
+        WebhookKind::Discord => {
+            let body = response.bytes().await.unwrap_or_default();
+            discord::retry_after(&body)
+                .map(|wait| wait.min(MAX_RETRY_AFTER))
+                .or(header)
+        }
+        WebhookKind::Generic => header,
+    }
+}
+
+/// The wait in a `Retry-After` header, in the delay-seconds form.
+fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let secs: u64 = headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(Duration::from_secs(secs).min(MAX_RETRY_AFTER))
+}
+
+// ─── Delivery ────────────────────────────────────────────────────────────
+
+/// POST `body` to `hook`, retrying network errors, `429` and `5xx`
+/// up to `max_attempts` times — after the wait a `429` asks for, else
+/// after [`backoff`].  The outcome is added to the delivery log.
+async fn deliver(
+    webhooks: &Webhooks,
+    hook: &Webhook,
+    id: String,
+    events: Vec<WebhookEvent>,
+    body: Vec<u8>,
+    max_attempts: u32,
+) -> WebhookDelivery {
+    let signature = sign(&hook.secret, &body);
+    let event_names: Vec<&str> = events.iter().map(|e| e.as_str()).collect();
+    let event_header = event_names.join(",");
+
+    let mut delivery = WebhookDelivery {
+        id,
+        webhook_id: hook.id.clone(),
+        events,
+        timestamp: unix_now(),
+        attempts: 0,
+        success: false,
+        status: None,
+        error: None,
+    };
+
+    let mut wait = None;
+    while delivery.attempts < max_attempts {
+        if delivery.attempts > 0 {
+            tokio::time::sleep(wait.take().unwrap_or_else(|| backoff(delivery.attempts))).await;
+        }
+        delivery.attempts += 1;
+
+        let result = webhooks
+            .client
+            .post(&hook.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("X-Webhook-Event", &event_header)
+            .header("X-Webhook-Delivery", &delivery.id)
+            .header("X-Webhook-Signature-256", &signature)
+            .body(body.clone())
+            .send()
+            .await;
+
+        let retryable = match result {
+            Ok(response) if response.status().is_success() => {
+                delivery.success = true;
+                delivery.status = Some(response.status().as_u16());
+                delivery.error = None;
+                break;
+            }
+            Ok(response) => {
+                let status = response.status();
+                delivery.status = Some(status.as_u16());
+                delivery.error = Some(format!("HTTP {status}"));
+                let too_many = status == reqwest::StatusCode::TOO_MANY_REQUESTS;
+                if too_many {
+                    wait = requested_wait(hook, response).await;
+                }
+                status.is_server_error() || too_many
+            }
+            Err(e) => {
+                delivery.status = None;
+                delivery.error = Some(e.to_string());
+                true
+            }
+        };
+        warn!(
+            "Attempt {}/{max_attempts} failed: {}",
+            delivery.attempts,
+            delivery.error.as_deref().unwrap_or_default()
+        );
+        if !retryable {
+            break;
+        }
+    }
+
+    if delivery.success {
+        info!("Delivered after {} attempt(s)", delivery.attempts);
+    } else {
+        error!("Giving up after {} attempt(s)", delivery.attempts);
+    }
+    webhooks.record(delivery.clone());
+    delivery
+}
+
+/// Send `payload` to a generic webhook as signed JSON.
+async fn deliver_event(
+    webhooks: &Webhooks,
+    hook: &Webhook,
+    payload: &WebhookPayload,
+    max_attempts: u32,
+) -> WebhookDelivery {
+    let body = serde_json::to_vec(payload).unwrap_or_default();
+    deliver(
+        webhooks,
+        hook,
+        payload.id.clone(),
+        vec![payload.event],
+        body,
+        max_attempts,
+    )
+    .await
+}
+
+/// Send `entries` to a Discord webhook as one message.
+async fn deliver_discord(
+    webhooks: &Webhooks,
+    hook: &Webhook,
+    entries: &[BatchEntry],
+    max_attempts: u32,
+) -> WebhookDelivery {
+    let message = discord::message(webhooks.locale_of(hook), entries, webhooks.batch_window);
+    let body = serde_json::to_vec(&message).unwrap_or_default();
+    let events = entries.iter().map(|e| e.payload.event).collect();
+    deliver(
+        webhooks,
+        hook,
+        Uuid::new_v4().to_string(),
+        events,
+        body,
+        max_attempts,
+    )
+    .await
+}
+
+/// Queue `payload` for a Discord webhook.  The first event of a batch
+/// schedules its delivery one batch window later.
+fn enqueue_discord(webhooks: &SharedWebhooks, hook_id: &str, payload: WebhookPayload) {
+    let mut batches = webhooks.batches.lock().unwrap();
+    let batch = batches.entry(hook_id.to_string()).or_default();
+    let first = batch.is_empty();
+    discord::add_to_batch(batch, payload);
+    if !first {
+        return;
+    }
+
+    let webhooks = webhooks.clone();
+    let hook_id = hook_id.to_string();
+    let span = info_span!("webhook", webhook.id = %hook_id, event = "batch");
+    tokio::spawn(
+        async move {
+            tokio::time::sleep(webhooks.batch_window).await;
+            let entries = webhooks
+                .batches
+                .lock()
+                .unwrap()
+                .remove(&hook_id)
+                .unwrap_or_default();
+            // The webhook may have been removed or disabled meanwhile.
+            let hook = webhooks
+                .hooks
+                .read()
+                .await
+                .iter()
+                .find(|h| h.id == hook_id && h.enabled)
+                .cloned();
+            if let (Some(hook), false) = (hook, entries.is_empty()) {
+                deliver_discord(&webhooks, &hook, &entries, webhooks.max_attempts).await;
+            }
+        }
+        .instrument(span),
+    );
+}
+
+/// Map a live event to the webhook event it triggers, if any.
+fn webhook_event(event: &ApiEvent) -> Option<(WebhookEvent, Option<String>)> {
+    match event {
+        ApiEvent::TunnelCreated { .. } => Some((WebhookEvent::TunnelCreated, None)),
+        ApiEvent::TunnelDeleted { .. } => Some((WebhookEvent::TunnelDeleted, None)),
+        ApiEvent::StatusChanged { running: true, .. } => Some((WebhookEvent::TunnelUp, None)),
+        ApiEvent::StatusChanged {
+            running: false,
+            error: Some(error),
+            ..
+        } => Some((WebhookEvent::TunnelDown, Some(error.clone()))),
+        ApiEvent::HealthChanged { health, error, .. } => match health {
+            Health::Healthy => Some((WebhookEvent::TunnelUp, None)),
+            Health::Degraded => Some((WebhookEvent::HealthDegraded, error.clone())),
+            Health::Down => Some((WebhookEvent::TunnelDown, error.clone())),
+        },
+        _ => None,
+    }
+}
+
+/// Start the task that turns live events into webhook deliveries.  Call
+/// before tunnels are restored so restore failures are reported too.
+pub fn spawn_dispatcher(webhooks: SharedWebhooks, state: SharedState) {
+    let mut rx = events::subscribe();
+    tokio::spawn(async move {
+        // Deleted tunnels are gone from the state by the time their event
+        // arrives, so keep what the payload needs.
+        let mut known: HashMap<String, TunnelRef> = state
+            .read()
+            .await
+            .iter()
+            .map(|t| (t.id.clone(), TunnelRef::from(t)))
+            .collect();
+        // Health only turns "up" again after it was degraded or down.
+        let mut unhealthy: HashMap<String, bool> = HashMap::new();
+
+        loop {
+            let event = match rx.recv().await {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!("Webhook dispatcher fell behind, {n} event(s) not delivered");
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+
+            let id = match &event {
+                ApiEvent::TunnelCreated { tunnel } | ApiEvent::TunnelUpdated { tunnel } => {
+                    known.insert(tunnel.tunnel.id.clone(), TunnelRef::from(&tunnel.tunnel));
+                    tunnel.tunnel.id.clone()
+                }
+                ApiEvent::TunnelDeleted { id }
+                | ApiEvent::StatusChanged { id, .. }
+                | ApiEvent::HealthChanged { id, .. }
+                | ApiEvent::Connections { id, .. } => id.clone(),
+            };
+            let Some((kind, detail)) = webhook_event(&event) else {
+                continue;
+            };
+            if let ApiEvent::HealthChanged { health, .. } = &event {
+                let was_unhealthy = unhealthy.insert(id.clone(), *health != Health::Healthy);
+                if *health == Health::Healthy && was_unhealthy != Some(true) {
+                    continue;
+                }
+            }
+            let tunnel = if kind == WebhookEvent::TunnelDeleted {
+                unhealthy.remove(&id);
+                known.remove(&id)
+            } else {
+                known.get(&id).cloned()
+            };
+
+            let payload = WebhookPayload {
+                id: String::new(),
+                event: kind,
+                timestamp: unix_now(),
+                tunnel,
+                detail,
+            };
+            let hooks: Vec<Webhook> = webhooks
+                .hooks
+                .read()
+                .await
+                .iter()
+                .filter(|h| h.enabled && h.events.contains(&kind) && !h.muted_tunnels.contains(&id))
+                .cloned()
+                .collect();
+            for hook in hooks {
+                let payload = WebhookPayload {
+                    id: Uuid::new_v4().to_string(),
+                    ..payload.clone()
+                };
+                if hook.kind == WebhookKind::Discord {
+                    enqueue_discord(&webhooks, &hook.id, payload);
+                    continue;
+                }
+                let webhooks = webhooks.clone();
+                let span = info_span!("webhook", webhook.id = %hook.id, event = kind.as_str());
+                tokio::spawn(
+                    async move {
+                        let max_attempts = webhooks.max_attempts;
+                        deliver_event(&webhooks, &hook, &payload, max_attempts).await;
+                    }
+                    .instrument(span),
+                );
+            }
+        }
+    });
+}
+
+// ─── Handlers ────────────────────────────────────────────────────────────
+
+fn view(hook: &Webhook, with_secret: bool) -> WebhookResponse {
+    WebhookResponse {
+        id: hook.id.clone(),
+        url: hook.url.clone(),
+        kind: hook.kind,
+        events: hook.events.clone(),
+        enabled: hook.enabled,
+        locale: hook.locale.clone(),
+        muted_tunnels: hook.muted_tunnels.clone(),
+        secret: with_secret.then(|| hook.secret.clone()),
+    }
+}
+
+fn not_found(id: &str) -> (StatusCode, Json<ApiErrorResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(api_err_params(
+            "api.error.webhook_not_found",
+            params1("id", id.to_string()),
+        )),
+    )
+}
+
+fn validate_url(url: &str) -> ApiResult<String> {
+    let url = url.trim();
+    match reqwest::Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => Ok(url.to_string()),
+        _ => Err((
+            StatusCode::BAD_REQUEST,
+            Json(api_err_params(
+                "api.error.invalid_webhook_url",
+                params1("url", url.to_string()),
+            )),
+        )),
+    }
+}
+
+fn parse_events(names: &[String]) -> ApiResult<Vec<WebhookEvent>> {
+    if names.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(api_err("api.error.webhook_events_empty")),
+        ));
+    }
+    let mut events = Vec::new();
+    for name in names {
+        let event = name.parse::<WebhookEvent>().map_err(|_| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(api_err_params(
+                    "api.error.invalid_webhook_event",
+                    params1("event", name.clone()),
+                )),
+            )
+        })?;
+        if !events.contains(&event) {
+            events.push(event);
+        }
+    }
+    Ok(events)
+}
+
+/// `Ok(None)` for an empty locale, which means `NOTIFY_LOCALE`.
+fn validate_locale(locale: &str) -> ApiResult<Option<String>> {
+    let locale = locale.trim();
+    if locale.is_empty() {
+        return Ok(None);
+    }
+    if i18n::catalog(locale).is_none() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(api_err_params(
+                "api.error.invalid_locale",
+                params1("locale", locale.to_string()),
+            )),
+        ));
+    }
+    Ok(Some(locale.to_string()))
+}
+
+/// A fresh random signing secret.
+fn generate_secret() -> String {
+    Uuid::new_v4().simple().to_string()
+}
+
+fn clean_secret(secret: Option<String>) -> Option<String> {
+    secret
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+/// `GET /api/webhooks`
+pub async fn list_webhooks(State(webhooks): State<SharedWebhooks>) -> Json<Vec<WebhookResponse>> {
+    let hooks = webhooks.hooks.read().await;
+    Json(hooks.iter().map(|h| view(h, false)).collect())
+}
+
+/// `POST /api/webhooks` — the response carries the signing secret.
+pub async fn create_webhook(
+    State(webhooks): State<SharedWebhooks>,
+    Json(payload): Json<CreateWebhookRequest>,
+) -> ApiResult<(StatusCode, Json<WebhookResponse>)> {
+    let url = validate_url(&payload.url)?;
+    let events = match payload.events.as_deref() {
+        Some(names) => parse_events(names)?,
+        None => WebhookEvent::SUBSCRIBABLE.to_vec(),
+    };
+    let locale = match payload.locale.as_deref() {
+        Some(locale) => validate_locale(locale)?,
+        None => None,
+    };
+    let hook = Webhook {
+        id: Uuid::new_v4().to_string(),
+        url,
+        kind: payload.kind,
+        secret: clean_secret(payload.secret).unwrap_or_else(generate_secret),
+        events,
+        enabled: payload.enabled,
+        locale,
+        muted_tunnels: payload.muted_tunnels,
+    };
+
+    let mut hooks = webhooks.hooks.write().await;
+    hooks.push(hook.clone());
+    if let Err(e) = save_webhooks(&hooks).await {
+        error!("Persistence failed: {e}");
+    }
+    info!(webhook.id = %hook.id, "Webhook created");
+
+    Ok((StatusCode::CREATED, Json(view(&hook, true))))
+}
+
+/// `PUT /api/webhooks/:id` — the secret is returned only when replaced.
+pub async fn update_webhook(
+    State(webhooks): State<SharedWebhooks>,
+    Path(id): Path<String>,
+    Json(payload): Json<UpdateWebhookRequest>,
+) -> ApiResult<Json<WebhookResponse>> {
+    let url = payload.url.as_deref().map(validate_url).transpose()?;
+    let events = payload.events.as_deref().map(parse_events).transpose()?;
+    let locale = payload.locale.as_deref().map(validate_locale).transpose()?;
+    let secret = clean_secret(payload.secret);
+    let secret_changed = secret.is_some();
+
+    let mut hooks = webhooks.hooks.write().await;
+    let hook = hooks
+        .iter_mut()
+        .find(|h| h.id == id)
+        .ok_or_else(|| not_found(&id))?;
+    if let Some(url) = url {
+        hook.url = url;
+    }
+    if let Some(events) = events {
+        hook.events = events;
+    }
+    if let Some(secret) = secret {
+        hook.secret = secret;
+    }
+    if let Some(enabled) = payload.enabled {
+        hook.enabled = enabled;
+    }
+    if let Some(kind) = payload.kind {
+        hook.kind = kind;
+    }
+    if let Some(locale) = locale {
+        hook.locale = locale;
+    }
+    if let Some(muted) = payload.muted_tunnels {
+        hook.muted_tunnels = muted;
+    }
+    let response = view(hook, secret_changed);
+
+    if let Err(e) = save_webhooks(&hooks).await {
+        error!("Persistence failed: {e}");
+    }
+    info!(webhook.id = %id, "Webhook updated");
+
+    Ok(Json(response))
+}
+
+/// `DELETE /api/webhooks/:id`
+pub async fn delete_webhook(
+    State(webhooks): State<SharedWebhooks>,
+    Path(id): Path<String>,
+) -> ApiResult<StatusCode> {
+    let mut hooks = webhooks.hooks.write().await;
+    let index = hooks
+        .iter()
+        .position(|h| h.id == id)
+        .ok_or_else(|| not_found(&id))?;
+    hooks.remove(index);
+    if let Err(e) = save_webhooks(&hooks).await {
+        error!("Persistence failed: {e}");
+    }
+    info!(webhook.id = %id, "Webhook deleted");
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// `GET /api/webhooks/:id/deliveries` — most recent first.
+pub async fn list_deliveries(
+    State(webhooks): State<SharedWebhooks>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<Vec<WebhookDelivery>>> {
+    if !webhooks.hooks.read().await.iter().any(|h| h.id == id) {
+        return Err(not_found(&id));
+    }
+    let deliveries = webhooks.deliveries.lock().unwrap();
+    Ok(Json(
+        deliveries
+            .iter()
+            .rev()
+            .filter(|d| d.webhook_id == id)
+            .cloned()
+            .collect(),
+    ))
+}
+
+/// `POST /api/webhooks/:id/test` — send a `ping` once, without retries,
+/// and return the delivery.
+pub async fn test_webhook(
+    State(webhooks): State<SharedWebhooks>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<WebhookDelivery>> {
+    let hook = webhooks
+        .hooks
+        .read()
+        .await
+        .iter()
+        .find(|h| h.id == id)
+        .cloned()
+        .ok_or_else(|| not_found(&id))?;
+
+    let payload = WebhookPayload {
+        id: Uuid::new_v4().to_string(),
+        event: WebhookEvent::Ping,
+        timestamp: unix_now(),
+        tunnel: None,
+        detail: None,
+    };
+    let span = info_span!("webhook", webhook.id = %hook.id, event = "ping");
+    let delivery = match hook.kind {
+        WebhookKind::Generic => {
+            deliver_event(&webhooks, &hook, &payload, 1)
+                .instrument(span)
+                .await
+        }
+        WebhookKind::Discord => {
+            let entries = [BatchEntry {
+                payload,
+                changes: 1,
+            }];
+            deliver_discord(&webhooks, &hook, &entries, 1)
+                .instrument(span)
+                .await
+        }
+    };
+    Ok(Json(delivery))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Bytes, http::HeaderMap, response::IntoResponse, routing::post, Router};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use tokio::net::TcpListener;
+    use tokio::time::Instant;
+
+    /// A stand-in endpoint answering with `replies` in turn (the last one
+    /// repeats) and keeping every request it receives.
+    #[derive(Default)]
+    struct Endpoint {
+        replies: Vec<(u16, Option<&'static str>)>,
+        received: Mutex<Vec<(HeaderMap, Bytes)>>,
+    }
+
+    impl Endpoint {
+        fn received(&self) -> Vec<(HeaderMap, Bytes)> {
+            self.received.lock().unwrap().clone()
+        }
+    }
+
+    async fn serve(replies: Vec<(u16, Option<&'static str>)>) -> (String, Arc<Endpoint>) {
+        let endpoint = Arc::new(Endpoint {
+            replies,
+            ..Default::default()
+        });
+        let app = Router::new().route(
+            "/hook",
+            post(
+                |State(e): State<Arc<Endpoint>>, headers: HeaderMap, body: Bytes| async move {
+                    let mut received = e.received.lock().unwrap();
+                    let (status, retry_after) = e.replies[received.len().min(e.replies.len() - 1)];
+                    received.push((headers, body));
+                    let mut response = axum::response::Response::new(axum::body::Body::empty());
+                    *response.status_mut() = StatusCode::from_u16(status).unwrap();
+                    if let Some(secs) = retry_after {
+                        response
+                            .headers_mut()
+                            .insert(axum::http::header::RETRY_AFTER, secs.parse().unwrap());
+                    }
+                    response
+                },
+            ),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let app = app.with_state(endpoint.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (url, endpoint)
+    }
+
+    /// Without idle connections hyper keeps no pool timer, which the
+    /// paused clock would otherwise jump to while a request is in flight.
+    fn webhooks(max_attempts: u32) -> Webhooks {
+        Webhooks {
+            hooks: RwLock::new(Vec::new()),
+            deliveries: Mutex::new(VecDeque::new()),
+            batches: Mutex::new(HashMap::new()),
+            client: reqwest::Client::builder()
+                .pool_max_idle_per_host(0)
+                .build()
+                .unwrap(),
+            max_attempts,
+            batch_window: Duration::from_secs(10),
+            locale: i18n::FALLBACK_LOCALE.to_string(),
+        }
+    }
+
+    fn hook(url: &str) -> Webhook {
+        Webhook {
+            id: "hook-1".to_string(),
+            url: url.to_string(),
+            kind: WebhookKind::Generic,
+            secret: "s3cret".to_string(),
+            events: vec![WebhookEvent::TunnelDown],
+            enabled: true,
+            locale: None,
+            muted_tunnels: Vec::new(),
+        }
+    }
+
+    async fn send(webhooks: &Webhooks, hook: &Webhook, body: &[u8]) -> WebhookDelivery {
+        deliver(
+            webhooks,
+            hook,
+            "delivery-1".to_string(),
+            vec![WebhookEvent::TunnelDown],
+            body.to_vec(),
+            webhooks.max_attempts,
+        )
+        .await
+    }
+
+    fn logged(webhooks: &Webhooks) -> Vec<WebhookDelivery> {
+        webhooks
+            .deliveries
+            .lock()
+            .unwrap()
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn sign_is_hmac_sha256_hex() {
+        // RFC 4231, test case 2.
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_a_minute() {
+        let waits: Vec<u64> = (1..=8).map(|a| backoff(a).as_secs()).collect();
+        assert_eq!(waits, [1, 2, 4, 8, 16, 32, 60, 60]);
+    }
+
+    #[test]
+    fn retry_after_reads_seconds_and_caps_them() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+        headers.insert(reqwest::header::RETRY_AFTER, "7".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(7)));
+        headers.insert(reqwest::header::RETRY_AFTER, "86400".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(MAX_RETRY_AFTER));
+        headers.insert(
+            reqwest::header::RETRY_AFTER,
+            "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap(),
+        );
+        assert_eq!(retry_after(&headers), None);
+    }
+
+    #[tokio::test]
+    async fn delivery_is_signed_and_logged() {
+        let (url, endpoint) = serve(vec![(200, None)]).await;
+        let webhooks = webhooks(3);
+        let hook = hook(&url);
+        let body = br#"{"event":"tunnel.down"}"#;
+
+        let delivery = send(&webhooks, &hook, body).await;
+        assert!(delivery.success);
+        assert_eq!(delivery.attempts, 1);
+        assert_eq!(delivery.status, Some(200));
+
+        let received = endpoint.received();
+        assert_eq!(received.len(), 1);
+        let (headers, received_body) = &received[0];
+        assert_eq!(&received_body[..], body);
+        assert_eq!(
+            headers["x-webhook-signature-256"].to_str().unwrap(),
+            sign("s3cret", received_body)
+        );
+        assert_eq!(headers["x-webhook-event"], "tunnel.down");
+        assert_eq!(headers["x-webhook-delivery"], "delivery-1");
+        assert_eq!(headers["content-type"], "application/json");
+
+        let log = logged(&webhooks);
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].webhook_id, "hook-1");
+        assert!(log[0].success);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn server_errors_are_retried_up_to_max_attempts() {
+        let (url, endpoint) = serve(vec![(503, None)]).await;
+        let webhooks = webhooks(3);
+
+        let delivery = send(&webhooks, &hook(&url), b"{}").await;
+        assert!(!delivery.success);
+        assert_eq!(delivery.attempts, 3);
+        assert_eq!(delivery.status, Some(503));
+        assert_eq!(endpoint.received().len(), 3);
+
+        let log = logged(&webhooks);
+        assert_eq!(log.len(), 1);
+        assert!(!log[0].success);
+        assert_eq!(log[0].attempts, 3);
+        assert_eq!(
+            log[0].error.as_deref(),
+            Some("HTTP 503 Service Unavailable")
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_succeeds_after_a_server_error() {
+        let (url, endpoint) = serve(vec![(500, None), (204, None)]).await;
+        let webhooks = webhooks(5);
+
+        let delivery = send(&webhooks, &hook(&url), b"{}").await;
+        assert!(delivery.success);
+        assert_eq!(delivery.attempts, 2);
+        assert_eq!(delivery.status, Some(204));
+        assert_eq!(delivery.error, None);
+        assert_eq!(endpoint.received().len(), 2);
+        assert!(logged(&webhooks)[0].success);
+    }
+
+    #[tokio::test]
+    async fn client_errors_stop_after_one_attempt() {
+        let (url, endpoint) = serve(vec![(404, None), (200, None)]).await;
+        let webhooks = webhooks(5);
+
+        let delivery = send(&webhooks, &hook(&url), b"{}").await;
+        assert!(!delivery.success);
+        assert_eq!(delivery.attempts, 1);
+        assert_eq!(delivery.status, Some(404));
+        assert_eq!(endpoint.received().len(), 1);
+
+        let log = logged(&webhooks);
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].status, Some(404));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn too_many_requests_waits_for_retry_after() {
+        let (url, endpoint) = serve(vec![(429, Some("30")), (200, None)]).await;
+        let webhooks = webhooks(3);
+
+        let started = Instant::now();
+        let delivery = send(&webhooks, &hook(&url), b"{}").await;
+        assert!(delivery.success);
+        assert_eq!(delivery.attempts, 2);
+        assert_eq!(endpoint.received().len(), 2);
+        // Retry-After, not the one-second backoff of the first retry.
+        assert!(started.elapsed() >= Duration::from_secs(30));
+        assert!(started.elapsed() < Duration::from_secs(31));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn discord_rate_limits_wait_for_retry_after_in_the_body() {
+        let endpoint = Arc::new(AtomicU32::new(0));
+        let app = Router::new().route(
+            "/hook",
+            post(|State(calls): State<Arc<AtomicU32>>| async move {
+                match calls.fetch_add(1, Ordering::Relaxed) {
+                    0 => (
+                        StatusCode::TOO_MANY_REQUESTS,
+                        [(axum::http::header::RETRY_AFTER, "20")],
+                        r#"{"message": "You are being rate limited.", "retry_after": 12.5, "global": false}"#,
+                    )
+                        .into_response(),
+                    _ => StatusCode::NO_CONTENT.into_response(),
+                }
+            }),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let app = app.with_state(endpoint.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        let webhooks = webhooks(3);
+        let hook = Webhook {
+            kind: WebhookKind::Discord,
+            ..hook(&url)
+        };
+
+        let started = Instant::now();
+        let delivery = send(&webhooks, &hook, b"{}").await;
+        assert!(delivery.success);
+        assert_eq!(delivery.attempts, 2);
+        assert_eq!(endpoint.load(Ordering::Relaxed), 2);
+        // The body's precise 12.5s wins over the header's rounded value.
+        assert_eq!(started.elapsed().as_millis() / 100, 125);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn too_many_requests_without_retry_after_backs_off() {
+        let (url, endpoint) = serve(vec![(429, None)]).await;
+        let webhooks = webhooks(3);
+
+        let started = Instant::now();
+        let delivery = send(&webhooks, &hook(&url), b"{}").await;
+        assert!(!delivery.success);
+        assert_eq!(delivery.attempts, 3);
+        assert_eq!(delivery.status, Some(429));
+        assert_eq!(endpoint.received().len(), 3);
+        // 1s, then 2s.
+        assert!(started.elapsed() >= Duration::from_secs(3));
+        assert!(started.elapsed() < Duration::from_secs(4));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn network_errors_are_retried_and_logged() {
+        // Nothing listens on a port just released.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        drop(listener);
+        let webhooks = webhooks(2);
+
+        let delivery = send(&webhooks, &hook(&url), b"{}").await;
+        assert!(!delivery.success);
+        assert_eq!(delivery.attempts, 2);
+        assert_eq!(delivery.status, None);
+        assert!(delivery.error.is_some());
+        assert_eq!(logged(&webhooks).len(), 1);
+    }
+
+    #[test]
+    fn delivery_log_keeps_the_newest_entries() {
+        let webhooks = webhooks(1);
+        for i in 0..DELIVERY_LOG_SIZE + 5 {
+            webhooks.record(WebhookDelivery {
+                id: i.to_string(),
+                webhook_id: "hook-1".to_string(),
+                events: vec![WebhookEvent::TunnelDown],
+                timestamp: 0,
+                attempts: 1,
+                success: true,
+                status: Some(200),
+                error: None,
+            });
+        }
+        let log = logged(&webhooks);
+        assert_eq!(log.len(), DELIVERY_LOG_SIZE);
+        assert_eq!(log[0].id, "5");
+        assert_eq!(log.last().unwrap().id, (DELIVERY_LOG_SIZE + 4).to_string());
+    }
+}
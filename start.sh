@@ -2,29 +2,11 @@
 
 set -e
 
-# Start tailscaled daemon in the background
-tailscaled --tun=userspace-networking --state="$TAILSCALE_STATE" --socket=/var/run/tailscale/tailscaled.sock &
+# The API binary starts tailscaled, logs in with TAILSCALE_AUTHKEY and
+# restarts it if it dies (see MANAGE_TAILSCALED in the README).
+export MANAGE_TAILSCALED="${MANAGE_TAILSCALED:-true}"
 
-# Wait for tailscaled socket to be available
-sleep 3
+echo "[start.sh] Starting API server"
 
-# Authenticate and bring up tailscale
-tailscale --socket=/var/run/tailscale/tailscaled.sock up \
-  --authkey="${TAILSCALE_AUTHKEY}" \
-  --hostname="${TAILSCALE_HOSTNAME:-tailscale-discloud}" \
-  --accept-routes \
-  --accept-dns
-
-echo "[start.sh] Tailscale is up"
-
-# Change to the working directory so the API binary resolves relative paths
-# (./tunnels.json, ./public/) correctly.
-cd /home/tailscale
-
-# Start the tunnel manager API in the background
-./api &
-
-echo "[start.sh] API server started (working dir: $(pwd))"
-
-# Wait for all background processes
-wait
+# Replace the shell so the API receives container signals directly
+exec /home/tailscale/api "$@"